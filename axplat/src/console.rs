@@ -1,6 +1,17 @@
 //! Console input and output.
 
 use core::fmt::{Arguments, Result, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "persistent-log")]
+pub mod persistent_log;
+#[cfg(feature = "irq")]
+pub mod rx;
+pub mod sink;
+
+use self::sink::Level;
+#[cfg(feature = "irq")]
+use crate::irq::IrqMask;
 
 /// Console input and output interface.
 #[def_interface(gen_caller)]
@@ -12,20 +23,138 @@ pub trait ConsoleIf {
     ///
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize;
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    ///
+    /// The platform handles the IRQ by itself, and buffers the received bytes
+    /// as described in [`rx`].
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize>;
 }
 
-struct EarlyConsole;
+/// Writes to all sinks, or the sinks that accept the level if it is set.
+struct EarlyConsole(Option<Level>);
 
 impl Write for EarlyConsole {
     fn write_str(&mut self, s: &str) -> Result {
-        write_bytes(s.as_bytes());
+        match self.0 {
+            Some(level) => sink::write_with_level(level, s.as_bytes()),
+            None => sink::write(s.as_bytes()),
+        }
+        Ok(())
+    }
+}
+
+/// Writes to the platform console by the emergency writer.
+struct EmergencyConsole;
+
+impl Write for EmergencyConsole {
+    fn write_str(&mut self, s: &str) -> Result {
+        emergency_write(s.as_bytes());
         Ok(())
     }
 }
 
 /// Lock for console operations to prevent mixed output from concurrent execution
+///
+/// It does not mask IRQs on hosted platforms by itself (see
+/// [`set_irq_mask_fn`](crate::irq::set_irq_mask_fn)), only when taken by the
+/// print functions of this module.
 pub static CONSOLE_LOCK: kspin::SpinNoIrq<()> = kspin::SpinNoIrq::new(());
 
+/// The ID of the CPU holding [`CONSOLE_LOCK`] to print, plus one, or `0` if
+/// no CPU is printing.
+static CONSOLE_OWNER: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of attempts to take [`CONSOLE_LOCK`] in
+/// [`console_panic_print!`](crate::console_panic_print), before falling back
+/// to the emergency writer.
+const PANIC_LOCK_ATTEMPTS: usize = 1 << 20;
+
+static EMERGENCY_WRITE_FN: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the function that writes bytes to the platform console without taking
+/// any lock.
+///
+/// It is used when the console is re-entered on the same CPU (e.g., a panic
+/// in the middle of printing), or the console lock is held by another CPU for
+/// too long in panic handlers. Re-entry is only detected once the kernel has
+/// called [`set_this_cpu_id_fn`](crate::power::set_this_cpu_id_fn). It is set by platforms whose
+/// [`ConsoleIf::write_bytes`] takes a lock. Otherwise,
+/// [`ConsoleIf::write_bytes`] is used.
+pub fn set_emergency_write_fn(f: fn(&[u8])) {
+    EMERGENCY_WRITE_FN.store(f as usize, Ordering::Release);
+}
+
+fn emergency_write(bytes: &[u8]) {
+    let f = EMERGENCY_WRITE_FN.load(Ordering::Acquire);
+    if f == 0 {
+        write_bytes(bytes);
+        return;
+    }
+    // SAFETY: `EMERGENCY_WRITE_FN` is only set from a `fn(&[u8])`.
+    let f = unsafe { core::mem::transmute::<usize, fn(&[u8])>(f) };
+    f(bytes);
+}
+
+/// A guard of [`CONSOLE_LOCK`] that records the current CPU as the owner.
+struct ConsoleGuard<G> {
+    _guard: G,
+    _irq: IrqMask,
+}
+
+impl<G> Drop for ConsoleGuard<G> {
+    fn drop(&mut self) {
+        CONSOLE_OWNER.store(0, Ordering::Release);
+    }
+}
+
+/// Returns the value of [`CONSOLE_OWNER`] for the current CPU, or `None` if
+/// the current CPU is unknown.
+fn owner_id() -> Option<usize> {
+    crate::power::try_this_cpu_id().map(|cpu_id| cpu_id + 1)
+}
+
+/// Whether the current CPU is holding [`CONSOLE_LOCK`] to print.
+///
+/// The current CPU is found by [`this_cpu_id`](crate::power::this_cpu_id).
+/// If it is not set up by
+/// [`set_this_cpu_id_fn`](crate::power::set_this_cpu_id_fn), re-entry can
+/// not be told apart from printing on other CPUs, so it always returns
+/// `false` and the lock is taken as usual. A re-entry then spins on the lock,
+/// except in panic handlers, which give up after a while.
+fn is_reentered() -> bool {
+    owner_id().is_some_and(|id| CONSOLE_OWNER.load(Ordering::Acquire) == id)
+}
+
+/// Without IRQs, there is nothing to mask while holding the console locks.
+#[cfg(not(feature = "irq"))]
+struct IrqMask;
+
+#[cfg(not(feature = "irq"))]
+impl IrqMask {
+    fn new() -> Self {
+        Self
+    }
+}
+
+/// Takes [`CONSOLE_LOCK`] by `lock`, or returns `None` on re-entry.
+fn lock_console<G>(lock: impl FnOnce() -> Option<G>) -> Option<ConsoleGuard<G>> {
+    if is_reentered() {
+        return None;
+    }
+    let irq = IrqMask::new();
+    let guard = ConsoleGuard {
+        _guard: lock()?,
+        _irq: irq,
+    };
+    if let Some(id) = owner_id() {
+        CONSOLE_OWNER.store(id, Ordering::Release);
+    }
+    Some(guard)
+}
+
 /// Simple console print operation.
 #[macro_export]
 macro_rules! console_print {
@@ -37,15 +166,62 @@ macro_rules! console_print {
 /// Simple console print operation, with a newline.
 #[macro_export]
 macro_rules! console_println {
-    () => { $crate::ax_print!("\n") };
+    () => { $crate::console::__simple_print(format_args!("\n")) };
     ($($arg:tt)*) => {
         $crate::console::__simple_print(format_args!("{}\n", format_args!($($arg)*)));
     }
 }
 
+/// Console print operation for panic handlers, with a newline.
+///
+/// Unlike [`console_println!`](crate::console_println), it does not wait forever for the console lock
+/// held by other CPUs, and only writes to the platform console by the
+/// emergency writer (see [`set_emergency_write_fn`]) if it can not take the
+/// lock.
+///
+/// [`set_emergency_write_fn`]: crate::console::set_emergency_write_fn
+#[macro_export]
+macro_rules! console_panic_print {
+    () => { $crate::console::__panic_print(format_args!("\n")) };
+    ($($arg:tt)*) => {
+        $crate::console::__panic_print(format_args!("{}\n", format_args!($($arg)*)));
+    }
+}
+
+fn print(level: Option<Level>, fmt: Arguments) {
+    if let Some(_guard) = lock_console(|| Some(CONSOLE_LOCK.lock())) {
+        EarlyConsole(level).write_fmt(fmt).ok();
+    } else {
+        EmergencyConsole.write_fmt(fmt).ok();
+    }
+}
+
 #[doc(hidden)]
 pub fn __simple_print(fmt: Arguments) {
-    let _guard = CONSOLE_LOCK.lock();
-    EarlyConsole.write_fmt(fmt).unwrap();
-    drop(_guard);
+    print(None, fmt);
+}
+
+#[doc(hidden)]
+pub fn __panic_print(fmt: Arguments) {
+    // Other CPUs may have stopped while holding the lock, do not wait forever.
+    let guard = (!is_reentered()).then(|| {
+        (0..PANIC_LOCK_ATTEMPTS).find_map(|_| {
+            lock_console(|| CONSOLE_LOCK.try_lock()).or_else(|| {
+                core::hint::spin_loop();
+                None
+            })
+        })
+    });
+    if let Some(Some(_guard)) = guard {
+        EarlyConsole(None).write_fmt(fmt).ok();
+    } else {
+        EmergencyConsole.write_fmt(fmt).ok();
+    }
+}
+
+/// Prints a message of the given level to the console sinks that accept it.
+///
+/// See [`sink`] for details.
+pub fn print_with_level(level: Level, fmt: Arguments) {
+    print(Some(level), fmt);
 }
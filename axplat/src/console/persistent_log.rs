@@ -0,0 +1,366 @@
+//! Console log buffer that survives warm reboot.
+//!
+//! All console output is mirrored into a fixed physical memory region, which
+//! the platform must exclude from the allocatable RAM by
+//! [`MemIf::reserved_phys_ram_ranges`]. On platforms with a device tree, the
+//! region is a `/reserved-memory` child node compatible with
+//! `"arceos,persistent-log"` (see [`init_from_fdt`]).
+//!
+//! **The device tree must be provided with such a node**, e.g., by passing a
+//! modified DTB to the boot loader or QEMU (`-dtb`). The DTBs generated by
+//! QEMU do not have it, so the log is not kept on these platforms otherwise:
+//!
+//! ```text
+//! reserved-memory {
+//!     #address-cells = <2>;
+//!     #size-cells = <2>;
+//!     ranges;
+//!
+//!     persistent-log@88100000 {
+//!         compatible = "arceos,persistent-log";
+//!         reg = <0x0 0x88100000 0x0 0x100000>;
+//!     };
+//! };
+//! ```
+//!
+//! The region is split into two slots, each with a header holding a magic
+//! number and the boot sequence number. Every boot writes to the slot that is
+//! not holding the latest log, so after a warm reset, the log of the previous
+//! boot is kept intact and can be dumped by [`previous`] at any time.
+//!
+//! The buffer is written through the CPU cache. The log survives resets that
+//! keep the RAM content, but on some platforms, the last lines may be lost if
+//! the cache is not written back before resetting.
+//!
+//! [`MemIf::reserved_phys_ram_ranges`]: crate::mem::MemIf::reserved_phys_ram_ranges
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use memory_addr::PhysAddr;
+
+use super::sink::{self, ConsoleSink, Level};
+
+const LOG_MAGIC: u64 = u64::from_le_bytes(*b"AXPLOG01");
+
+/// The minimum size of the region, which holds two slots.
+pub const MIN_PERSISTENT_LOG_SIZE: usize = 2 * (HEADER_SIZE + 256);
+
+const HEADER_SIZE: usize = size_of::<Header>();
+
+/// The header at the beginning of each slot.
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    /// The boot sequence number, which increases by one on every boot.
+    seq: AtomicU64,
+    /// The size of the data following the header.
+    capacity: AtomicU64,
+    /// The number of bytes ever written. The data wraps around if it exceeds
+    /// the capacity.
+    written: AtomicU64,
+    /// The checksum of the fields above except `written`.
+    checksum: AtomicU64,
+}
+
+impl Header {
+    const fn checksum(seq: u64, capacity: u64) -> u64 {
+        LOG_MAGIC ^ seq.rotate_left(17) ^ capacity.rotate_left(41)
+    }
+
+    fn is_valid(&self, capacity: usize) -> bool {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.magic.load(Ordering::Acquire) == LOG_MAGIC
+            && self.capacity.load(Ordering::Relaxed) == capacity as u64
+            && self.checksum.load(Ordering::Relaxed) == Self::checksum(seq, capacity as u64)
+    }
+}
+
+/// One of the two slots in the region.
+#[derive(Clone, Copy)]
+struct Slot {
+    header: &'static Header,
+    data: *mut u8,
+    capacity: usize,
+}
+
+impl Slot {
+    /// # Safety
+    ///
+    /// `vaddr` must be 8-byte aligned, and point to `size` bytes of memory
+    /// that is only accessed by this module.
+    unsafe fn new(vaddr: usize, size: usize) -> Self {
+        Self {
+            header: unsafe { &*(vaddr as *const Header) },
+            data: (vaddr + HEADER_SIZE) as *mut u8,
+            capacity: size - HEADER_SIZE,
+        }
+    }
+
+    fn vaddr(&self) -> usize {
+        self.header as *const Header as usize
+    }
+
+    /// Returns the boot sequence number if the slot holds a valid log.
+    fn valid_seq(&self) -> Option<u64> {
+        self.header
+            .is_valid(self.capacity)
+            .then(|| self.header.seq.load(Ordering::Relaxed))
+    }
+
+    /// Resets the slot to hold the log of the given boot.
+    fn reset(&self, seq: u64) {
+        let header = self.header;
+        // Invalidate it first, in case of a reset in the middle.
+        header.magic.store(0, Ordering::Release);
+        header.seq.store(seq, Ordering::Relaxed);
+        header
+            .capacity
+            .store(self.capacity as u64, Ordering::Relaxed);
+        header.written.store(0, Ordering::Relaxed);
+        let checksum = Header::checksum(seq, self.capacity as u64);
+        header.checksum.store(checksum, Ordering::Relaxed);
+        header.magic.store(LOG_MAGIC, Ordering::Release);
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        // Reserve the space first, so that concurrent writers do not overlap.
+        let len = bytes.len() as u64;
+        let pos = self.header.written.fetch_add(len, Ordering::Relaxed);
+        // Only the last `capacity` bytes are kept.
+        let skip = bytes.len().saturating_sub(self.capacity);
+        for (i, &c) in bytes.iter().enumerate().skip(skip) {
+            let offset = (pos + i as u64) % self.capacity as u64;
+            unsafe { self.data.add(offset as usize).write_volatile(c) };
+        }
+    }
+
+    fn saved_log(&self) -> SavedLog {
+        let written = self.header.written.load(Ordering::Relaxed);
+        let data = unsafe { core::slice::from_raw_parts(self.data, self.capacity) };
+        let (older, newer) = if written <= self.capacity as u64 {
+            (&data[..written as usize], &data[..0])
+        } else {
+            let start = (written % self.capacity as u64) as usize;
+            (&data[start..], &data[..start])
+        };
+        SavedLog {
+            boot_seq: self.header.seq.load(Ordering::Relaxed),
+            written,
+            chunks: [older, newer],
+        }
+    }
+}
+
+/// The log of a previous boot.
+#[derive(Debug, Clone, Copy)]
+pub struct SavedLog {
+    /// The boot sequence number of the log.
+    pub boot_seq: u64,
+    /// The number of bytes written in that boot, including the lost ones.
+    pub written: u64,
+    chunks: [&'static [u8]; 2],
+}
+
+impl SavedLog {
+    /// Returns the kept log in two parts, in the order they were written.
+    ///
+    /// The first part may start in the middle of a line if some bytes were
+    /// lost.
+    pub fn chunks(&self) -> [&'static [u8]; 2] {
+        self.chunks
+    }
+
+    /// Returns the number of bytes that were overwritten, as the buffer
+    /// wrapped around.
+    pub fn lost(&self) -> u64 {
+        self.written - (self.chunks[0].len() + self.chunks[1].len()) as u64
+    }
+}
+
+/// Returns the size of each slot in a region of the given size.
+const fn slot_size(size: usize) -> usize {
+    (size / 2) & !7
+}
+
+/// The slots in use.
+struct Slots {
+    current: Slot,
+    previous: Option<Slot>,
+}
+
+impl Slots {
+    /// Picks the slot with the latest valid log as the previous one, and
+    /// resets the other one for the current boot.
+    ///
+    /// # Safety
+    ///
+    /// See [`Slot::new`].
+    unsafe fn setup(vaddr: usize, size: usize) -> Self {
+        let slot_size = slot_size(size);
+        let slots = unsafe {
+            [
+                Slot::new(vaddr, slot_size),
+                Slot::new(vaddr + slot_size, slot_size),
+            ]
+        };
+        let latest = (0..2)
+            .filter_map(|i| Some((slots[i].valid_seq()?, i)))
+            .max();
+        let (current, previous, seq) = match latest {
+            Some((seq, i)) => (slots[1 - i], Some(slots[i]), seq.wrapping_add(1)),
+            None => (slots[0], None, 1),
+        };
+        current.reset(seq);
+        Self { current, previous }
+    }
+}
+
+struct LogSink;
+
+impl ConsoleSink for LogSink {
+    fn write_bytes(&self, bytes: &[u8]) {
+        if let Some(slot) = slot(&CURRENT) {
+            slot.write(bytes);
+        }
+    }
+}
+
+/// The virtual addresses of the slots, or `0` if not present.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+static SLOT_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn slot(vaddr: &AtomicUsize) -> Option<Slot> {
+    match vaddr.load(Ordering::Acquire) {
+        0 => None,
+        // SAFETY: the slots are set up by `init`.
+        vaddr => Some(unsafe { Slot::new(vaddr, SLOT_SIZE.load(Ordering::Relaxed)) }),
+    }
+}
+
+/// Starts mirroring the console output into the given physical memory region.
+///
+/// The region must be reserved by the platform, and mapped in the linear
+/// mapping (see [`phys_to_virt`](crate::mem::phys_to_virt)). It can be
+/// called only once. Returns `false` if it has been called, the region is
+/// smaller than [`MIN_PERSISTENT_LOG_SIZE`], or there is no room for more
+/// console sinks.
+pub fn init(paddr: PhysAddr, size: usize) -> bool {
+    let vaddr = crate::mem::phys_to_virt(paddr.align_up(8usize)).as_usize();
+    let size = size.saturating_sub(paddr.align_offset(8usize));
+    if size < MIN_PERSISTENT_LOG_SIZE
+        || SLOT_SIZE
+            .compare_exchange(0, slot_size(size), Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return false;
+    }
+    // SAFETY: the region is reserved for the log.
+    let slots = unsafe { Slots::setup(vaddr, size) };
+    if let Some(previous) = slots.previous {
+        PREVIOUS.store(previous.vaddr(), Ordering::Release);
+    }
+    CURRENT.store(slots.current.vaddr(), Ordering::Release);
+    sink::register(&LogSink, Level::Trace).is_some()
+}
+
+/// Starts mirroring the console output into the `/reserved-memory` region
+/// compatible with `"arceos,persistent-log"` in the device tree.
+///
+/// Returns `false` if there is no such region (see the [module-level
+/// documentation](self)), or [`init`] fails.
+#[cfg(feature = "fdt")]
+pub fn init_from_fdt() -> bool {
+    match crate::fdt::reserved_region("arceos,persistent-log") {
+        Some((start, size)) => init(PhysAddr::from(start), size),
+        None => false,
+    }
+}
+
+/// Returns the log of the previous boot, or `None` if there is no valid log
+/// (e.g., after a cold boot), or [`init`] has not been called.
+pub fn previous() -> Option<SavedLog> {
+    slot(&PREVIOUS).map(|slot| slot.saved_log())
+}
+
+/// Writes the log of the previous boot to the platform console, between two
+/// banner lines.
+///
+/// The log is not written to other console sinks, so it is not mirrored into
+/// the log of the current boot. Returns `false` if there is no previous log.
+pub fn dump_previous() -> bool {
+    struct PlatformConsole(fn(&[u8]));
+
+    impl Write for PlatformConsole {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            (self.0)(s.as_bytes());
+            Ok(())
+        }
+    }
+
+    let Some(log) = previous() else {
+        return false;
+    };
+    let guard = super::lock_console(|| Some(super::CONSOLE_LOCK.lock()));
+    let mut console = match guard {
+        Some(_) => PlatformConsole(super::write_bytes),
+        None => PlatformConsole(super::emergency_write),
+    };
+    let (seq, lost) = (log.boot_seq, log.lost());
+    writeln!(console, "---- log of boot {seq} ({lost} bytes lost) ----").ok();
+    for chunk in log.chunks() {
+        (console.0)(chunk);
+    }
+    writeln!(console, "\n---- end of boot {seq} ----").ok();
+    true
+}
+
+/// Returns the boot sequence number of the current boot, which is one more
+/// than the previous one, or `None` if [`init`] has not been called.
+pub fn boot_seq() -> Option<u64> {
+    slot(&CURRENT).map(|slot| slot.header.seq.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MIN_PERSISTENT_LOG_SIZE, Slots};
+
+    #[repr(align(8))]
+    struct Region([u8; MIN_PERSISTENT_LOG_SIZE]);
+
+    fn text(log: &super::SavedLog) -> Vec<u8> {
+        log.chunks().concat()
+    }
+
+    #[test]
+    fn survive_reboot() {
+        // The slots refer to the region as `'static`.
+        let region = Box::leak(Box::new(Region([0xaa; MIN_PERSISTENT_LOG_SIZE])));
+        let (vaddr, size) = (region.0.as_mut_ptr() as usize, region.0.len());
+
+        // Cold boot, garbage in the region.
+        let slots = unsafe { Slots::setup(vaddr, size) };
+        assert!(slots.previous.is_none());
+        slots.current.write(b"boot 1\n");
+
+        let slots = unsafe { Slots::setup(vaddr, size) };
+        let prev = slots.previous.unwrap().saved_log();
+        assert_eq!((prev.boot_seq, prev.lost()), (1, 0));
+        assert_eq!(text(&prev), b"boot 1\n");
+        // Wrap around.
+        for i in 0..100u8 {
+            slots.current.write(&[b'0' + i % 10; 3]);
+        }
+        slots.current.write(b"end");
+
+        let slots = unsafe { Slots::setup(vaddr, size) };
+        let prev = slots.previous.unwrap().saved_log();
+        assert_eq!((prev.boot_seq, prev.written), (2, 303));
+        let text = text(&prev);
+        assert_eq!(text.len(), slots.current.capacity);
+        assert_eq!(prev.lost(), 303 - text.len() as u64);
+        assert!(text.ends_with(b"999end"));
+        assert!(slots.current.saved_log().chunks()[0].is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Interrupt-driven console input.
+//!
+//! By default, [`read_bytes`] polls the UART. Platforms that support the
+//! interrupt-driven mode do so only if the kernel opts in, usually by a
+//! `console-irq` feature of the platform crate. Then they register a handler
+//! for the console IRQ (returned by [`irq_num`]) at the later stage, which
+//! drains the UART by [`push`] into a lock-free ring buffer, then calls
+//! [`notify`]. Once [`enable`] is called, their [`read_bytes`] only reads
+//! from the buffer by [`read`].
+//!
+//! The kernel registers a hook by [`set_notify_fn`] to wake up the tasks
+//! waiting for input, so that they do not have to poll the console.
+//!
+//! [`read_bytes`]: super::read_bytes
+//! [`irq_num`]: super::irq_num
+
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+
+/// The capacity of the receive buffer. Bytes received when it is full are
+/// dropped.
+pub const RX_BUFFER_SIZE: usize = 1024;
+
+/// A lock-free ring buffer of received bytes.
+///
+/// It allows only one producer at a time (the IRQ handler, which holds the
+/// UART lock), but any number of concurrent consumers.
+struct RxBuffer<const N: usize> {
+    buf: [AtomicU8; N],
+    /// The number of bytes ever pushed.
+    head: AtomicUsize,
+    /// The number of bytes ever popped.
+    tail: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl<const N: usize> RxBuffer<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [const { AtomicU8::new(0) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.head.load(Ordering::Acquire).wrapping_sub(tail)
+    }
+
+    fn push(&self, c: u8) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(self.tail.load(Ordering::Acquire)) >= N {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.buf[head % N].store(c, Ordering::Relaxed);
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    fn pop(&self) -> Option<u8> {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            if tail == self.head.load(Ordering::Acquire) {
+                return None;
+            }
+            // The slot is not overwritten until `tail` moves past it.
+            let c = self.buf[tail % N].load(Ordering::Relaxed);
+            if self
+                .tail
+                .compare_exchange(
+                    tail,
+                    tail.wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                return Some(c);
+            }
+        }
+    }
+}
+
+static RX_BUFFER: RxBuffer<RX_BUFFER_SIZE> = RxBuffer::new();
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static NOTIFY_FN: AtomicUsize = AtomicUsize::new(0);
+
+/// Switches the console input to the interrupt-driven mode.
+///
+/// It is called by the platform after registering the console IRQ handler.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Whether the console input is interrupt-driven.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Pushes a received byte into the buffer.
+///
+/// It is called by the console IRQ handler of the platform, which must not
+/// run concurrently with itself. It returns `false` if the buffer is full and
+/// the byte is dropped.
+pub fn push(c: u8) -> bool {
+    RX_BUFFER.push(c)
+}
+
+/// Calls the hook set by [`set_notify_fn`] if there is data available.
+///
+/// It is called by the console IRQ handler of the platform after pushing all
+/// the received bytes.
+pub fn notify() {
+    let f = NOTIFY_FN.load(Ordering::Acquire);
+    if f != 0 && RX_BUFFER.len() > 0 {
+        // SAFETY: `NOTIFY_FN` is only set from a `fn()`.
+        let f = unsafe { core::mem::transmute::<usize, fn()>(f) };
+        f();
+    }
+}
+
+/// Reads bytes from the buffer into the given mutable slice.
+///
+/// Returns the number of bytes read, which is `0` if no data is available.
+pub fn read(bytes: &mut [u8]) -> usize {
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        match RX_BUFFER.pop() {
+            Some(c) => *byte = c,
+            None => return i,
+        }
+    }
+    bytes.len()
+}
+
+/// Returns the number of bytes available in the buffer.
+pub fn available() -> usize {
+    RX_BUFFER.len()
+}
+
+/// Returns the number of bytes dropped because the buffer was full.
+pub fn dropped() -> usize {
+    RX_BUFFER.dropped.load(Ordering::Relaxed)
+}
+
+/// Sets the hook to be called in the console IRQ handler when data is
+/// available.
+///
+/// It is called in the interrupt context, so it should only wake up the
+/// waiting tasks, rather than reading the data by itself.
+pub fn set_notify_fn(f: fn()) {
+    NOTIFY_FN.store(f as usize, Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RxBuffer;
+
+    #[test]
+    fn push_and_pop() {
+        let buf = RxBuffer::<4>::new();
+        assert_eq!(buf.pop(), None);
+        for round in 0..3u8 {
+            for i in 0..4 {
+                assert!(buf.push(round * 4 + i));
+            }
+            assert!(!buf.push(0xff));
+            assert_eq!(buf.len(), 4);
+            for i in 0..4 {
+                assert_eq!(buf.pop(), Some(round * 4 + i));
+            }
+            assert_eq!(buf.pop(), None);
+        }
+        assert_eq!(buf.dropped.load(core::sync::atomic::Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,232 @@
+//! Multiple console output sinks.
+//!
+//! The console output of the platform ([`ConsoleIf::write_bytes`]) is always
+//! the first sink, which is available since the early boot. Additional sinks
+//! (e.g., a second UART, a framebuffer text console or a memory log buffer)
+//! can be registered at runtime by [`register`], each with its own maximum
+//! log level.
+//!
+//! [`console_print!`](crate::console_print) and
+//! [`console_println!`](crate::console_println) write to all sinks by
+//! [`write`]. The kernel logger should use [`write_with_level`] (or
+//! [`print_with_level`](super::print_with_level)), so that each sink only
+//! receives messages up to its level.
+//!
+//! [`ConsoleIf::write_bytes`]: super::ConsoleIf::write_bytes
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+use super::IrqMask;
+
+/// The maximum number of sinks that can be registered, besides the platform
+/// console.
+pub const MAX_CONSOLE_SINKS: usize = 8;
+
+/// A console output sink.
+pub trait ConsoleSink: Sync {
+    /// Writes given bytes to the sink.
+    ///
+    /// It may be called in any context, including interrupt handlers, so it
+    /// must not block.
+    fn write_bytes(&self, bytes: &[u8]);
+}
+
+/// The level of a log message, in the same order as `log::Level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Level {
+    /// Very serious errors.
+    Error = 1,
+    /// Hazardous situations.
+    Warn,
+    /// Useful information.
+    Info,
+    /// Lower priority information.
+    Debug,
+    /// Very low priority, often extremely verbose, information.
+    Trace,
+}
+
+impl Level {
+    const fn from_u8(level: u8) -> Self {
+        match level {
+            1 => Self::Error,
+            2 => Self::Warn,
+            3 => Self::Info,
+            4 => Self::Debug,
+            _ => Self::Trace,
+        }
+    }
+}
+
+/// The identifier of a sink, used to unregister it or change its level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinkId(usize);
+
+impl SinkId {
+    /// The console output of the platform, which can not be unregistered.
+    pub const PLATFORM: Self = Self(usize::MAX);
+}
+
+#[derive(Clone, Copy)]
+struct Sink {
+    sink: &'static dyn ConsoleSink,
+    max_level: Level,
+}
+
+/// A table of registered sinks.
+///
+/// Sinks are copied out before being written, so that the lock is not held
+/// while writing to slow sinks.
+struct SinkTable<const N: usize> {
+    sinks: SpinNoIrq<[Option<Sink>; N]>,
+    len: AtomicUsize,
+}
+
+impl<const N: usize> SinkTable<N> {
+    const fn new() -> Self {
+        Self {
+            sinks: SpinNoIrq::new([None; N]),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    fn register(&self, sink: &'static dyn ConsoleSink, max_level: Level) -> Option<SinkId> {
+        let _irq = IrqMask::new();
+        let mut sinks = self.sinks.lock();
+        let idx = sinks.iter().position(Option::is_none)?;
+        sinks[idx] = Some(Sink { sink, max_level });
+        self.len.fetch_add(1, Ordering::Release);
+        Some(SinkId(idx))
+    }
+
+    fn unregister(&self, id: SinkId) -> bool {
+        let _irq = IrqMask::new();
+        let mut sinks = self.sinks.lock();
+        match sinks.get_mut(id.0).and_then(Option::take) {
+            Some(_) => {
+                self.len.fetch_sub(1, Ordering::Release);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_max_level(&self, id: SinkId, max_level: Level) -> bool {
+        let _irq = IrqMask::new();
+        let mut sinks = self.sinks.lock();
+        match sinks.get_mut(id.0).and_then(Option::as_mut) {
+            Some(sink) => {
+                sink.max_level = max_level;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes to the sinks whose maximum level is not lower than `level`, or
+    /// all sinks if `level` is `None`.
+    fn write(&self, level: Option<Level>, bytes: &[u8]) {
+        if self.len.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let sinks = {
+            let _irq = IrqMask::new();
+            *self.sinks.lock()
+        };
+        for sink in sinks.iter().flatten() {
+            if level.is_none_or(|level| level <= sink.max_level) {
+                sink.sink.write_bytes(bytes);
+            }
+        }
+    }
+}
+
+static SINKS: SinkTable<MAX_CONSOLE_SINKS> = SinkTable::new();
+
+static PLATFORM_MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Trace as u8);
+
+/// Registers a sink, which receives messages up to `max_level`.
+///
+/// Returns `None` if there are already [`MAX_CONSOLE_SINKS`] sinks.
+pub fn register(sink: &'static dyn ConsoleSink, max_level: Level) -> Option<SinkId> {
+    SINKS.register(sink, max_level)
+}
+
+/// Unregisters a sink.
+///
+/// Returns `false` if it is not registered, or is [`SinkId::PLATFORM`].
+pub fn unregister(id: SinkId) -> bool {
+    SINKS.unregister(id)
+}
+
+/// Sets the maximum level of messages that a sink receives.
+///
+/// Returns `false` if the sink is not registered.
+pub fn set_max_level(id: SinkId, max_level: Level) -> bool {
+    if id == SinkId::PLATFORM {
+        PLATFORM_MAX_LEVEL.store(max_level as u8, Ordering::Relaxed);
+        return true;
+    }
+    SINKS.set_max_level(id, max_level)
+}
+
+/// Writes given bytes to all sinks, regardless of their levels.
+pub fn write(bytes: &[u8]) {
+    super::write_bytes(bytes);
+    SINKS.write(None, bytes);
+}
+
+/// Writes a message of the given level to the sinks that accept it.
+pub fn write_with_level(level: Level, bytes: &[u8]) {
+    if level <= Level::from_u8(PLATFORM_MAX_LEVEL.load(Ordering::Relaxed)) {
+        super::write_bytes(bytes);
+    }
+    SINKS.write(Some(level), bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{ConsoleSink, Level, SinkId, SinkTable};
+
+    struct CountingSink(AtomicUsize);
+
+    impl ConsoleSink for CountingSink {
+        fn write_bytes(&self, bytes: &[u8]) {
+            self.0.fetch_add(bytes.len(), Ordering::Relaxed);
+        }
+    }
+
+    static A: CountingSink = CountingSink(AtomicUsize::new(0));
+    static B: CountingSink = CountingSink(AtomicUsize::new(0));
+
+    fn counts() -> (usize, usize) {
+        (A.0.load(Ordering::Relaxed), B.0.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn register_and_filter() {
+        let table = SinkTable::<2>::new();
+        let a = table.register(&A, Level::Trace).unwrap();
+        let b = table.register(&B, Level::Warn).unwrap();
+        assert!(table.register(&A, Level::Info).is_none());
+
+        table.write(None, b"ab");
+        table.write(Some(Level::Error), b"c");
+        table.write(Some(Level::Info), b"de");
+        assert_eq!(counts(), (5, 3));
+
+        assert!(table.set_max_level(b, Level::Debug));
+        table.write(Some(Level::Debug), b"f");
+        assert!(table.unregister(a));
+        assert!(!table.unregister(a));
+        assert!(!table.unregister(SinkId::PLATFORM));
+        table.write(None, b"g");
+        assert_eq!(counts(), (6, 5));
+        assert_eq!(table.register(&A, Level::Info), Some(a));
+    }
+}
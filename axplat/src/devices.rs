@@ -0,0 +1,219 @@
+//! Device discovery.
+//!
+//! Platforms describe the devices that drivers may use by [`DeviceIf`], so
+//! that drivers do not need to read the configuration of a specific platform.
+//! The devices are taken from the device tree (see `axplat::fdt::devices`)
+//! if present, or built from the static configuration by [`DeviceList`].
+
+use core::fmt;
+use core::ops::Deref;
+
+use crate::mem::RawRange;
+
+/// The maximum number of devices in a [`DeviceList`].
+pub const MAX_DEVICES: usize = 64;
+
+/// The kind of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DeviceKind {
+    /// A VirtIO device with the MMIO transport.
+    VirtioMmio,
+    /// A PCIe host bridge with the ECAM configuration space.
+    PciHostBridge,
+    /// A real-time clock.
+    Rtc,
+    /// A serial port.
+    Uart,
+    /// A GPIO controller.
+    Gpio,
+}
+
+/// A device on the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Device {
+    /// The kind of the device.
+    pub kind: DeviceKind,
+    /// The model of the device, as the most specific `compatible` string in
+    /// the device tree, e.g., `"arm,pl011"` or `"ns16550a"`.
+    pub compatible: &'static str,
+    /// The MMIO window of the device, in the format of (`base_paddr`, `size`).
+    ///
+    /// For a PCIe host bridge, it is the ECAM configuration space.
+    pub mmio: RawRange,
+    /// The IRQ number of the device as used by `axplat::irq`, if known.
+    pub irq: Option<usize>,
+}
+
+impl Device {
+    /// Creates a new device.
+    pub const fn new(
+        kind: DeviceKind,
+        compatible: &'static str,
+        mmio: RawRange,
+        irq: Option<usize>,
+    ) -> Self {
+        Self {
+            kind,
+            compatible,
+            mmio,
+            irq,
+        }
+    }
+}
+
+/// A list of devices with fixed capacity, which can be built in const
+/// context from the static configuration.
+///
+/// # Example
+///
+/// ```rust
+/// # use axplat::devices::{Device, DeviceKind, DeviceList};
+/// const VIRTIO_MMIO_RANGES: &[(usize, usize)] = &[(0x1000_1000, 0x1000), (0x1000_2000, 0x1000)];
+///
+/// static DEVICES: DeviceList = DeviceList::new()
+///     .with(Device::new(DeviceKind::Uart, "ns16550a", (0x1000_0000, 0x1000), Some(10)))
+///     .with_each(DeviceKind::VirtioMmio, "virtio,mmio", VIRTIO_MMIO_RANGES);
+///
+/// assert_eq!(DEVICES.len(), 3);
+/// ```
+#[derive(Clone)]
+pub struct DeviceList {
+    devices: [Device; MAX_DEVICES],
+    len: usize,
+}
+
+impl DeviceList {
+    /// Creates an empty list.
+    pub const fn new() -> Self {
+        const EMPTY: Device = Device::new(DeviceKind::Uart, "", (0, 0), None);
+        Self {
+            devices: [EMPTY; MAX_DEVICES],
+            len: 0,
+        }
+    }
+
+    /// Appends a device to the list.
+    ///
+    /// Returns `false` if the list is full.
+    pub const fn push(&mut self, device: Device) -> bool {
+        if self.len == MAX_DEVICES {
+            return false;
+        }
+        self.devices[self.len] = device;
+        self.len += 1;
+        true
+    }
+
+    /// Returns the list with a device appended.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list is full, which is a compile error in const context.
+    pub const fn with(mut self, device: Device) -> Self {
+        assert!(self.push(device), "too many devices");
+        self
+    }
+
+    /// Returns the list with a device of the given kind and model appended for
+    /// each MMIO window, without IRQ.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list is full, which is a compile error in const context.
+    pub const fn with_each(
+        mut self,
+        kind: DeviceKind,
+        compatible: &'static str,
+        mmio: &[RawRange],
+    ) -> Self {
+        let mut i = 0;
+        while i < mmio.len() {
+            self = self.with(Device::new(kind, compatible, mmio[i], None));
+            i += 1;
+        }
+        self
+    }
+}
+
+impl Default for DeviceList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for DeviceList {
+    type Target = [Device];
+
+    fn deref(&self) -> &[Device] {
+        &self.devices[..self.len]
+    }
+}
+
+impl<'a> IntoIterator for &'a DeviceList {
+    type Item = &'a Device;
+    type IntoIter = core::slice::Iter<'a, Device>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for DeviceList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Device discovery interface.
+#[def_interface(gen_caller)]
+pub trait DeviceIf {
+    /// Returns the devices on the platform that drivers may use.
+    fn devices() -> &'static [Device];
+}
+
+/// Returns the devices of the given kind, in the order of [`devices`].
+pub fn devices_of(kind: DeviceKind) -> impl Iterator<Item = &'static Device> {
+    devices().iter().filter(move |dev| dev.kind == kind)
+}
+
+/// Returns the first device of the given kind.
+pub fn find(kind: DeviceKind) -> Option<&'static Device> {
+    devices_of(kind).next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Device, DeviceKind, DeviceList, MAX_DEVICES};
+
+    #[test]
+    fn device_list() {
+        const RANGES: &[(usize, usize)] = &[(0x1000, 0x200), (0x1200, 0x200)];
+        const LIST: DeviceList = DeviceList::new()
+            .with(Device::new(
+                DeviceKind::Rtc,
+                "arm,pl031",
+                (0x0901_0000, 0x1000),
+                Some(34),
+            ))
+            .with_each(DeviceKind::VirtioMmio, "virtio,mmio", RANGES);
+        assert_eq!(
+            LIST.iter()
+                .map(|d| (d.kind, d.mmio, d.irq))
+                .collect::<Vec<_>>(),
+            [
+                (DeviceKind::Rtc, (0x0901_0000, 0x1000), Some(34)),
+                (DeviceKind::VirtioMmio, (0x1000, 0x200), None),
+                (DeviceKind::VirtioMmio, (0x1200, 0x200), None),
+            ]
+        );
+
+        let mut list = LIST;
+        let dev = Device::new(DeviceKind::Gpio, "arm,pl061", (0x0903_0000, 0x1000), None);
+        for _ in LIST.len()..MAX_DEVICES {
+            assert!(list.push(dev));
+        }
+        assert!(!list.push(dev));
+        assert_eq!(list.len(), MAX_DEVICES);
+    }
+}
@@ -0,0 +1,827 @@
+//! Platform information from the flattened device tree (FDT).
+//!
+//! Platforms that are booted with a device tree blob (DTB) call [`init`] at
+//! the early stage, then use the information parsed from it to implement
+//! [`MemIf`](crate::mem::MemIf), [`PowerIf::cpu_num`](crate::power::PowerIf::cpu_num),
+//! the [CPU topology](crate::power::topology) and [`DeviceIf`](crate::devices::DeviceIf).
+//! Every getter returns `None` if no valid DTB is present, so that platforms
+//! can fall back to their static configuration.
+
+use ::fdt::node::FdtNode;
+use lazyinit::LazyInit;
+use memory_addr::{PhysAddr, align_down_4k, align_up_4k};
+
+use crate::devices::{Device, DeviceKind, DeviceList};
+use crate::mem::RawRange;
+use crate::power::topology::{CpuTopology, MAX_TOPOLOGY_CPUS};
+
+pub use ::fdt::{Fdt, FdtError};
+
+/// The maximum number of RAM ranges recorded from the device tree.
+pub const MAX_RAM_RANGES: usize = 16;
+/// The maximum number of reserved memory ranges recorded from the device tree.
+pub const MAX_RESERVED_RANGES: usize = 32;
+/// The maximum number of MMIO ranges recorded from the device tree.
+pub const MAX_MMIO_RANGES: usize = 64;
+
+/// The maximum depth of bus nodes to look for MMIO ranges.
+const MAX_BUS_DEPTH: usize = 4;
+
+/// The maximum depth of the nodes in `/cpus/cpu-map`.
+const MAX_CPU_MAP_DEPTH: usize = 4;
+
+/// The kinds of devices recognized by their `compatible` strings.
+const COMPATIBLE_KINDS: &[(&str, DeviceKind)] = &[
+    ("virtio,mmio", DeviceKind::VirtioMmio),
+    ("pci-host-ecam-generic", DeviceKind::PciHostBridge),
+    ("arm,pl031", DeviceKind::Rtc),
+    ("google,goldfish-rtc", DeviceKind::Rtc),
+    ("loongson,ls7a-rtc", DeviceKind::Rtc),
+    ("arm,pl011", DeviceKind::Uart),
+    ("ns16550a", DeviceKind::Uart),
+    ("ns16550", DeviceKind::Uart),
+    ("snps,dw-apb-uart", DeviceKind::Uart),
+    ("arm,pl061", DeviceKind::Gpio),
+    ("sifive,gpio0", DeviceKind::Gpio),
+];
+
+/// A sorted list of non-overlapping ranges with fixed capacity.
+struct RangeList<const N: usize> {
+    ranges: [RawRange; N],
+    len: usize,
+    overflow: bool,
+}
+
+impl<const N: usize> RangeList<N> {
+    const fn new() -> Self {
+        Self {
+            ranges: [(0, 0); N],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Inserts a range, merging it with the overlapping or adjacent ones.
+    fn insert(&mut self, (start, size): RawRange) {
+        if size == 0 {
+            return;
+        }
+        let mut start = start;
+        let mut end = start.saturating_add(size);
+        // Remove all ranges that touch the new one, and extend it to cover them.
+        let mut i = 0;
+        while i < self.len {
+            let (s, e) = (self.ranges[i].0, self.ranges[i].0 + self.ranges[i].1);
+            if s <= end && start <= e {
+                start = start.min(s);
+                end = end.max(e);
+                self.ranges.copy_within(i + 1..self.len, i);
+                self.len -= 1;
+            } else {
+                i += 1;
+            }
+        }
+        if self.len == N {
+            self.overflow = true;
+            return;
+        }
+        let pos = self.ranges[..self.len].partition_point(|r| r.0 < start);
+        self.ranges.copy_within(pos..self.len, pos + 1);
+        self.ranges[pos] = (start, end - start);
+        self.len += 1;
+    }
+
+    /// Returns the ranges, or `None` if some ranges were dropped for the lack
+    /// of capacity, or there is no range at all.
+    fn get(&self) -> Option<&[RawRange]> {
+        if self.overflow || self.len == 0 {
+            None
+        } else {
+            Some(&self.ranges[..self.len])
+        }
+    }
+}
+
+/// Platform information parsed from the device tree.
+struct FdtInfo {
+    fdt: Fdt<'static>,
+    ram: RangeList<MAX_RAM_RANGES>,
+    reserved: RangeList<MAX_RESERVED_RANGES>,
+    mmio: RangeList<MAX_MMIO_RANGES>,
+    devices: DeviceList,
+    cpus: [CpuTopology; MAX_TOPOLOGY_CPUS],
+    cpu_num: usize,
+}
+
+static FDT_INFO: LazyInit<FdtInfo> = LazyInit::new();
+
+/// Extends the range to 4K-aligned boundaries, so that it covers whole pages.
+fn align_range_4k(start: usize, size: usize) -> RawRange {
+    let aligned = align_down_4k(start);
+    (aligned, align_up_4k(start + size) - aligned)
+}
+
+fn is_device_type(node: FdtNode, ty: &str) -> bool {
+    node.property("device_type")
+        .and_then(|p| p.as_str())
+        .is_some_and(|s| s == ty)
+}
+
+fn is_enabled(node: FdtNode) -> bool {
+    node.property("status")
+        .and_then(|p| p.as_str())
+        .is_none_or(|s| s == "okay" || s == "ok")
+}
+
+/// Reads a big-endian number of `cells` 32-bit cells.
+fn read_cells(data: &[u8], cells: usize) -> Option<usize> {
+    if cells > 2 || data.len() < cells * 4 {
+        return None;
+    }
+    let (cells, _) = data[..cells * 4].as_chunks::<4>();
+    Some(
+        cells
+            .iter()
+            .fold(0u64, |acc, &c| (acc << 32) | u32::from_be_bytes(c) as u64) as usize,
+    )
+}
+
+/// Iterates over the `(child_addr, parent_addr, size)` entries of the
+/// `ranges` property of a bus node.
+///
+/// The child address is `None` if it can not be represented by a `usize`
+/// (e.g., PCI addresses with 3 cells).
+fn bus_ranges<'a>(
+    node: FdtNode<'_, 'a>,
+    parent_addr_cells: usize,
+) -> impl Iterator<Item = (Option<usize>, usize, usize)> + 'a {
+    let child = node.cell_sizes();
+    let entry_len = (child.address_cells + parent_addr_cells + child.size_cells) * 4;
+    let data = node.property("ranges").map_or(&[][..], |p| p.value);
+    data.chunks_exact(entry_len.max(1))
+        .filter_map(move |entry| {
+            let (child_addr, rest) = entry.split_at(child.address_cells * 4);
+            let (parent_addr, size) = rest.split_at(parent_addr_cells * 4);
+            Some((
+                read_cells(child_addr, child.address_cells),
+                read_cells(parent_addr, parent_addr_cells)?,
+                read_cells(size, child.size_cells)?,
+            ))
+        })
+}
+
+/// Returns the kind and the most specific `compatible` string of a device
+/// recognized by [`COMPATIBLE_KINDS`].
+fn device_kind(node: FdtNode<'_, 'static>) -> Option<(DeviceKind, &'static str)> {
+    let compatible = node.compatible()?;
+    let kind = compatible.all().find_map(|c| {
+        COMPATIBLE_KINDS
+            .iter()
+            .find(|(s, _)| *s == c)
+            .map(|&(_, kind)| kind)
+    })?;
+    Some((kind, compatible.first()))
+}
+
+/// Returns the first interrupt of `node` as the IRQ number used by the
+/// platform, where `irq_parent` is its interrupt controller.
+///
+/// GIC interrupts are specified by 3 cells (type, number, flags), and are
+/// translated to interrupt IDs: SPIs start from 32 and PPIs from 16. For other
+/// interrupt controllers, the IRQ number is the first cell.
+fn first_irq(node: FdtNode, irq_parent: FdtNode) -> Option<usize> {
+    let cells = irq_parent.property("#interrupt-cells")?.as_usize()?;
+    let data = node.property("interrupts")?.value;
+    if cells == 0 || data.len() < cells * 4 {
+        return None;
+    }
+    let (spec, _) = data[..cells * 4].as_chunks::<4>();
+    let cell = |i: usize| u32::from_be_bytes(spec[i]) as usize;
+    let is_gic = irq_parent
+        .compatible()
+        .is_some_and(|c| c.all().any(|s| s.starts_with("arm,") && s.contains("gic")));
+    if !is_gic || cells < 3 {
+        return Some(cell(0));
+    }
+    match cell(0) {
+        0 => Some(cell(1) + 32),
+        1 => Some(cell(1) + 16),
+        _ => None,
+    }
+}
+
+/// Collects MMIO ranges and devices of the children of `node`, whose
+/// addresses are translated to CPU physical addresses by `translate`.
+///
+/// `irq_parent` is the phandle of the interrupt controller inherited from the
+/// ancestors (the `interrupt-parent` property).
+fn collect_devices(
+    node: FdtNode<'_, 'static>,
+    depth: usize,
+    translate: &dyn Fn(usize) -> Option<usize>,
+    irq_parent: Option<usize>,
+    info: &mut FdtInfo,
+) {
+    let addr_cells = node.cell_sizes().address_cells;
+    for child in node.children() {
+        if is_device_type(child, "memory")
+            || is_device_type(child, "cpu")
+            || ["cpus", "chosen", "aliases", "reserved-memory"].contains(&child.name)
+        {
+            continue;
+        }
+        let irq_parent = child
+            .property("interrupt-parent")
+            .and_then(|p| p.as_usize())
+            .or(irq_parent);
+        let mut first_reg = None;
+        if let Some(reg) = child.reg() {
+            for r in reg {
+                let (Some(paddr), Some(size)) = (translate(r.starting_address as usize), r.size)
+                else {
+                    continue;
+                };
+                first_reg.get_or_insert((paddr, size));
+                info.mmio.insert(align_range_4k(paddr, size));
+            }
+        }
+        if let (Some((kind, compatible)), Some(mmio)) = (device_kind(child), first_reg) {
+            if is_enabled(child) {
+                let irq = irq_parent
+                    .and_then(|phandle| info.fdt.find_phandle(phandle as u32))
+                    .and_then(|parent| first_irq(child, parent));
+                info.devices.push(Device::new(kind, compatible, mmio, irq));
+            }
+        }
+
+        if is_device_type(child, "pci") {
+            // The address windows of the PCI host bridge.
+            for (_, paddr, size) in bus_ranges(child, addr_cells) {
+                if let Some(paddr) = translate(paddr) {
+                    info.mmio.insert(align_range_4k(paddr, size));
+                }
+            }
+        } else if child.property("ranges").is_some() && depth < MAX_BUS_DEPTH {
+            // A bus node, e.g., `simple-bus`. An empty `ranges` means that the
+            // child address space is identical to the parent's.
+            let identity = child.property("ranges").is_some_and(|p| p.value.is_empty());
+            let child_translate = |addr: usize| {
+                if identity {
+                    return translate(addr);
+                }
+                bus_ranges(child, addr_cells).find_map(|(child_base, parent_base, size)| {
+                    let child_base = child_base?;
+                    if (child_base..child_base + size).contains(&addr) {
+                        translate(addr - child_base + parent_base)
+                    } else {
+                        None
+                    }
+                })
+            };
+            collect_devices(child, depth + 1, &child_translate, irq_parent, info);
+        }
+    }
+}
+
+/// Applies the locations in the `cpu-map` node (or its child) to the CPUs,
+/// whose phandles are given in `phandles`.
+fn apply_cpu_map(
+    node: FdtNode,
+    depth: usize,
+    location: CpuTopology,
+    phandles: &[Option<usize>],
+    cpus: &mut [CpuTopology],
+) {
+    for child in node.children() {
+        let mut location = location;
+        let name = child.name.split('@').next().unwrap_or_default();
+        let (field, index) = if let Some(i) = name.strip_prefix("socket") {
+            (&mut location.package, i)
+        } else if let Some(i) = name.strip_prefix("cluster") {
+            (&mut location.cluster, i)
+        } else if let Some(i) = name.strip_prefix("core") {
+            (&mut location.core, i)
+        } else if let Some(i) = name.strip_prefix("thread") {
+            (&mut location.thread, i)
+        } else {
+            continue;
+        };
+        let Ok(index) = index.parse() else {
+            continue;
+        };
+        *field = index;
+
+        if let Some(phandle) = child.property("cpu").and_then(|p| p.as_usize()) {
+            if let Some(i) = phandles.iter().position(|&p| p == Some(phandle)) {
+                cpus[i] = CpuTopology {
+                    hw_id: cpus[i].hw_id,
+                    ..location
+                };
+            }
+        } else if depth < MAX_CPU_MAP_DEPTH {
+            apply_cpu_map(child, depth + 1, location, phandles, cpus);
+        }
+    }
+}
+
+/// Collects the enabled CPUs in the `/cpus` node, along with their locations
+/// in the `cpu-map` node if present.
+fn collect_cpus(cpus_node: FdtNode, info: &mut FdtInfo) {
+    let addr_cells = cpus_node.cell_sizes().address_cells;
+    let mut phandles = [None; MAX_TOPOLOGY_CPUS];
+    for node in cpus_node
+        .children()
+        .filter(|n| is_device_type(*n, "cpu") && is_enabled(*n))
+    {
+        let Some(hw_id) = node
+            .property("reg")
+            .and_then(|p| read_cells(p.value, addr_cells))
+        else {
+            continue;
+        };
+        if info.cpu_num == MAX_TOPOLOGY_CPUS {
+            break;
+        }
+        info.cpus[info.cpu_num] = CpuTopology::flat(hw_id);
+        phandles[info.cpu_num] = node.property("phandle").and_then(|p| p.as_usize());
+        info.cpu_num += 1;
+    }
+    if let Some(cpu_map) = cpus_node.children().find(|n| n.name == "cpu-map") {
+        let cpus = &mut info.cpus[..info.cpu_num];
+        apply_cpu_map(cpu_map, 0, CpuTopology::flat(0), &phandles, cpus);
+    }
+}
+
+fn parse(fdt: Fdt<'static>, fdt_paddr: usize) -> FdtInfo {
+    let mut info = FdtInfo {
+        fdt,
+        ram: RangeList::new(),
+        reserved: RangeList::new(),
+        mmio: RangeList::new(),
+        devices: DeviceList::new(),
+        cpus: [CpuTopology::flat(0); MAX_TOPOLOGY_CPUS],
+        cpu_num: 0,
+    };
+
+    let root = fdt.find_node("/").unwrap();
+    for node in root.children() {
+        if is_device_type(node, "memory") && is_enabled(node) {
+            for r in node.reg().into_iter().flatten() {
+                info.ram
+                    .insert((r.starting_address as usize, r.size.unwrap_or(0)));
+            }
+        }
+    }
+
+    // The DTB itself must not be overwritten.
+    info.reserved
+        .insert(align_range_4k(fdt_paddr, fdt.total_size()));
+    for r in fdt.memory_reservations() {
+        info.reserved
+            .insert(align_range_4k(r.address() as usize, r.size()));
+    }
+    if let Some(node) = fdt.find_node("/reserved-memory") {
+        for child in node.children().filter(|n| is_enabled(*n)) {
+            // Nodes without `reg` are dynamically allocated by the OS.
+            for r in child.reg().into_iter().flatten() {
+                info.reserved.insert(align_range_4k(
+                    r.starting_address as usize,
+                    r.size.unwrap_or(0),
+                ));
+            }
+        }
+    }
+
+    let irq_parent = root.property("interrupt-parent").and_then(|p| p.as_usize());
+    collect_devices(root, 0, &Some, irq_parent, &mut info);
+
+    if let Some(cpus) = fdt.find_node("/cpus") {
+        collect_cpus(cpus, &mut info);
+    }
+    info
+}
+
+/// Parses the device tree blob at the given physical address, and records the
+/// platform information in it.
+///
+/// The DTB is accessed through [`phys_to_virt`](crate::mem::phys_to_virt), so
+/// it must be mapped at this time. It should be called only once on the
+/// primary CPU at the early stage, before calling any other functions in this
+/// module.
+///
+/// Returns an error if `fdt_paddr` is zero or does not point to a valid DTB.
+///
+/// # Safety
+///
+/// If `fdt_paddr` is non-zero, the memory it points to must stay accessible
+/// and unmodified forever.
+pub unsafe fn init(fdt_paddr: usize) -> Result<(), FdtError> {
+    unsafe { init_with_reserved(fdt_paddr, &[]) }
+}
+
+/// Like [`init`], but also adds the given ranges to
+/// [`reserved_phys_ram_ranges`] (extended to 4K-aligned boundaries).
+///
+/// It is for memory that the platform must reserve even if the DTB does not
+/// list it, e.g., the spin table where secondary CPUs wait.
+///
+/// # Safety
+///
+/// See [`init`].
+pub unsafe fn init_with_reserved(fdt_paddr: usize, reserved: &[RawRange]) -> Result<(), FdtError> {
+    if fdt_paddr == 0 {
+        return Err(FdtError::BadPtr);
+    }
+    let fdt_vaddr = crate::mem::phys_to_virt(PhysAddr::from_usize(fdt_paddr));
+    let fdt = unsafe { Fdt::from_ptr(fdt_vaddr.as_ptr()) }?;
+    let mut info = parse(fdt, fdt_paddr);
+    for &(start, size) in reserved {
+        info.reserved.insert(align_range_4k(start, size));
+    }
+    FDT_INFO.init_once(info);
+    Ok(())
+}
+
+/// Returns the device tree recorded by [`init`].
+pub fn get() -> Option<Fdt<'static>> {
+    FDT_INFO.get().map(|info| info.fdt)
+}
+
+/// Returns the physical memory (RAM) ranges described by the `memory` nodes
+/// of the device tree.
+pub fn phys_ram_ranges() -> Option<&'static [RawRange]> {
+    FDT_INFO.get()?.ram.get()
+}
+
+/// Returns the reserved physical memory ranges, which are the ones in the
+/// `/memreserve/` entries and `/reserved-memory` node of the device tree,
+/// and the range of the DTB itself, extended to 4K-aligned boundaries.
+pub fn reserved_phys_ram_ranges() -> Option<&'static [RawRange]> {
+    FDT_INFO.get()?.reserved.get()
+}
+
+/// Returns the first region of the enabled `/reserved-memory` child node
+/// compatible with the given string, e.g., `"arceos,persistent-log"`.
+pub fn reserved_region(compatible: &str) -> Option<RawRange> {
+    find_reserved_region(get()?, compatible)
+}
+
+fn find_reserved_region(fdt: Fdt, compatible: &str) -> Option<RawRange> {
+    fdt.find_node("/reserved-memory")?
+        .children()
+        .filter(|n| is_enabled(*n))
+        .find(|n| {
+            n.compatible()
+                .is_some_and(|c| c.all().any(|s| s == compatible))
+        })?
+        .reg()?
+        .next()
+        .map(|r| (r.starting_address as usize, r.size.unwrap_or(0)))
+}
+
+/// Returns the device memory (MMIO) ranges of the devices in the device tree,
+/// including the address windows of PCI host bridges.
+///
+/// All ranges are aligned to 4K bytes.
+pub fn mmio_ranges() -> Option<&'static [RawRange]> {
+    FDT_INFO.get()?.mmio.get()
+}
+
+/// Returns the enabled devices in the device tree that are recognized by their
+/// `compatible` strings, in the order of the nodes.
+///
+/// The MMIO window of each device is its first `reg` entry, and the IRQ number
+/// is its first interrupt. At most [`MAX_DEVICES`](crate::devices::MAX_DEVICES)
+/// devices are recorded.
+pub fn devices() -> Option<&'static [Device]> {
+    let info = FDT_INFO.get()?;
+    (!info.devices.is_empty()).then_some(&info.devices[..])
+}
+
+/// Returns the number of enabled CPUs in the `/cpus` node of the device tree.
+pub fn cpu_num() -> Option<usize> {
+    FDT_INFO.get().map(|info| info.cpu_num).filter(|&n| n > 0)
+}
+
+/// Returns the enabled CPUs in the `/cpus` node of the device tree.
+///
+/// The hardware ID of each CPU is its `reg` property. The locations are taken
+/// from the `/cpus/cpu-map` node, or are [`CpuTopology::flat`] if it is not
+/// present. CPUs with more than 2 address cells (which can not be represented
+/// by a `usize`) are skipped.
+pub fn cpus() -> Option<&'static [CpuTopology]> {
+    let info = FDT_INFO.get()?;
+    (info.cpu_num > 0).then(|| &info.cpus[..info.cpu_num])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal DTB builder.
+    struct DtbBuilder {
+        structs: Vec<u8>,
+        strings: Vec<u8>,
+        reservations: Vec<(u64, u64)>,
+    }
+
+    impl DtbBuilder {
+        fn new() -> Self {
+            Self {
+                structs: Vec::new(),
+                strings: Vec::new(),
+                reservations: Vec::new(),
+            }
+        }
+
+        fn begin_node(&mut self, name: &str) -> &mut Self {
+            self.structs.extend_from_slice(&1u32.to_be_bytes());
+            self.structs.extend_from_slice(name.as_bytes());
+            self.structs.push(0);
+            self.align();
+            self
+        }
+
+        fn end_node(&mut self) -> &mut Self {
+            self.structs.extend_from_slice(&2u32.to_be_bytes());
+            self
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let name_off = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+            self.structs.extend_from_slice(&3u32.to_be_bytes());
+            self.structs
+                .extend_from_slice(&(value.len() as u32).to_be_bytes());
+            self.structs.extend_from_slice(&name_off.to_be_bytes());
+            self.structs.extend_from_slice(value);
+            self.align();
+            self
+        }
+
+        fn prop_str(&mut self, name: &str, value: &str) -> &mut Self {
+            let mut v = value.as_bytes().to_vec();
+            v.push(0);
+            self.prop(name, &v)
+        }
+
+        fn prop_cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let v: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+            self.prop(name, &v)
+        }
+
+        fn align(&mut self) {
+            while !self.structs.len().is_multiple_of(4) {
+                self.structs.push(0);
+            }
+        }
+
+        fn build(&mut self) -> Vec<u8> {
+            self.structs.extend_from_slice(&9u32.to_be_bytes());
+            let off_rsvmap = 40;
+            let off_struct = off_rsvmap + (self.reservations.len() + 1) * 16;
+            let off_strings = off_struct + self.structs.len();
+            let total = off_strings + self.strings.len();
+            let header = [
+                0xd00d_feedu32,
+                total as u32,
+                off_struct as u32,
+                off_strings as u32,
+                off_rsvmap as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structs.len() as u32,
+            ];
+            let mut dtb: Vec<u8> = header.iter().flat_map(|c| c.to_be_bytes()).collect();
+            for &(addr, size) in self.reservations.iter().chain([(0, 0)].iter()) {
+                dtb.extend_from_slice(&addr.to_be_bytes());
+                dtb.extend_from_slice(&size.to_be_bytes());
+            }
+            dtb.extend_from_slice(&self.structs);
+            dtb.extend_from_slice(&self.strings);
+            dtb
+        }
+    }
+
+    fn qemu_virt_dtb() -> &'static [u8] {
+        let mut b = DtbBuilder::new();
+        b.reservations.push((0x8800_0800, 0x1000));
+        b.begin_node("")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .prop_cells("interrupt-parent", &[0x8000]);
+        b.begin_node("intc@8000000")
+            .prop_str("compatible", "arm,cortex-a15-gic")
+            .prop_cells("#interrupt-cells", &[3])
+            .prop("interrupt-controller", &[])
+            .prop_cells(
+                "reg",
+                &[0, 0x0800_0000, 0, 0x1_0000, 0, 0x0801_0000, 0, 0x1_0000],
+            )
+            .prop_cells("phandle", &[0x8000])
+            .end_node();
+        b.begin_node("memory@80000000")
+            .prop_str("device_type", "memory")
+            .prop_cells("reg", &[0, 0x8000_0000, 0, 0x2000_0000])
+            .end_node();
+        b.begin_node("memory@100000000")
+            .prop_str("device_type", "memory")
+            .prop_cells("reg", &[1, 0, 0, 0x1000_0000])
+            .end_node();
+        b.begin_node("reserved-memory")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .prop("ranges", &[]);
+        b.begin_node("mmode_resv0@80000000")
+            .prop_cells("reg", &[0, 0x8000_0000, 0, 0x40000])
+            .prop("no-map", &[])
+            .end_node();
+        b.begin_node("dynamic")
+            .prop_cells("size", &[0, 0x10000])
+            .end_node();
+        b.begin_node("persistent-log@88100000")
+            .prop_str("compatible", "arceos,persistent-log")
+            .prop_cells("reg", &[0, 0x8810_0000, 0, 0x10000])
+            .end_node();
+        b.end_node();
+        b.begin_node("cpus")
+            .prop_cells("#address-cells", &[1])
+            .prop_cells("#size-cells", &[0]);
+        for (i, status) in ["okay", "okay", "disabled"].iter().enumerate() {
+            b.begin_node(&format!("cpu@{i}"))
+                .prop_str("device_type", "cpu")
+                .prop_cells("reg", &[i as u32])
+                .prop_str("status", status)
+                .prop_cells("phandle", &[i as u32 + 1])
+                .end_node();
+        }
+        b.begin_node("cpu-map");
+        b.begin_node("cluster1");
+        for i in 0..2 {
+            b.begin_node(&format!("core{i}"))
+                .prop_cells("cpu", &[i + 1])
+                .end_node();
+        }
+        b.end_node();
+        b.end_node();
+        b.end_node();
+        b.begin_node("pl011@9000000")
+            .prop("compatible", b"arm,pl011\0arm,primecell\0")
+            .prop_cells("reg", &[0, 0x0900_0000, 0, 0x1000])
+            .prop_cells("interrupts", &[0, 1, 4])
+            .end_node();
+        b.begin_node("virtio_mmio@a000000")
+            .prop_str("compatible", "virtio,mmio")
+            .prop_cells("reg", &[0, 0x0a00_0000, 0, 0x200])
+            .prop_cells("interrupts", &[0, 0x10, 1])
+            .end_node();
+        b.begin_node("virtio_mmio@a000200")
+            .prop_str("compatible", "virtio,mmio")
+            .prop_cells("reg", &[0, 0x0a00_0200, 0, 0x200])
+            .prop_cells("interrupts", &[0, 0x11, 1])
+            .prop_str("status", "disabled")
+            .end_node();
+        b.begin_node("soc")
+            .prop_cells("#address-cells", &[1])
+            .prop_cells("#size-cells", &[1])
+            .prop_cells("ranges", &[0, 0, 0x1000_0000, 0x100_0000]);
+        b.begin_node("plic@c000000")
+            .prop_str("compatible", "sifive,plic-1.0.0")
+            .prop_cells("#interrupt-cells", &[1])
+            .prop("interrupt-controller", &[])
+            .prop_cells("phandle", &[0x9000])
+            .end_node();
+        b.begin_node("serial@20000")
+            .prop_str("compatible", "ns16550a")
+            .prop_cells("reg", &[0x2_0000, 0x100])
+            .prop_cells("interrupt-parent", &[0x9000])
+            .prop_cells("interrupts", &[10])
+            .end_node();
+        b.end_node();
+        b.begin_node("pcie@30000000")
+            .prop_str("compatible", "pci-host-ecam-generic")
+            .prop_str("device_type", "pci")
+            .prop_cells("#address-cells", &[3])
+            .prop_cells("#size-cells", &[2])
+            .prop_cells("reg", &[0, 0x3000_0000, 0, 0x800_0000])
+            .prop_cells(
+                "ranges",
+                &[
+                    0x0100_0000,
+                    0,
+                    0,
+                    0,
+                    0x0300_0800,
+                    0,
+                    0x1_0000, // PIO, not 4K-aligned
+                    0x0200_0000,
+                    0,
+                    0x4000_0000,
+                    0,
+                    0x4000_0000,
+                    0,
+                    0x4000_0000, // MMIO
+                ],
+            )
+            .end_node();
+        b.end_node();
+        Vec::leak(b.build())
+    }
+
+    #[test]
+    fn test_range_list() {
+        let mut list = RangeList::<3>::new();
+        assert_eq!(list.get(), None);
+        list.insert((0x3000, 0x1000));
+        list.insert((0x1000, 0x1000));
+        list.insert((0x2000, 0x1000)); // adjacent to both
+        assert_eq!(list.get(), Some(&[(0x1000, 0x3000)][..]));
+        list.insert((0x8000, 0x1000));
+        list.insert((0x6000, 0x1000));
+        list.insert((0x6800, 0x1000)); // overlapping
+        assert_eq!(
+            list.get(),
+            Some(&[(0x1000, 0x3000), (0x6000, 0x1800), (0x8000, 0x1000)][..])
+        );
+        list.insert((0xa000, 0x1000));
+        assert_eq!(list.get(), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let fdt = Fdt::new(qemu_virt_dtb()).unwrap();
+        let info = parse(fdt, 0x8400_0000);
+        assert_eq!(
+            info.ram.get(),
+            Some(&[(0x8000_0000, 0x2000_0000), (0x1_0000_0000, 0x1000_0000)][..])
+        );
+        assert_eq!(
+            info.reserved.get(),
+            Some(
+                &[
+                    (0x8000_0000, 0x40000),
+                    (0x8400_0000, align_up_4k(fdt.total_size())),
+                    (0x8800_0000, 0x2000),
+                    (0x8810_0000, 0x10000)
+                ][..]
+            )
+        );
+        assert_eq!(
+            find_reserved_region(fdt, "arceos,persistent-log"),
+            Some((0x8810_0000, 0x10000))
+        );
+        assert_eq!(find_reserved_region(fdt, "ramoops"), None);
+        assert_eq!(
+            info.mmio.get(),
+            Some(
+                &[
+                    (0x0300_0000, 0x1_1000),    // PCI PIO window
+                    (0x0800_0000, 0x2_0000),    // GIC
+                    (0x0900_0000, 0x1000),      // pl011
+                    (0x0a00_0000, 0x1000),      // virtio_mmio
+                    (0x1002_0000, 0x1000),      // serial in soc
+                    (0x3000_0000, 0x800_0000),  // PCI ECAM
+                    (0x4000_0000, 0x4000_0000), // PCI MMIO window
+                ][..]
+            )
+        );
+        assert_eq!(
+            info.devices
+                .iter()
+                .map(|d| (d.kind, d.compatible, d.mmio, d.irq))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    DeviceKind::Uart,
+                    "arm,pl011",
+                    (0x0900_0000, 0x1000),
+                    Some(33)
+                ),
+                (
+                    DeviceKind::VirtioMmio,
+                    "virtio,mmio",
+                    (0x0a00_0000, 0x200),
+                    Some(48)
+                ),
+                (DeviceKind::Uart, "ns16550a", (0x1002_0000, 0x100), Some(10)),
+                (
+                    DeviceKind::PciHostBridge,
+                    "pci-host-ecam-generic",
+                    (0x3000_0000, 0x800_0000),
+                    None
+                ),
+            ]
+        );
+        assert_eq!(info.cpu_num, 2);
+        let cpus = &info.cpus[..info.cpu_num];
+        assert_eq!(
+            cpus.iter()
+                .map(|c| (c.hw_id, c.cluster, c.core))
+                .collect::<Vec<_>>(),
+            [(0, 1, 0), (1, 1, 1)]
+        );
+    }
+}
@@ -1,9 +1,25 @@
 //! Interrupt request (IRQ) handling.
 
-pub use handler_table::HandlerTable;
+pub mod stats;
+mod table;
 
-/// The type if an IRQ handler.
-pub type IrqHandler = handler_table::Handler;
+pub use self::table::{HandlerTable, HandlerToken, MAX_SHARED_HANDLERS};
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The type of a plain IRQ handler.
+///
+/// On a shared IRQ line, a plain handler is always considered to have handled
+/// the interrupt.
+pub type IrqHandler = fn();
+
+/// The type of an IRQ handler with context.
+///
+/// It receives the IRQ number and the opaque context (e.g., a pointer to the
+/// device instance) registered along with it, and returns whether the
+/// interrupt was raised by its device and handled. It is used to tell the
+/// unhandled interrupts on shared IRQ lines.
+pub type IrqContextHandler = fn(irq: usize, ctx: usize) -> bool;
 
 /// Target specification for inter-processor interrupts (IPIs).
 pub enum IpiTarget {
@@ -26,23 +42,58 @@ pub enum IpiTarget {
     },
 }
 
+/// The trigger mode of an IRQ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Triggered on the edge of the interrupt signal.
+    Edge,
+    /// Triggered while the interrupt signal is at the active level.
+    Level,
+}
+
+/// Errors of the IRQ configuration operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    /// The operation is not supported by the interrupt controller, or not
+    /// supported for the given IRQ (e.g., routing a per-CPU interrupt).
+    Unsupported,
+    /// The IRQ number or the argument is invalid.
+    InvalidInput,
+}
+
+/// A specialized [`Result`] type for the IRQ configuration operations.
+pub type IrqResult<T = ()> = Result<T, IrqError>;
+
 /// IRQ management interface.
 #[def_interface(gen_caller)]
 pub trait IrqIf {
     /// Enables or disables the given IRQ.
     fn set_enable(irq: usize, enabled: bool);
 
-    /// Registers an IRQ handler for the given IRQ.
+    /// Registers an IRQ handler with context for the given IRQ.
     ///
-    /// It also enables the IRQ if the registration succeeds. It returns `false`
-    /// if the registration failed.
-    fn register(irq: usize, handler: IrqHandler) -> bool;
+    /// `ctx` is passed to the handler as-is each time the IRQ is handled.
+    /// Multiple handlers can be registered for a shared IRQ, and all of them
+    /// are called when the IRQ is raised. It also enables the IRQ if the
+    /// registration succeeds. It returns the token to unregister the handler,
+    /// or `None` if the registration failed.
+    fn register_with_context(
+        irq: usize,
+        handler: IrqContextHandler,
+        ctx: usize,
+    ) -> Option<HandlerToken>;
 
-    /// Unregisters the IRQ handler for the given IRQ.
+    /// Unregisters the IRQ handler registered with the given token for the
+    /// given IRQ.
+    ///
+    /// Other handlers of a shared IRQ are kept, and the IRQ is disabled only
+    /// if the last handler is unregistered. It returns `true` if the handler
+    /// was registered, `false` otherwise.
     ///
-    /// It also disables the IRQ if the unregistration succeeds. It returns the
-    /// existing handler if it is registered, `None` otherwise.
-    fn unregister(irq: usize) -> Option<IrqHandler>;
+    /// It waits until the calls of the handler in flight on other CPUs have
+    /// returned, so the context can be freed afterwards. Therefore, it must
+    /// not be called from the handler itself.
+    fn unregister(irq: usize, token: HandlerToken) -> bool;
 
     /// Handles the IRQ.
     ///
@@ -53,4 +104,87 @@ pub trait IrqIf {
 
     /// Sends an inter-processor interrupt (IPI) to the specified target CPU or all CPUs.
     fn send_ipi(irq_num: usize, target: IpiTarget);
+
+    /// Routes the given IRQ to the CPUs in `cpu_mask`.
+    ///
+    /// Bit `i` of `cpu_mask` stands for the CPU with ID `i`. If the interrupt
+    /// controller can only route an IRQ to one CPU, `cpu_mask` must have
+    /// exactly one bit set, otherwise [`IrqError::Unsupported`] is returned.
+    fn set_affinity(irq: usize, cpu_mask: usize) -> IrqResult;
+
+    /// Sets the priority of the given IRQ.
+    ///
+    /// The range and meaning of `priority` are controller-specific. For most
+    /// controllers (e.g., GIC), a lower value means a higher priority.
+    fn set_priority(irq: usize, priority: u8) -> IrqResult;
+
+    /// Sets the trigger mode (edge or level) of the given IRQ.
+    fn set_trigger(irq: usize, mode: TriggerMode) -> IrqResult;
+}
+
+/// Calls a plain [`IrqHandler`] stored in the context.
+fn call_plain_handler(_irq: usize, ctx: usize) -> bool {
+    // SAFETY: `ctx` is always set from an `IrqHandler` along with this function.
+    let handler = unsafe { core::mem::transmute::<usize, IrqHandler>(ctx) };
+    handler();
+    true
+}
+
+/// Registers a plain IRQ handler for the given IRQ.
+///
+/// It is a shorthand of [`register_with_context`] for handlers that need
+/// neither the IRQ number nor a context. It also enables the IRQ if the
+/// registration succeeds. It returns the token to [`unregister`] the handler,
+/// or `None` if the registration failed.
+pub fn register(irq: usize, handler: IrqHandler) -> Option<HandlerToken> {
+    register_with_context(irq, call_plain_handler, handler as usize)
+}
+
+/// The address of the function that masks or unmasks IRQs on the current CPU,
+/// or `0` if not set.
+static IRQ_MASK_FN: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the function that masks (`true`) or unmasks (`false`) IRQs on the
+/// current CPU, and returns whether they were masked before.
+///
+/// The locks of `axplat` shared with IRQ handlers (e.g., the timer queues and
+/// the console lock) are [`kspin::SpinNoIrq`], which only disables IRQs when
+/// running on bare metal (`target_os = "none"`). Hosted platforms that emulate
+/// IRQs in other ways (e.g., with signals) set this function, so that the
+/// locks are not taken by an IRQ handler on the CPU that is holding them. It
+/// is not used on bare metal.
+pub fn set_irq_mask_fn(f: fn(bool) -> bool) {
+    IRQ_MASK_FN.store(f as usize, Ordering::Release);
+}
+
+/// Masks IRQs on the current CPU by the function set with
+/// [`set_irq_mask_fn`] until dropped, if they were not masked.
+///
+/// It is taken before the locks shared with IRQ handlers, and must be dropped
+/// after them.
+pub(crate) struct IrqMask {
+    unmask: bool,
+}
+
+impl IrqMask {
+    pub(crate) fn new() -> Self {
+        let f = IRQ_MASK_FN.load(Ordering::Acquire);
+        if cfg!(target_os = "none") || f == 0 {
+            return Self { unmask: false };
+        }
+        // SAFETY: `IRQ_MASK_FN` is only set from a `fn(bool) -> bool`.
+        let f = unsafe { core::mem::transmute::<usize, fn(bool) -> bool>(f) };
+        Self { unmask: !f(true) }
+    }
+}
+
+impl Drop for IrqMask {
+    fn drop(&mut self) {
+        if self.unmask {
+            // SAFETY: `unmask` is only set if `IRQ_MASK_FN` has been set.
+            let f = IRQ_MASK_FN.load(Ordering::Acquire);
+            let f = unsafe { core::mem::transmute::<usize, fn(bool) -> bool>(f) };
+            f(false);
+        }
+    }
 }
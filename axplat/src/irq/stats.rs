@@ -0,0 +1,274 @@
+//! Per-CPU interrupt statistics.
+//!
+//! Platforms record every IRQ they handle through [`record_irq`],
+//! [`record_spurious`] and [`record_ipi_sent`]; the kernel reads the counters
+//! with [`lines`] (like `/proc/interrupts`) and [`cpu_stats`].
+//!
+//! Events are counted on the CPU returned by [`this_cpu_id`], so the kernel
+//! should set it up by [`set_this_cpu_id_fn`]. Before that, all events are
+//! counted on CPU 0.
+//!
+//! [`this_cpu_id`]: crate::power::this_cpu_id
+//! [`set_this_cpu_id_fn`]: crate::power::set_this_cpu_id_fn
+
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// The maximum number of CPUs that have separate counters. Events on CPUs
+/// beyond it are counted on the last one.
+pub const MAX_STATS_CPUS: usize = 32;
+
+/// The maximum number of distinct IRQs that are counted. Events of IRQs
+/// beyond it are only counted in [`CpuIrqStats::dropped`].
+pub const MAX_STATS_IRQS: usize = 64;
+
+const LINE_FREE: usize = usize::MAX;
+
+/// Counters of one IRQ.
+pub struct IrqLineStats {
+    irq: AtomicUsize,
+    is_ipi: AtomicBool,
+    handled: [AtomicU64; MAX_STATS_CPUS],
+    unhandled: [AtomicU64; MAX_STATS_CPUS],
+}
+
+impl IrqLineStats {
+    const fn new() -> Self {
+        Self {
+            irq: AtomicUsize::new(LINE_FREE),
+            is_ipi: AtomicBool::new(false),
+            handled: [const { AtomicU64::new(0) }; MAX_STATS_CPUS],
+            unhandled: [const { AtomicU64::new(0) }; MAX_STATS_CPUS],
+        }
+    }
+
+    /// The IRQ number.
+    pub fn irq(&self) -> usize {
+        self.irq.load(Ordering::Acquire)
+    }
+
+    /// Whether the IRQ is used to send IPIs.
+    pub fn is_ipi(&self) -> bool {
+        self.is_ipi.load(Ordering::Relaxed)
+    }
+
+    /// The number of times the IRQ is handled on the given CPU.
+    pub fn handled(&self, cpu_id: usize) -> u64 {
+        self.handled
+            .get(cpu_id)
+            .map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    /// The number of times the IRQ is raised on the given CPU but no handler
+    /// has handled it.
+    pub fn unhandled(&self, cpu_id: usize) -> u64 {
+        self.unhandled
+            .get(cpu_id)
+            .map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    /// The number of times the IRQ is raised on all CPUs, no matter whether
+    /// it is handled.
+    pub fn total(&self) -> u64 {
+        (0..MAX_STATS_CPUS)
+            .map(|cpu_id| self.handled(cpu_id) + self.unhandled(cpu_id))
+            .sum()
+    }
+}
+
+/// Counters of one CPU that are not bound to an IRQ.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuIrqStats {
+    /// The number of spurious interrupts, which are raised but have no valid
+    /// IRQ number, or are raised while disabled.
+    pub spurious: u64,
+    /// The number of IPIs sent from the CPU.
+    pub ipi_sent: u64,
+    /// The number of IPIs received by the CPU.
+    pub ipi_received: u64,
+    /// The number of IRQs not counted per IRQ for the lack of space.
+    pub dropped: u64,
+}
+
+struct CpuCounters {
+    spurious: AtomicU64,
+    ipi_sent: AtomicU64,
+    ipi_received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl CpuCounters {
+    const fn new() -> Self {
+        Self {
+            spurious: AtomicU64::new(0),
+            ipi_sent: AtomicU64::new(0),
+            ipi_received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+}
+
+/// The counters of all IRQs and CPUs.
+struct Stats {
+    lines: [IrqLineStats; MAX_STATS_IRQS],
+    cpus: [CpuCounters; MAX_STATS_CPUS],
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            lines: [const { IrqLineStats::new() }; MAX_STATS_IRQS],
+            cpus: [const { CpuCounters::new() }; MAX_STATS_CPUS],
+        }
+    }
+
+    fn cpu(&self, cpu_id: usize) -> &CpuCounters {
+        &self.cpus[cpu_id.min(MAX_STATS_CPUS - 1)]
+    }
+
+    /// Finds the counters of the given IRQ, allocates them if not exist.
+    fn line(&self, irq: usize) -> Option<&IrqLineStats> {
+        if irq == LINE_FREE {
+            return None;
+        }
+        let start = irq % MAX_STATS_IRQS;
+        for i in 0..MAX_STATS_IRQS {
+            let line = &self.lines[(start + i) % MAX_STATS_IRQS];
+            match line
+                .irq
+                .compare_exchange(LINE_FREE, irq, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return Some(line),
+                Err(cur) if cur == irq => return Some(line),
+                Err(_) => {}
+            }
+        }
+        None
+    }
+
+    fn record_irq(&self, cpu_id: usize, irq: usize, handled: bool) {
+        let cpu_id = cpu_id.min(MAX_STATS_CPUS - 1);
+        let Some(line) = self.line(irq) else {
+            self.cpus[cpu_id].dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let counters = if handled {
+            &line.handled
+        } else {
+            &line.unhandled
+        };
+        counters[cpu_id].fetch_add(1, Ordering::Relaxed);
+        if line.is_ipi() {
+            self.cpus[cpu_id]
+                .ipi_received
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_spurious(&self, cpu_id: usize) {
+        self.cpu(cpu_id).spurious.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_ipi_sent(&self, cpu_id: usize, irq: usize) {
+        if let Some(line) = self.line(irq) {
+            line.is_ipi.store(true, Ordering::Relaxed);
+        }
+        self.cpu(cpu_id).ipi_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &IrqLineStats> {
+        self.lines.iter().filter(|line| line.irq() != LINE_FREE)
+    }
+
+    fn line_stats(&self, irq: usize) -> Option<&IrqLineStats> {
+        self.lines().find(|line| line.irq() == irq)
+    }
+
+    fn cpu_stats(&self, cpu_id: usize) -> CpuIrqStats {
+        let Some(c) = self.cpus.get(cpu_id) else {
+            return CpuIrqStats::default();
+        };
+        CpuIrqStats {
+            spurious: c.spurious.load(Ordering::Relaxed),
+            ipi_sent: c.ipi_sent.load(Ordering::Relaxed),
+            ipi_received: c.ipi_received.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static STATS: Stats = Stats::new();
+
+/// Records an IRQ raised on the current CPU, and whether it is handled.
+///
+/// It should be called by the platform each time it dispatches an IRQ.
+pub fn record_irq(irq: usize, handled: bool) {
+    STATS.record_irq(crate::power::this_cpu_id(), irq, handled);
+}
+
+/// Records a spurious interrupt on the current CPU.
+pub fn record_spurious() {
+    STATS.record_spurious(crate::power::this_cpu_id());
+}
+
+/// Records an IPI sent from the current CPU with the given IRQ.
+///
+/// `irq` must be the number that the receiver dispatches the IPI with, so
+/// that [`record_irq`] of it is counted as receiving an IPI.
+pub fn record_ipi_sent(irq: usize) {
+    STATS.record_ipi_sent(crate::power::this_cpu_id(), irq);
+}
+
+/// Returns an iterator over the counters of all IRQs that have been raised.
+pub fn lines() -> impl Iterator<Item = &'static IrqLineStats> {
+    STATS.lines()
+}
+
+/// Returns the counters of the given IRQ, or `None` if it has never been
+/// raised.
+pub fn line_stats(irq: usize) -> Option<&'static IrqLineStats> {
+    STATS.line_stats(irq)
+}
+
+/// Returns the counters of the given CPU that are not bound to an IRQ.
+pub fn cpu_stats(cpu_id: usize) -> CpuIrqStats {
+    STATS.cpu_stats(cpu_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_read() {
+        let stats = Box::new(Stats::new());
+        stats.record_irq(0, 5, true);
+        stats.record_irq(0, 5, true);
+        stats.record_irq(0, 5 + MAX_STATS_IRQS, false); // collides with IRQ 5
+        stats.record_ipi_sent(0, 1);
+        stats.record_irq(0, 1, true);
+        stats.record_spurious(0);
+
+        let line = stats.line_stats(5).unwrap();
+        assert_eq!(
+            (line.handled(0), line.unhandled(0), line.total()),
+            (2, 0, 2)
+        );
+        assert!(!line.is_ipi());
+        let line = stats.line_stats(5 + MAX_STATS_IRQS).unwrap();
+        assert_eq!((line.handled(0), line.unhandled(0)), (0, 1));
+        assert!(stats.line_stats(1).unwrap().is_ipi());
+        assert!(stats.line_stats(2).is_none());
+        assert_eq!(stats.lines().count(), 3);
+
+        stats.record_irq(1, 5, true);
+        assert_eq!(stats.line_stats(5).unwrap().handled(1), 1);
+        // CPUs beyond the limit are counted on the last one.
+        stats.record_spurious(MAX_STATS_CPUS + 1);
+        assert_eq!(stats.cpu_stats(MAX_STATS_CPUS - 1).spurious, 1);
+
+        let cpu0 = stats.cpu_stats(0);
+        assert_eq!((cpu0.spurious, cpu0.ipi_sent, cpu0.ipi_received), (1, 1, 1));
+        let cpu1 = stats.cpu_stats(1);
+        assert_eq!((cpu1.spurious, cpu1.dropped), (0, 0));
+    }
+}
@@ -0,0 +1,525 @@
+//! A table of IRQ handlers, whose calls are lock-free.
+
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering, fence};
+
+use kspin::SpinNoIrq;
+
+use super::{IrqContextHandler, IrqHandler, call_plain_handler};
+
+/// The default maximum number of handlers sharing one IRQ.
+pub const MAX_SHARED_HANDLERS: usize = 4;
+
+/// A registered handler along with its context.
+///
+/// The handler and the context are published together by a sequence lock:
+/// writers make `seq` odd while updating the slot, and readers retry if `seq`
+/// has changed during the read. So a handler is never called with the
+/// context of another registration that reuses the slot.
+///
+/// When the slot is emptied, `seq` stays odd until the calls that may have
+/// read the old handler have returned, so it is not reused before that.
+struct Slot {
+    /// The sequence number, odd while the slot is being written.
+    seq: AtomicUsize,
+    /// The address of the [`IrqContextHandler`], `0` if the slot is empty.
+    handler: AtomicUsize,
+    ctx: AtomicUsize,
+    /// The epoch of the calls, advanced each time the slot is emptied.
+    epoch: AtomicUsize,
+    /// The number of calls in flight started in even and odd epochs.
+    active: [AtomicUsize; 2],
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            handler: AtomicUsize::new(0),
+            ctx: AtomicUsize::new(0),
+            epoch: AtomicUsize::new(0),
+            active: [const { AtomicUsize::new(0) }; 2],
+        }
+    }
+
+    /// Reads the handler and the context, along with the sequence number they
+    /// are read at.
+    ///
+    /// Returns `None` if the slot is being written. The writer may be the code
+    /// interrupted by the caller, so it must not be waited for.
+    fn read(&self) -> Option<(usize, usize, usize)> {
+        loop {
+            let seq = self.seq.load(Ordering::SeqCst);
+            if seq & 1 != 0 {
+                return None;
+            }
+            let handler = self.handler.load(Ordering::Relaxed);
+            let ctx = self.ctx.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return Some((seq, handler, ctx));
+            }
+        }
+    }
+
+    /// Writes the slot if it is still at sequence number `seq`, and returns
+    /// the new sequence number.
+    fn write_at(&self, seq: usize, handler: usize, ctx: usize) -> Option<usize> {
+        self.seq
+            .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        fence(Ordering::Release);
+        self.handler.store(handler, Ordering::Relaxed);
+        self.ctx.store(ctx, Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::SeqCst);
+        Some(seq + 2)
+    }
+
+    /// Fills the slot if it is empty, and returns the new sequence number.
+    fn fill(&self, handler: IrqContextHandler, ctx: usize) -> Option<usize> {
+        match self.read() {
+            Some((seq, 0, _)) => self.write_at(seq, handler as usize, ctx),
+            _ => None,
+        }
+    }
+
+    /// Empties the slot if it is still at sequence number `seq`, and returns
+    /// the epoch of the calls that may have read it before.
+    ///
+    /// The slot stays odd (being written) until [`Slot::wait_idle`] is called,
+    /// so it can not be filled and emptied again in the meantime.
+    fn empty_at(&self, seq: usize) -> Option<usize> {
+        self.seq
+            .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        fence(Ordering::Release);
+        self.handler.store(0, Ordering::Relaxed);
+        self.ctx.store(0, Ordering::Relaxed);
+        Some(self.epoch.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Waits until the calls of `epoch` returned by [`Slot::empty_at`] have
+    /// returned, then allows the slot to be filled again.
+    ///
+    /// Calls started after that are counted in the new epoch, so they can not
+    /// delay the wait even if the IRQ keeps firing. As the slot is not
+    /// emptied again during the wait, the new epoch uses the other counter.
+    fn wait_idle(&self, seq: usize, epoch: usize) {
+        while self.active[epoch & 1].load(Ordering::SeqCst) != 0 {
+            spin_loop();
+        }
+        self.seq.store(seq + 2, Ordering::SeqCst);
+    }
+
+    /// Announces a call in the given epoch, and returns whether it is still
+    /// the current epoch.
+    ///
+    /// Otherwise, the call is withdrawn, as a writer may have waited for the
+    /// calls of that epoch before it was announced.
+    fn enter(&self, epoch: usize) -> bool {
+        self.active[epoch & 1].fetch_add(1, Ordering::SeqCst);
+        if self.epoch.load(Ordering::SeqCst) == epoch {
+            return true;
+        }
+        self.exit(epoch);
+        false
+    }
+
+    /// Ends a call announced by [`Slot::enter`].
+    fn exit(&self, epoch: usize) {
+        self.active[epoch & 1].fetch_sub(1, Ordering::Release);
+    }
+
+    /// Calls the handler in the slot, returns `None` if the slot is empty.
+    fn call(&self, irq: usize) -> Option<bool> {
+        // Announce the call before reading the slot, so that a writer that
+        // empties the slot either is seen here, or waits for the call.
+        let epoch = loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            if self.enter(epoch) {
+                break epoch;
+            }
+        };
+        let res = match self.read() {
+            Some((_, handler, ctx)) if handler != 0 => {
+                // SAFETY: non-empty handler words are always set from an `IrqContextHandler`.
+                let handler = unsafe { core::mem::transmute::<usize, IrqContextHandler>(handler) };
+                Some(handler(irq, ctx))
+            }
+            _ => None,
+        };
+        self.exit(epoch);
+        res
+    }
+}
+
+/// The handlers sharing one IRQ.
+struct Line<const S: usize> {
+    /// Serializes the registrations and unregistrations, so that looking for
+    /// a duplicate and claiming a slot are done at once. Calls of the handlers
+    /// do not take it.
+    lock: SpinNoIrq<()>,
+    slots: [Slot; S],
+}
+
+/// The token of a handler registered in a [`HandlerTable`], used to
+/// unregister it.
+///
+/// It identifies the registration rather than the handler, so it becomes
+/// stale once the handler is unregistered, even if the same handler is
+/// registered again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerToken {
+    idx: usize,
+    slot: usize,
+    seq: usize,
+}
+
+/// A table of IRQ handlers, whose calls are lock-free.
+///
+/// Each of the `N` entries holds up to `S` handlers that share the IRQ. A
+/// handler is either a plain [`IrqHandler`], or an [`IrqContextHandler`] with
+/// its context.
+pub struct HandlerTable<const N: usize, const S: usize = MAX_SHARED_HANDLERS> {
+    lines: [Line<S>; N],
+}
+
+impl<const N: usize, const S: usize> HandlerTable<N, S> {
+    /// Creates a new handler table with all entries empty.
+    pub const fn new() -> Self {
+        Self {
+            lines: [const {
+                Line {
+                    lock: SpinNoIrq::new(()),
+                    slots: [const { Slot::new() }; S],
+                }
+            }; N],
+        }
+    }
+
+    /// Registers a plain handler for the given index.
+    ///
+    /// Returns the token to unregister it, or `None` if the index is out of
+    /// range, the handler is already registered, or the entry is full.
+    pub fn register_handler(&self, idx: usize, handler: IrqHandler) -> Option<HandlerToken> {
+        self.register_handler_with_context(idx, call_plain_handler, handler as usize)
+    }
+
+    /// Registers a handler with context for the given index.
+    ///
+    /// `ctx` is passed to the handler as-is each time it is called. Returns
+    /// the token to unregister it, or `None` if the index is out of range, the
+    /// same handler and context are already registered, or the entry is full.
+    pub fn register_handler_with_context(
+        &self,
+        idx: usize,
+        handler: IrqContextHandler,
+        ctx: usize,
+    ) -> Option<HandlerToken> {
+        let line = self.lines.get(idx)?;
+        let _guard = line.lock.lock();
+        if find(&line.slots, handler, ctx) {
+            return None;
+        }
+        line.slots.iter().enumerate().find_map(|(slot, entry)| {
+            let seq = entry.fill(handler, ctx)?;
+            Some(HandlerToken { idx, slot, seq })
+        })
+    }
+
+    /// Unregisters the handler registered with the given token for the given
+    /// index.
+    ///
+    /// Other handlers of the entry are kept. Returns `true` if the handler was
+    /// registered, `false` if the token is stale or of another index.
+    ///
+    /// It waits until the calls of the handler in flight on other CPUs have
+    /// returned, so its context can be freed afterwards. Therefore, it must
+    /// not be called from the handler itself.
+    pub fn unregister_handler(&self, idx: usize, token: HandlerToken) -> bool {
+        if token.idx != idx {
+            return false;
+        }
+        let Some(line) = self.lines.get(idx) else {
+            return false;
+        };
+        let Some(slot) = line.slots.get(token.slot) else {
+            return false;
+        };
+        let emptied = {
+            let _guard = line.lock.lock();
+            slot.empty_at(token.seq)
+        };
+        let Some(epoch) = emptied else {
+            return false;
+        };
+        // Wait without the lock, the calls on other CPUs may take a while.
+        slot.wait_idle(token.seq, epoch);
+        true
+    }
+
+    /// Returns whether any handler is registered for the given index.
+    pub fn is_registered(&self, idx: usize) -> bool {
+        self.lines.get(idx).is_some_and(|line| {
+            line.slots
+                .iter()
+                .any(|slot| slot.handler.load(Ordering::Acquire) != 0)
+        })
+    }
+
+    /// Handles the interrupt for the given index.
+    ///
+    /// All handlers of the entry are called with `idx` as the IRQ number.
+    /// Returns `true` if any handler has handled the interrupt, `false`
+    /// otherwise.
+    pub fn handle(&self, idx: usize) -> bool {
+        self.handle_with_irq(idx, idx)
+    }
+
+    /// Handles the interrupt for the given index, and passes `irq` to the
+    /// handlers as the IRQ number.
+    ///
+    /// It is useful when the IRQ numbers are not the indices of the table.
+    /// Returns `true` if any handler has handled the interrupt, `false`
+    /// otherwise.
+    pub fn handle_with_irq(&self, idx: usize, irq: usize) -> bool {
+        let Some(line) = self.lines.get(idx) else {
+            return false;
+        };
+        // Do not short-circuit, every device on a shared line needs to be serviced.
+        line.slots.iter().fold(false, |handled, slot| {
+            slot.call(irq).unwrap_or(false) | handled
+        })
+    }
+}
+
+impl<const N: usize, const S: usize> Default for HandlerTable<N, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns whether the given handler and context are registered in `slots`.
+fn find(slots: &[Slot], handler: IrqContextHandler, ctx: usize) -> bool {
+    slots
+        .iter()
+        .any(|slot| matches!(slot.read(), Some((_, h, c)) if h == handler as usize && c == ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::HandlerTable;
+
+    static PLAIN_COUNT: AtomicUsize = AtomicUsize::new(0);
+    static CONTEXT_SUM: AtomicUsize = AtomicUsize::new(0);
+
+    fn plain_handler() {
+        PLAIN_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn context_handler(irq: usize, ctx: usize) -> bool {
+        CONTEXT_SUM.fetch_add(irq * 100 + ctx, Ordering::Relaxed);
+        true
+    }
+
+    fn unhandled_handler(_irq: usize, _ctx: usize) -> bool {
+        false
+    }
+
+    /// The context of the registered handler, or `usize::MAX` after it is
+    /// unregistered.
+    static LIVE_CTX: AtomicUsize = AtomicUsize::new(usize::MAX);
+    static LIVE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn live_handler(_irq: usize, ctx: usize) -> bool {
+        assert_eq!(LIVE_CTX.load(Ordering::SeqCst), ctx);
+        LIVE_CALLS.fetch_add(1, Ordering::SeqCst);
+        // Give the unregistering thread a chance to run.
+        std::thread::yield_now();
+        assert_eq!(LIVE_CTX.load(Ordering::SeqCst), ctx);
+        true
+    }
+
+    #[test]
+    fn register_and_handle() {
+        let table = HandlerTable::<4, 1>::new();
+        let plain = table.register_handler(0, plain_handler).unwrap();
+        assert!(table.register_handler(0, plain_handler).is_none());
+        let ctx7 = table
+            .register_handler_with_context(1, context_handler, 7)
+            .unwrap();
+        assert!(
+            table
+                .register_handler_with_context(2, context_handler, 8)
+                .is_some()
+        );
+        assert!(
+            table
+                .register_handler_with_context(4, context_handler, 9)
+                .is_none()
+        );
+
+        assert!(table.handle(0));
+        assert!(table.handle(1));
+        assert!(table.handle(2));
+        assert!(!table.handle(3));
+        assert!(!table.handle(4));
+        assert!(table.handle_with_irq(2, 3));
+        assert_eq!(PLAIN_COUNT.load(Ordering::Relaxed), 1);
+        assert_eq!(CONTEXT_SUM.load(Ordering::Relaxed), 107 + 208 + 308);
+
+        assert!(!table.unregister_handler(2, ctx7));
+        assert!(table.unregister_handler(1, ctx7));
+        assert!(!table.unregister_handler(1, ctx7));
+        assert!(!table.is_registered(1));
+        assert!(!table.handle(1));
+        assert!(table.unregister_handler(0, plain));
+        assert!(!table.is_registered(0));
+    }
+
+    #[test]
+    fn stale_token() {
+        let table = HandlerTable::<2, 1>::new();
+        let old = table
+            .register_handler_with_context(0, context_handler, 1)
+            .unwrap();
+        assert!(table.unregister_handler(0, old));
+        // The same handler and context in the same slot is a new registration.
+        let new = table
+            .register_handler_with_context(0, context_handler, 1)
+            .unwrap();
+        assert_ne!(old, new);
+        assert!(!table.unregister_handler(0, old));
+        assert!(table.is_registered(0));
+        assert!(table.unregister_handler(0, new));
+    }
+
+    #[test]
+    fn shared_irq() {
+        let table = HandlerTable::<2, 3>::new();
+        let first = table
+            .register_handler_with_context(1, unhandled_handler, 1)
+            .unwrap();
+        assert!(!table.handle(1));
+        let second = table
+            .register_handler_with_context(1, unhandled_handler, 2)
+            .unwrap();
+        let handled = table
+            .register_handler_with_context(1, context_handler, 3)
+            .unwrap();
+        assert!(
+            table
+                .register_handler_with_context(1, context_handler, 4)
+                .is_none()
+        );
+        assert!(table.handle(1));
+
+        assert!(table.unregister_handler(1, handled));
+        assert!(!table.handle(1));
+        assert!(table.unregister_handler(1, first));
+        assert!(table.is_registered(1));
+        assert!(table.unregister_handler(1, second));
+        assert!(!table.is_registered(1));
+    }
+
+    #[test]
+    fn concurrent_duplicates() {
+        let table = HandlerTable::<1, 8>::new();
+        let registered = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    if table
+                        .register_handler_with_context(0, context_handler, 1)
+                        .is_some()
+                    {
+                        registered.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(registered.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn reuse_while_handling() {
+        static TABLE: HandlerTable<1, 1> = HandlerTable::new();
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            let handling = s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    TABLE.handle(0);
+                }
+            });
+            for ctx in 0..100 {
+                LIVE_CTX.store(ctx, Ordering::SeqCst);
+                let calls = LIVE_CALLS.load(Ordering::SeqCst);
+                let token = TABLE
+                    .register_handler_with_context(0, live_handler, ctx)
+                    .unwrap();
+                while LIVE_CALLS.load(Ordering::SeqCst) == calls {
+                    // The handler has panicked.
+                    if handling.is_finished() {
+                        return;
+                    }
+                    std::thread::yield_now();
+                }
+                assert!(TABLE.unregister_handler(0, token));
+                // No call with `ctx` is in flight after unregistering.
+                LIVE_CTX.store(usize::MAX, Ordering::SeqCst);
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn stalled_call() {
+        let table = HandlerTable::<1, 1>::new();
+        let slot = &table.lines[0].slots[0];
+        let a = table
+            .register_handler_with_context(0, context_handler, 1)
+            .unwrap();
+        // A call loads the epoch, then stalls before announcing itself, while
+        // A is unregistered and B takes the slot.
+        let stalled = slot.epoch.load(Ordering::SeqCst);
+        assert!(table.unregister_handler(0, a));
+        let b = table
+            .register_handler_with_context(0, context_handler, 2)
+            .unwrap();
+        // The stalled call must not be counted in the old epoch, which the
+        // unregistration of B would not wait for.
+        assert!(!slot.enter(stalled));
+        assert_eq!(slot.active[stalled & 1].load(Ordering::SeqCst), 0);
+
+        // A call of B announced in the current epoch is waited for.
+        let epoch = slot.epoch.load(Ordering::SeqCst);
+        assert!(slot.enter(epoch));
+        let unregistered = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                assert!(table.unregister_handler(0, b));
+                unregistered.store(true, Ordering::SeqCst);
+            });
+            while slot.seq.load(Ordering::SeqCst) & 1 == 0 {
+                std::thread::yield_now();
+            }
+            // The slot is not reused until the call has returned.
+            assert!(
+                table
+                    .register_handler_with_context(0, context_handler, 3)
+                    .is_none()
+            );
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!unregistered.load(Ordering::SeqCst));
+            slot.exit(epoch);
+        });
+        assert!(unregistered.load(Ordering::SeqCst));
+        assert!(
+            table
+                .register_handler_with_context(0, context_handler, 3)
+                .is_some()
+        );
+    }
+}
@@ -6,10 +6,15 @@
 extern crate crate_interface;
 
 pub mod console;
+pub mod devices;
+#[cfg(feature = "fdt")]
+pub mod fdt;
 pub mod init;
 #[cfg(feature = "irq")]
 pub mod irq;
 pub mod mem;
+#[cfg(feature = "pci")]
+pub mod pci;
 pub mod power;
 pub mod time;
 
@@ -67,9 +72,35 @@ pub fn call_main(cpu_id: usize, arg: usize) -> ! {
 /// This function should only be called by the platform implementer, not the kernel.
 #[cfg(feature = "smp")]
 pub fn call_secondary_main(cpu_id: usize) -> ! {
+    power::secondary_entered(cpu_id);
     unsafe { __axplat_secondary_main(cpu_id) }
 }
 
+/// Call the function decorated by [`axplat::main`][main] for the primary core,
+/// which has the given hardware ID (e.g., MPIDR or hart ID).
+///
+/// The primary core is registered in [`power::topology`] first, so that it is
+/// given the logical CPU ID 0.
+///
+/// This function should only be called by the platform implementer, not the kernel.
+pub fn call_main_hw(hw_id: usize, arg: usize) -> ! {
+    let cpu_id = power::topology::register(power::topology::CpuTopology::flat(hw_id));
+    call_main(cpu_id.unwrap_or(0), arg)
+}
+
+/// Call the function decorated by [`axplat::secondary_main`][secondary_main] for
+/// the secondary core with the given hardware ID, which is translated to the
+/// logical CPU ID by [`power::topology`].
+///
+/// This function should only be called by the platform implementer, not the kernel.
+#[cfg(feature = "smp")]
+pub fn call_secondary_main_hw(hw_id: usize) -> ! {
+    let Some(cpu_id) = power::topology::cpu_id(hw_id) else {
+        panic!("unknown CPU with hardware ID {hw_id:#x}");
+    };
+    call_secondary_main(cpu_id)
+}
+
 unsafe extern "Rust" {
     fn __axplat_main(cpu_id: usize, arg: usize) -> !;
     fn __axplat_secondary_main(cpu_id: usize) -> !;
@@ -1,5 +1,8 @@
 //! Physical memory information.
 
+pub mod image;
+pub mod map;
+
 use core::ops::{Deref, DerefMut};
 use core::{fmt, ops::Range};
 
@@ -23,6 +26,10 @@ bitflags::bitflags! {
         const RESERVED      = 1 << 6;
         /// Free memory for allocation.
         const FREE          = 1 << 7;
+        /// Accessible by the DMA of all devices. (see [`DmaZone::Dma`])
+        const DMA           = 1 << 8;
+        /// Accessible by the DMA of devices with 32-bit addresses. (see [`DmaZone::Dma32`])
+        const DMA32         = 1 << 9;
     }
 }
 
@@ -146,6 +153,73 @@ impl PhysMemRegion {
     }
 }
 
+/// A window through which devices access physical memory by DMA.
+///
+/// The physical range `[paddr, paddr + size)` is accessed by devices at the
+/// bus addresses `[bus_addr, bus_addr + size)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaRange {
+    /// The start physical address of the window.
+    pub paddr: PhysAddr,
+    /// The start bus address of the window.
+    pub bus_addr: usize,
+    /// The size in bytes of the window.
+    pub size: usize,
+}
+
+impl DmaRange {
+    /// Creates a DMA window.
+    pub const fn new(paddr: usize, bus_addr: usize, size: usize) -> Self {
+        Self {
+            paddr: PhysAddr::from_usize(paddr),
+            bus_addr,
+            size,
+        }
+    }
+
+    /// Translates a physical address to the bus address, or returns `None` if
+    /// it is not in the window.
+    pub fn phys_to_bus(&self, paddr: PhysAddr) -> Option<usize> {
+        let offset = paddr.as_usize().checked_sub(self.paddr.as_usize())?;
+        (offset < self.size).then(|| self.bus_addr + offset)
+    }
+
+    /// Translates a bus address to the physical address, or returns `None` if
+    /// it is not in the window.
+    pub fn bus_to_phys(&self, bus_addr: usize) -> Option<PhysAddr> {
+        let offset = bus_addr.checked_sub(self.bus_addr)?;
+        (offset < self.size).then(|| PhysAddr::from_usize(self.paddr.as_usize() + offset))
+    }
+}
+
+/// A zone of physical memory that can be accessed by the DMA of devices with
+/// restricted addressing capabilities.
+///
+/// A page allocator can serve DMA buffers from the RAM regions of the
+/// [memory map](map::memory_map) with the flag of the zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DmaZone {
+    /// Memory that can be accessed by the most restricted devices on the
+    /// platform (e.g., ISA devices on x86). It is contained in
+    /// [`DmaZone::Dma32`].
+    Dma,
+    /// Memory that can be accessed by devices with 32-bit DMA addresses.
+    Dma32,
+}
+
+impl DmaZone {
+    /// All DMA zones, from the most restricted one.
+    pub const ALL: [Self; 2] = [Self::Dma, Self::Dma32];
+
+    /// Returns the region flag of the zone.
+    pub const fn flag(self) -> MemRegionFlags {
+        match self {
+            Self::Dma => MemRegionFlags::DMA,
+            Self::Dma32 => MemRegionFlags::DMA32,
+        }
+    }
+}
+
 /// Physical memory interface.
 #[def_interface(gen_caller)]
 pub trait MemIf {
@@ -161,7 +235,7 @@ pub trait MemIf {
     /// allocatable but should be mapped to kernel's address space.
     ///
     /// Note that the ranges returned should not include the range where the
-    /// kernel is loaded.
+    /// kernel is loaded (see [`image::phys_range`]).
     fn reserved_phys_ram_ranges() -> &'static [RawRange];
 
     /// Returns all device memory (MMIO) ranges on the platform.
@@ -180,6 +254,41 @@ pub trait MemIf {
     /// `vaddr` must be available through the [`phys_to_virt`] translation.
     /// It **cannot** be used to translate arbitrary virtual addresses.
     fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr;
+
+    /// Whether the DMA of devices is coherent with the CPU caches.
+    ///
+    /// If not, drivers must clean the cache by [`cache_clean_range`] before a
+    /// device reads a buffer, and invalidate the cache by
+    /// [`cache_invalidate_range`] after a device writes a buffer.
+    fn dma_coherent() -> bool;
+
+    /// Cleans (writes back) the data cache lines that cover the given virtual
+    /// address range, so that the data is visible to devices.
+    fn cache_clean_range(vaddr: VirtAddr, size: usize);
+
+    /// Invalidates the data cache lines that cover the given virtual address
+    /// range, so that the data written by devices is visible to the CPU.
+    ///
+    /// The lines partially covered by the range are also cleaned, so that the
+    /// data outside the range is not discarded.
+    fn cache_invalidate_range(vaddr: VirtAddr, size: usize);
+
+    /// Cleans and invalidates the data cache lines that cover the given
+    /// virtual address range.
+    fn cache_flush_range(vaddr: VirtAddr, size: usize);
+
+    /// Returns the windows through which devices access physical memory by
+    /// DMA.
+    ///
+    /// Physical memory outside the windows can not be accessed by DMA, e.g.,
+    /// on platforms whose DMA controllers only reach the first 1 GiB.
+    fn dma_ranges() -> &'static [DmaRange];
+
+    /// Returns the end physical address (exclusive) of the given DMA zone.
+    ///
+    /// The zone starts from physical address `0`. Returns `None` if all
+    /// physical memory is in the zone.
+    fn dma_zone_end(zone: DmaZone) -> Option<usize>;
 }
 
 /// Returns the total size of physical memory (RAM) on the platform.
@@ -190,6 +299,25 @@ pub fn total_ram_size() -> usize {
     phys_ram_ranges().iter().map(|range| range.1).sum()
 }
 
+/// Translates a physical address to the bus address that devices use to
+/// access it by DMA.
+///
+/// Returns `None` if the address is not in any of the [`dma_ranges`].
+pub fn phys_to_bus(paddr: PhysAddr) -> Option<usize> {
+    dma_ranges()
+        .iter()
+        .find_map(|range| range.phys_to_bus(paddr))
+}
+
+/// Translates a bus address used by DMA to the physical address.
+///
+/// Returns `None` if the address is not in any of the [`dma_ranges`].
+pub fn bus_to_phys(bus_addr: usize) -> Option<PhysAddr> {
+    dma_ranges()
+        .iter()
+        .find_map(|range| range.bus_to_phys(bus_addr))
+}
+
 /// The error type for overlapping check.
 ///
 /// It contains the overlapping range pair.
@@ -340,6 +468,26 @@ mod tests {
         assert_eq!(f(&[(10, 10)], &[(0, 30)]), &[]); // - 0..30 = []
     }
 
+    #[test]
+    fn dma_range() {
+        use super::{DmaRange, pa};
+
+        let range = DmaRange::new(0, 0xc000_0000, 0x4000_0000);
+        assert_eq!(range.phys_to_bus(pa!(0x1000)), Some(0xc000_1000));
+        assert_eq!(range.phys_to_bus(pa!(0x4000_0000)), None);
+        assert_eq!(range.bus_to_phys(0xffff_ffff), Some(pa!(0x3fff_ffff)));
+        assert_eq!(range.bus_to_phys(0x1000), None);
+
+        let range = DmaRange::new(0x8000_0000, 0, 0x8000_0000);
+        assert_eq!(range.phys_to_bus(pa!(0x7fff_ffff)), None);
+        assert_eq!(range.phys_to_bus(pa!(0x8000_0000)), Some(0));
+        assert_eq!(range.bus_to_phys(0x7fff_ffff), Some(pa!(0xffff_ffff)));
+
+        let range = DmaRange::new(0, 0, usize::MAX);
+        assert_eq!(range.phys_to_bus(pa!(usize::MAX - 1)), Some(usize::MAX - 1));
+        assert_eq!(range.bus_to_phys(0), Some(pa!(0)));
+    }
+
     #[test]
     fn aligned_wrappers_have_expected_alignment() {
         assert_eq!(align_of::<super::Aligned4K<[u8; 1]>>(), 4096);
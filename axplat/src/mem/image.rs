@@ -0,0 +1,145 @@
+//! The layout of the kernel image.
+//!
+//! The boundaries of the sections are given by the symbols of the kernel's
+//! linker script (see `linker.lds.S` of the examples):
+//!
+//! | Section                | Start           | End              |
+//! |------------------------|-----------------|------------------|
+//! | (the whole image)      | `_skernel`      | `_ekernel`       |
+//! | [`Section::Text`]      | `_stext`        | `_etext`         |
+//! | [`Section::Rodata`]    | `_srodata`      | `_erodata`       |
+//! | [`Section::Data`]      | `_sdata`        | `_percpu_start`  |
+//! | [`Section::Percpu`]    | `_percpu_start` | `_percpu_end`    |
+//! | [`Section::BootStack`] | `boot_stack`    | `boot_stack_top` |
+//! | [`Section::Bss`]       | `_sbss`         | `_ebss`          |
+//!
+//! The symbols are virtual addresses, the physical ones are translated by
+//! [`virt_to_phys`].
+
+use core::ops::Range;
+
+use super::{MemRegionFlags, PhysAddr, PhysMemRegion, VirtAddr, va, virt_to_phys};
+
+unsafe extern "C" {
+    fn _skernel();
+    fn _stext();
+    fn _etext();
+    fn _srodata();
+    fn _erodata();
+    fn _sdata();
+    fn _percpu_start();
+    fn _percpu_end();
+    fn boot_stack();
+    fn boot_stack_top();
+    fn _sbss();
+    fn _ebss();
+    fn _ekernel();
+}
+
+fn symbol_addr(symbol: unsafe extern "C" fn()) -> VirtAddr {
+    va!(symbol as *const () as usize)
+}
+
+fn to_phys(range: Range<VirtAddr>) -> Range<PhysAddr> {
+    virt_to_phys(range.start)..virt_to_phys(range.end)
+}
+
+/// A section of the kernel image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// The code (`.text`).
+    Text,
+    /// The read-only data (`.rodata`).
+    Rodata,
+    /// The initialized data (`.data`).
+    Data,
+    /// The template of per-CPU data (`.percpu`).
+    Percpu,
+    /// The boot stacks (`.bss.stack`).
+    BootStack,
+    /// The zero-initialized data (`.bss`), excluding the boot stacks.
+    Bss,
+}
+
+impl Section {
+    /// All sections, in the order of their addresses.
+    pub const ALL: [Self; 6] = [
+        Self::Text,
+        Self::Rodata,
+        Self::Data,
+        Self::Percpu,
+        Self::BootStack,
+        Self::Bss,
+    ];
+
+    /// Returns the name of the section.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Text => ".text",
+            Self::Rodata => ".rodata",
+            Self::Data => ".data",
+            Self::Percpu => ".percpu",
+            Self::BootStack => "boot stack",
+            Self::Bss => ".bss",
+        }
+    }
+
+    /// Returns the flags that the section should be mapped with.
+    pub const fn flags(self) -> MemRegionFlags {
+        match self {
+            Self::Text => MemRegionFlags::READ
+                .union(MemRegionFlags::EXECUTE)
+                .union(MemRegionFlags::RESERVED),
+            Self::Rodata => MemRegionFlags::READ.union(MemRegionFlags::RESERVED),
+            _ => MemRegionFlags::READ
+                .union(MemRegionFlags::WRITE)
+                .union(MemRegionFlags::RESERVED),
+        }
+    }
+
+    /// Returns the virtual address range of the section.
+    pub fn virt_range(self) -> Range<VirtAddr> {
+        let (start, end): (unsafe extern "C" fn(), unsafe extern "C" fn()) = match self {
+            Self::Text => (_stext, _etext),
+            Self::Rodata => (_srodata, _erodata),
+            Self::Data => (_sdata, _percpu_start),
+            Self::Percpu => (_percpu_start, _percpu_end),
+            Self::BootStack => (boot_stack, boot_stack_top),
+            Self::Bss => (_sbss, _ebss),
+        };
+        symbol_addr(start)..symbol_addr(end)
+    }
+
+    /// Returns the physical address range of the section.
+    pub fn phys_range(self) -> Range<PhysAddr> {
+        to_phys(self.virt_range())
+    }
+
+    /// Returns the section as a physical memory region, named by
+    /// [`Section::name`].
+    pub fn region(self) -> PhysMemRegion {
+        let range = self.phys_range();
+        PhysMemRegion {
+            paddr: range.start,
+            size: range.end.as_usize() - range.start.as_usize(),
+            flags: self.flags(),
+            name: self.name(),
+        }
+    }
+}
+
+/// Returns the virtual address range of the whole kernel image.
+pub fn virt_range() -> Range<VirtAddr> {
+    symbol_addr(_skernel)..symbol_addr(_ekernel)
+}
+
+/// Returns the physical address range where the whole kernel image is loaded.
+pub fn phys_range() -> Range<PhysAddr> {
+    to_phys(virt_range())
+}
+
+/// Returns the physical memory regions of all sections, in the order of
+/// their addresses.
+pub fn regions() -> impl Iterator<Item = PhysMemRegion> {
+    Section::ALL.into_iter().map(Section::region)
+}
@@ -0,0 +1,381 @@
+//! The physical memory map.
+//!
+//! [`memory_map`] merges the ranges returned by [`MemIf`](super::MemIf) and
+//! the range where the kernel image is loaded into a list of non-overlapping
+//! [`PhysMemRegion`]s sorted by the start address, each named by its kind:
+//!
+//! - [`KERNEL_IMAGE`]: the whole kernel image ([`image::phys_range`](super::image::phys_range)).
+//! - [`RESERVED`]: the reserved ranges ([`reserved_phys_ram_ranges`]).
+//! - [`MMIO`]: the device memory ranges ([`mmio_ranges`]).
+//! - [`FREE_MEMORY`]: the rest of RAM ([`phys_ram_ranges`]), which is free
+//!   for allocation. It is split at the ends of the [`DmaZone`]s, and the
+//!   regions in a zone have its [flag](DmaZone::flag).
+//!
+//! Overlapping ranges of the same kind are merged, but those of different
+//! kinds (e.g., a MMIO range in RAM) make the map invalid. Platforms call
+//! [`validate`] at the early stage, so that such errors are reported at boot.
+
+use core::fmt;
+use core::ops::Deref;
+
+use super::{
+    DEFAULT_RAM_FLAGS, DmaZone, MemRegionFlags, PhysAddr, PhysMemRegion, RawRange, dma_zone_end,
+    mmio_ranges, phys_ram_ranges, ranges_difference, reserved_phys_ram_ranges,
+};
+
+/// The maximum number of regions in the memory map.
+pub const MAX_MEM_REGIONS: usize = 128;
+
+/// The name of the region where the kernel image is loaded.
+pub const KERNEL_IMAGE: &str = "kernel image";
+/// The name of the reserved regions.
+pub const RESERVED: &str = "reserved";
+/// The name of the device memory (MMIO) regions.
+pub const MMIO: &str = "mmio";
+/// The name of the RAM regions that are free for allocation.
+pub const FREE_MEMORY: &str = "free memory";
+
+/// The flags of the kernel image region (readable, writable, executable, and
+/// reserved).
+const KERNEL_IMAGE_FLAGS: MemRegionFlags = MemRegionFlags::READ
+    .union(MemRegionFlags::WRITE)
+    .union(MemRegionFlags::EXECUTE)
+    .union(MemRegionFlags::RESERVED);
+
+const EMPTY_REGION: PhysMemRegion = PhysMemRegion::new_ram(0, 0, "");
+
+/// The error type of building the memory map.
+#[derive(Debug, Clone, Copy)]
+pub enum MemMapError {
+    /// Two regions of different kinds overlap.
+    Overlap(PhysMemRegion, PhysMemRegion),
+    /// There are more than [`MAX_MEM_REGIONS`] regions.
+    TooManyRegions,
+}
+
+impl fmt::Display for MemMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Overlap(a, b) => write!(
+                f,
+                "{} [{:#x}, {:#x}) overlaps {} [{:#x}, {:#x})",
+                a.name,
+                start(a),
+                end(a),
+                b.name,
+                start(b),
+                end(b),
+            ),
+            Self::TooManyRegions => write!(f, "more than {MAX_MEM_REGIONS} memory regions"),
+        }
+    }
+}
+
+const fn start(region: &PhysMemRegion) -> usize {
+    region.paddr.as_usize()
+}
+
+const fn end(region: &PhysMemRegion) -> usize {
+    region.paddr.as_usize() + region.size
+}
+
+/// A list of non-overlapping physical memory regions, sorted by the start
+/// address.
+///
+/// It dereferences to a slice of [`PhysMemRegion`]s, so it can be iterated
+/// directly.
+#[derive(Clone)]
+pub struct MemMap {
+    regions: [PhysMemRegion; MAX_MEM_REGIONS],
+    len: usize,
+}
+
+impl MemMap {
+    const fn new() -> Self {
+        Self {
+            regions: [EMPTY_REGION; MAX_MEM_REGIONS],
+            len: 0,
+        }
+    }
+
+    /// Returns the region that contains the given physical address.
+    pub fn find(&self, paddr: PhysAddr) -> Option<&PhysMemRegion> {
+        let paddr = paddr.as_usize();
+        let idx = self.partition_point(|r| start(r) <= paddr);
+        idx.checked_sub(1)
+            .map(|idx| &self[idx])
+            .filter(|r| paddr < end(r))
+    }
+
+    /// Inserts a region, keeping the regions sorted by the start address.
+    fn insert(&mut self, region: PhysMemRegion) -> Result<(), MemMapError> {
+        if region.size == 0 {
+            return Ok(());
+        }
+        if self.len == MAX_MEM_REGIONS {
+            return Err(MemMapError::TooManyRegions);
+        }
+        let pos = self.partition_point(|r| start(r) <= start(&region));
+        self.regions.copy_within(pos..self.len, pos + 1);
+        self.regions[pos] = region;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Merges the overlapping or adjacent regions of the same kind.
+    ///
+    /// Returns an error if regions of different kinds overlap.
+    fn merge(&mut self) -> Result<(), MemMapError> {
+        let mut len = 0;
+        for i in 0..self.len {
+            let region = self.regions[i];
+            if len > 0 {
+                let last = &mut self.regions[len - 1];
+                if end(last) >= start(&region)
+                    && last.name == region.name
+                    && last.flags.bits() == region.flags.bits()
+                {
+                    last.size = end(last).max(end(&region)) - start(last);
+                    continue;
+                }
+                if end(last) > start(&region) {
+                    return Err(MemMapError::Overlap(*last, region));
+                }
+            }
+            self.regions[len] = region;
+            len += 1;
+        }
+        self.len = len;
+        Ok(())
+    }
+
+    /// Inserts a free RAM range, split at the ends of DMA zones.
+    fn insert_free(
+        &mut self,
+        (start, size): RawRange,
+        dma_zone_end: &impl Fn(DmaZone) -> Option<usize>,
+    ) -> Result<(), MemMapError> {
+        let (mut start, end) = (start, start + size);
+        while start < end {
+            let mut flags = DEFAULT_RAM_FLAGS;
+            let mut next = end;
+            for zone in DmaZone::ALL {
+                match dma_zone_end(zone) {
+                    None => flags |= zone.flag(),
+                    Some(zone_end) if start < zone_end => {
+                        flags |= zone.flag();
+                        next = next.min(zone_end);
+                    }
+                    Some(_) => {}
+                }
+            }
+            self.insert(PhysMemRegion {
+                paddr: PhysAddr::from_usize(start),
+                size: next - start,
+                flags,
+                name: FREE_MEMORY,
+            })?;
+            start = next;
+        }
+        Ok(())
+    }
+
+    fn raw_ranges(&self) -> ([RawRange; MAX_MEM_REGIONS], usize) {
+        let mut ranges = [(0, 0); MAX_MEM_REGIONS];
+        for (range, region) in ranges.iter_mut().zip(self.iter()) {
+            *range = (start(region), region.size);
+        }
+        (ranges, self.len)
+    }
+
+    /// Builds the memory map from the given ranges.
+    fn build(
+        ram: &[RawRange],
+        reserved: &[RawRange],
+        kernel_image: RawRange,
+        mmio: &[RawRange],
+        dma_zone_end: impl Fn(DmaZone) -> Option<usize>,
+    ) -> Result<Self, MemMapError> {
+        let mut map = Self::new();
+        map.insert(PhysMemRegion {
+            paddr: PhysAddr::from_usize(kernel_image.0),
+            size: kernel_image.1,
+            flags: KERNEL_IMAGE_FLAGS,
+            name: KERNEL_IMAGE,
+        })?;
+        for &(start, size) in reserved {
+            map.insert(PhysMemRegion::new_reserved(start, size, RESERVED))?;
+        }
+        for &(start, size) in mmio {
+            map.insert(PhysMemRegion::new_mmio(start, size, MMIO))?;
+        }
+        map.merge()?;
+
+        let mut ram_map = Self::new();
+        for &(start, size) in ram {
+            ram_map.insert(PhysMemRegion::new_ram(start, size, FREE_MEMORY))?;
+        }
+        ram_map.merge()?;
+        for mmio in map
+            .iter()
+            .filter(|r| r.flags.contains(MemRegionFlags::DEVICE))
+        {
+            if let Some(ram) = ram_map
+                .iter()
+                .find(|r| start(r) < end(mmio) && start(mmio) < end(r))
+            {
+                return Err(MemMapError::Overlap(*ram, *mmio));
+            }
+        }
+
+        let (from, from_len) = ram_map.raw_ranges();
+        let (exclude, exclude_len) = map.raw_ranges();
+        let mut res = Ok(());
+        // `exclude` has been merged, so it is sorted and non-overlapping.
+        let _ = ranges_difference(&from[..from_len], &exclude[..exclude_len], |range| {
+            if res.is_ok() {
+                res = map.insert_free(range, &dma_zone_end);
+            }
+        });
+        res.map(|_| map)
+    }
+}
+
+impl Deref for MemMap {
+    type Target = [PhysMemRegion];
+
+    fn deref(&self) -> &Self::Target {
+        &self.regions[..self.len]
+    }
+}
+
+impl<'a> IntoIterator for &'a MemMap {
+    type Item = &'a PhysMemRegion;
+    type IntoIter = core::slice::Iter<'a, PhysMemRegion>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for MemMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Returns the complete physical memory map of the platform.
+///
+/// Returns an error if regions of different kinds overlap, or there are too
+/// many regions.
+pub fn memory_map() -> Result<MemMap, MemMapError> {
+    let image = super::image::phys_range();
+    let image_size = image.end.as_usize() - image.start.as_usize();
+    MemMap::build(
+        phys_ram_ranges(),
+        reserved_phys_ram_ranges(),
+        (image.start.as_usize(), image_size),
+        mmio_ranges(),
+        dma_zone_end,
+    )
+}
+
+/// Checks the physical memory map of the platform.
+///
+/// It is called by the platform at the early stage, once the ranges returned
+/// by [`MemIf`](super::MemIf) are available.
+///
+/// # Panics
+///
+/// Panics with the overlapping regions if the memory map is invalid.
+pub fn validate() {
+    if let Err(err) = memory_map() {
+        panic!("invalid physical memory map: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FREE_MEMORY, KERNEL_IMAGE, MMIO, MemMap, MemMapError, RESERVED};
+    use crate::mem::{DmaZone, MemRegionFlags, pa};
+
+    #[test]
+    fn build() {
+        let map = MemMap::build(
+            &[(0x8000_0000, 0x1000_0000)],
+            &[
+                (0x8000_0000, 0x4_0000),
+                (0x8800_0000, 0x1000),
+                (0x8800_0000, 0x1000),
+            ],
+            (0x8020_0000, 0x10_0000),
+            &[
+                (0x1000_0000, 0x1000),
+                (0x1000_0000, 0x8000),
+                (0x0c00_0000, 0x21_0000),
+            ],
+            |_| None,
+        )
+        .unwrap();
+        let regions: Vec<_> = map
+            .iter()
+            .map(|r| (r.paddr.as_usize(), r.size, r.name))
+            .collect();
+        assert_eq!(
+            regions,
+            [
+                (0x0c00_0000, 0x21_0000, MMIO),
+                (0x1000_0000, 0x8000, MMIO),
+                (0x8000_0000, 0x4_0000, RESERVED),
+                (0x8004_0000, 0x1c_0000, FREE_MEMORY),
+                (0x8020_0000, 0x10_0000, KERNEL_IMAGE),
+                (0x8030_0000, 0x7d0_0000, FREE_MEMORY),
+                (0x8800_0000, 0x1000, RESERVED),
+                (0x8800_1000, 0x7ff_f000, FREE_MEMORY),
+            ]
+        );
+        assert_eq!(map.find(pa!(0x8020_1000)).unwrap().name, KERNEL_IMAGE);
+        assert_eq!(map.find(pa!(0x8fff_ffff)).unwrap().name, FREE_MEMORY);
+        assert!(map.find(pa!(0x9000_0000)).is_none());
+        assert!(map.find(pa!(0)).is_none());
+
+        // The kernel image overlaps a reserved range.
+        let err = MemMap::build(&[(0, 0x1000)], &[(0, 0x200)], (0x100, 0x100), &[], |_| None);
+        assert!(matches!(
+            err,
+            Err(MemMapError::Overlap(a, b)) if a.name == RESERVED && b.name == KERNEL_IMAGE
+        ));
+        // A MMIO range is in RAM.
+        let err = MemMap::build(&[(0, 0x1000)], &[], (0, 0x100), &[(0xf00, 0x200)], |_| None);
+        assert!(matches!(
+            err,
+            Err(MemMapError::Overlap(a, b)) if a.name == FREE_MEMORY && b.name == MMIO
+        ));
+    }
+
+    #[test]
+    fn dma_zones() {
+        let map = MemMap::build(&[(0, 0x2000)], &[], (0x1000, 0x100), &[], |zone| {
+            Some(match zone {
+                DmaZone::Dma => 0x800,
+                DmaZone::Dma32 => 0x1800,
+            })
+        })
+        .unwrap();
+        let (dma, dma32) = (MemRegionFlags::DMA.bits(), MemRegionFlags::DMA32.bits());
+        let regions: Vec<_> = map
+            .iter()
+            .map(|r| (r.paddr.as_usize(), r.size, r.flags.bits() & (dma | dma32)))
+            .collect();
+        assert_eq!(
+            regions,
+            [
+                (0, 0x800, dma | dma32),
+                (0x800, 0x800, dma32),
+                (0x1000, 0x100, 0),
+                (0x1100, 0x700, dma32),
+                (0x1800, 0x800, 0),
+            ]
+        );
+    }
+}
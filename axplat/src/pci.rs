@@ -0,0 +1,1013 @@
+//! PCI host bridge support.
+//!
+//! Platforms that have a PCI host bridge describe it by [`HostBridge`]: how
+//! the configuration space is accessed ([`ConfigAccess`], e.g., [`Ecam`]), the
+//! last bus number, the address windows for BARs ([`PciWindows`], usually
+//! given by `pci-ranges` in the config) and how legacy INTx interrupts are
+//! routed ([`IntxRouting`]). The platform calls [`init`] at the later stage,
+//! which enumerates the buses, assigns the bus numbers of PCI-to-PCI bridges
+//! and the BARs of all functions, and enables them. Then the kernel can get
+//! the ready-to-use functions by [`devices`].
+//!
+//! If no window is given (e.g., on x86, where the firmware has configured
+//! everything), the bus numbers and BARs set by the firmware are kept as is.
+
+use core::fmt;
+use core::ops::Deref;
+
+use lazyinit::LazyInit;
+use memory_addr::PhysAddr;
+
+use crate::mem::{RawRange, phys_to_virt};
+
+/// The maximum number of PCI functions recorded by [`HostBridge::enumerate`].
+pub const MAX_PCI_DEVICES: usize = 64;
+
+const REG_ID: u16 = 0x00;
+const REG_COMMAND: u16 = 0x04;
+const REG_CLASS: u16 = 0x08;
+const REG_HEADER_TYPE: u16 = 0x0c;
+const REG_BAR0: u16 = 0x10;
+const REG_BUS_NUMBERS: u16 = 0x18;
+const REG_IO_WINDOW: u16 = 0x1c;
+const REG_MEM_WINDOW: u16 = 0x20;
+const REG_PREF_WINDOW: u16 = 0x24;
+const REG_PREF_BASE_UPPER: u16 = 0x28;
+const REG_PREF_LIMIT_UPPER: u16 = 0x2c;
+const REG_IO_WINDOW_UPPER: u16 = 0x30;
+const REG_INTERRUPT: u16 = 0x3c;
+
+const COMMAND_IO: u32 = 1 << 0;
+const COMMAND_MEM: u32 = 1 << 1;
+const COMMAND_BUS_MASTER: u32 = 1 << 2;
+
+/// PCI I/O ports below it are left for legacy devices.
+const IO_ALLOC_START: usize = 0x1000;
+/// The alignment of the I/O windows of PCI-to-PCI bridges.
+const BRIDGE_IO_ALIGN: usize = 0x1000;
+/// The alignment of the memory windows of PCI-to-PCI bridges.
+const BRIDGE_MEM_ALIGN: usize = 0x10_0000;
+
+/// The location of a PCI function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciAddress {
+    /// The bus number.
+    pub bus: u8,
+    /// The device number on the bus, less than 32.
+    pub device: u8,
+    /// The function number of the device, less than 8.
+    pub function: u8,
+}
+
+impl PciAddress {
+    /// Creates a new PCI address.
+    pub const fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self {
+            bus,
+            device,
+            function,
+        }
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}.{}", self.bus, self.device, self.function)
+    }
+}
+
+/// Access to the PCI configuration space.
+///
+/// The configuration space is accessed in aligned 32-bit words, `offset` is
+/// the byte offset of the word in the configuration space of the function.
+/// Reading a function that does not exist returns all ones.
+pub trait ConfigAccess: Send + Sync {
+    /// Reads a 32-bit word from the configuration space of a function.
+    fn read(&self, addr: PciAddress, offset: u16) -> u32;
+
+    /// Writes a 32-bit word to the configuration space of a function.
+    fn write(&self, addr: PciAddress, offset: u16, value: u32);
+}
+
+/// The PCIe Enhanced Configuration Access Mechanism (ECAM), where the
+/// configuration space of each function is mapped to 4K bytes of MMIO.
+///
+/// The ECAM region is accessed through [`phys_to_virt`], so it must be mapped
+/// before [`init`] is called.
+#[derive(Debug)]
+pub struct Ecam {
+    base: PhysAddr,
+}
+
+impl Ecam {
+    /// Creates the ECAM access to the region at the given physical address,
+    /// which is for bus 0 and up.
+    pub const fn new(base: PhysAddr) -> Self {
+        Self { base }
+    }
+
+    fn reg(&self, addr: PciAddress, offset: u16) -> *mut u32 {
+        let offset = ((addr.bus as usize) << 20)
+            | ((addr.device as usize) << 15)
+            | ((addr.function as usize) << 12)
+            | (offset as usize & 0xffc);
+        phys_to_virt(self.base + offset).as_mut_ptr() as *mut u32
+    }
+}
+
+impl ConfigAccess for Ecam {
+    fn read(&self, addr: PciAddress, offset: u16) -> u32 {
+        unsafe { self.reg(addr, offset).read_volatile() }
+    }
+
+    fn write(&self, addr: PciAddress, offset: u16, value: u32) {
+        unsafe { self.reg(addr, offset).write_volatile(value) }
+    }
+}
+
+/// The address windows of a host bridge, from which the BARs are assigned.
+///
+/// Each window is in the format of (`base_paddr`, `size`). PCI memory
+/// addresses are the same as CPU physical addresses, and PCI I/O ports are
+/// mapped to the PIO window starting from port 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PciWindows {
+    /// The window of the PCI I/O space.
+    pub pio: Option<RawRange>,
+    /// The window of the 32-bit PCI memory space.
+    pub mmio32: Option<RawRange>,
+    /// The window of the 64-bit PCI memory space, for 64-bit prefetchable
+    /// BARs.
+    pub mmio64: Option<RawRange>,
+}
+
+impl PciWindows {
+    /// Creates the windows from `pci-ranges` of the config, which lists the
+    /// PIO, 32-bit and 64-bit MMIO windows in order. Missing or empty ranges
+    /// are ignored.
+    pub const fn from_ranges(ranges: &[RawRange]) -> Self {
+        const fn get(ranges: &[RawRange], i: usize) -> Option<RawRange> {
+            if i < ranges.len() && ranges[i].1 != 0 {
+                Some(ranges[i])
+            } else {
+                None
+            }
+        }
+        Self {
+            pio: get(ranges, 0),
+            mmio32: get(ranges, 1),
+            mmio64: get(ranges, 2),
+        }
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.pio.is_none() && self.mmio32.is_none() && self.mmio64.is_none()
+    }
+}
+
+/// How the legacy INTx interrupts of PCI functions are routed to the IRQs of
+/// the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntxRouting {
+    /// INTA to INTD of the host bridge are IRQ `base` to `base + 3`, and the
+    /// pins of devices are swizzled by their device numbers, like the
+    /// `interrupt-map` of the QEMU `virt` machines.
+    Swizzle {
+        /// The IRQ number of INTA.
+        base: usize,
+    },
+    /// The IRQ is `offset` plus the Interrupt Line register, which is set by
+    /// the firmware.
+    InterruptLine {
+        /// The offset added to the Interrupt Line.
+        offset: usize,
+    },
+    /// The IRQs are unknown.
+    None,
+}
+
+/// An INTx interrupt pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum IntxPin {
+    /// INTA#.
+    A = 0,
+    /// INTB#.
+    B = 1,
+    /// INTC#.
+    C = 2,
+    /// INTD#.
+    D = 3,
+}
+
+impl IntxPin {
+    /// Converts the value of the Interrupt Pin register.
+    const fn from_register(pin: u8) -> Option<Self> {
+        match pin {
+            1 => Some(Self::A),
+            2 => Some(Self::B),
+            3 => Some(Self::C),
+            4 => Some(Self::D),
+            _ => None,
+        }
+    }
+}
+
+/// The legacy INTx interrupt of a PCI function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Intx {
+    /// The pin used by the function.
+    pub pin: IntxPin,
+    /// The IRQ number that the pin is routed to, if known.
+    pub irq: Option<usize>,
+}
+
+/// The kind of a BAR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BarKind {
+    /// I/O space.
+    Io,
+    /// 32-bit memory space.
+    Mem32,
+    /// 64-bit memory space, which takes two BAR slots.
+    Mem64,
+}
+
+/// An assigned Base Address Register (BAR).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bar {
+    /// The kind of the BAR.
+    pub kind: BarKind,
+    /// Whether the memory is prefetchable.
+    pub prefetchable: bool,
+    /// The CPU physical address of the BAR.
+    ///
+    /// For I/O BARs, it is the base of the PIO window plus the port number,
+    /// or just the port number if there is no PIO window (e.g., on x86).
+    pub addr: usize,
+    /// The size of the BAR in bytes.
+    pub size: usize,
+}
+
+/// A PCI function found by [`HostBridge::enumerate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciDevice {
+    /// The location of the function.
+    pub addr: PciAddress,
+    /// The vendor ID.
+    pub vendor_id: u16,
+    /// The device ID.
+    pub device_id: u16,
+    /// The base class code.
+    pub class: u8,
+    /// The subclass code.
+    pub subclass: u8,
+    /// The programming interface.
+    pub prog_if: u8,
+    /// The revision ID.
+    pub revision: u8,
+    /// The header type, without the multi-function bit. It is 0 for endpoints
+    /// and 1 for PCI-to-PCI bridges.
+    pub header_type: u8,
+    /// The BARs. A 64-bit BAR is in the slot of its lower half, and the next
+    /// slot is `None`. Unimplemented or unassigned BARs are `None`.
+    pub bars: [Option<Bar>; 6],
+    /// The legacy INTx interrupt, or `None` if the function does not use one.
+    pub intx: Option<Intx>,
+}
+
+/// A list of PCI functions with fixed capacity, in the order of enumeration.
+#[derive(Clone)]
+pub struct PciDeviceList {
+    devices: [PciDevice; MAX_PCI_DEVICES],
+    len: usize,
+}
+
+impl PciDeviceList {
+    const fn new() -> Self {
+        const EMPTY: PciDevice = PciDevice {
+            addr: PciAddress::new(0, 0, 0),
+            vendor_id: 0xffff,
+            device_id: 0xffff,
+            class: 0,
+            subclass: 0,
+            prog_if: 0,
+            revision: 0,
+            header_type: 0,
+            bars: [None; 6],
+            intx: None,
+        };
+        Self {
+            devices: [EMPTY; MAX_PCI_DEVICES],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, device: PciDevice) {
+        if self.len < MAX_PCI_DEVICES {
+            self.devices[self.len] = device;
+            self.len += 1;
+        }
+    }
+}
+
+impl Deref for PciDeviceList {
+    type Target = [PciDevice];
+
+    fn deref(&self) -> &[PciDevice] {
+        &self.devices[..self.len]
+    }
+}
+
+impl fmt::Debug for PciDeviceList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A PCI host bridge.
+pub struct HostBridge {
+    access: &'static dyn ConfigAccess,
+    bus_end: u8,
+    windows: PciWindows,
+    intx: IntxRouting,
+}
+
+impl HostBridge {
+    /// Creates a host bridge with buses from 0 to `bus_end` (the `pci-bus-end`
+    /// of the config).
+    pub const fn new(
+        access: &'static dyn ConfigAccess,
+        bus_end: u8,
+        windows: PciWindows,
+        intx: IntxRouting,
+    ) -> Self {
+        Self {
+            access,
+            bus_end,
+            windows,
+            intx,
+        }
+    }
+
+    /// Reads a 32-bit word from the configuration space of a function.
+    pub fn read_config(&self, addr: PciAddress, offset: u16) -> u32 {
+        self.access.read(addr, offset)
+    }
+
+    /// Writes a 32-bit word to the configuration space of a function.
+    pub fn write_config(&self, addr: PciAddress, offset: u16, value: u32) {
+        self.access.write(addr, offset, value)
+    }
+
+    /// Enumerates all functions behind the host bridge, and configures them.
+    ///
+    /// PCI-to-PCI bridges are assigned bus numbers depth-first, and BARs are
+    /// assigned from the windows of the host bridge, then the I/O space,
+    /// memory space and bus mastering of the functions are enabled. Functions
+    /// beyond [`MAX_PCI_DEVICES`] are configured but not recorded.
+    pub fn enumerate(&self) -> PciDeviceList {
+        let mut e = Enumerator::new(self);
+        e.scan_bus(0, 0);
+        e.devices
+    }
+}
+
+impl fmt::Debug for HostBridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostBridge")
+            .field("bus_end", &self.bus_end)
+            .field("windows", &self.windows)
+            .field("intx", &self.intx)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A bump allocator of PCI addresses.
+#[derive(Debug, Clone, Copy)]
+struct Allocator {
+    next: usize,
+    end: usize,
+}
+
+impl Allocator {
+    fn new(start: usize, end: usize) -> Self {
+        Self {
+            next: start.min(end),
+            end,
+        }
+    }
+
+    /// Allocates `size` bytes aligned to `size`, which is a power of two.
+    fn alloc(&mut self, size: usize) -> Option<usize> {
+        let start = self.next.checked_next_multiple_of(size)?;
+        let end = start.checked_add(size)?;
+        if end > self.end {
+            return None;
+        }
+        self.next = end;
+        Some(start)
+    }
+
+    fn align(&mut self, align: usize) {
+        self.next = self
+            .next
+            .checked_next_multiple_of(align)
+            .map_or(self.end, |next| next.min(self.end));
+    }
+}
+
+struct Enumerator<'a> {
+    bridge: &'a HostBridge,
+    /// Whether to assign bus numbers and BARs, or keep the ones set by the
+    /// firmware.
+    assign: bool,
+    io: Option<Allocator>,
+    mem32: Option<Allocator>,
+    mem64: Option<Allocator>,
+    last_bus: u8,
+    devices: PciDeviceList,
+}
+
+impl<'a> Enumerator<'a> {
+    fn new(bridge: &'a HostBridge) -> Self {
+        let windows = &bridge.windows;
+        Self {
+            bridge,
+            assign: !windows.is_empty(),
+            io: windows
+                .pio
+                .map(|(_, size)| Allocator::new(IO_ALLOC_START, size)),
+            mem32: windows
+                .mmio32
+                .map(|(base, size)| Allocator::new(base, base + size)),
+            mem64: windows
+                .mmio64
+                .map(|(base, size)| Allocator::new(base, base + size)),
+            last_bus: 0,
+            devices: PciDeviceList::new(),
+        }
+    }
+
+    fn read(&self, addr: PciAddress, offset: u16) -> u32 {
+        self.bridge.read_config(addr, offset)
+    }
+
+    fn write(&self, addr: PciAddress, offset: u16, value: u32) {
+        self.bridge.write_config(addr, offset, value)
+    }
+
+    /// Scans a bus, where `slot_sum` is the sum of the device numbers of the
+    /// bridges from the host bridge to the bus, for INTx swizzling.
+    fn scan_bus(&mut self, bus: u8, slot_sum: usize) {
+        for device in 0..32 {
+            for function in 0..8 {
+                let addr = PciAddress::new(bus, device, function);
+                if self.read(addr, REG_ID) & 0xffff == 0xffff {
+                    if function == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                let header_type = (self.read(addr, REG_HEADER_TYPE) >> 16) as u8;
+                self.probe(addr, header_type & 0x7f, slot_sum);
+                if function == 0 && header_type & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn probe(&mut self, addr: PciAddress, header_type: u8, slot_sum: usize) {
+        let id = self.read(addr, REG_ID);
+        let class = self.read(addr, REG_CLASS);
+        // Disable decoding while sizing the BARs.
+        let command = self.read(addr, REG_COMMAND) & 0xffff;
+        self.write(addr, REG_COMMAND, command & !(COMMAND_IO | COMMAND_MEM));
+
+        let bar_num = match header_type {
+            0 => 6,
+            1 => 2,
+            _ => 0,
+        };
+        let mut bars = [None; 6];
+        let mut i = 0;
+        while i < bar_num {
+            let (bar, slots) = self.setup_bar(addr, i, bar_num);
+            bars[i] = bar;
+            i += slots;
+        }
+        self.devices.push(PciDevice {
+            addr,
+            vendor_id: id as u16,
+            device_id: (id >> 16) as u16,
+            class: (class >> 24) as u8,
+            subclass: (class >> 16) as u8,
+            prog_if: (class >> 8) as u8,
+            revision: class as u8,
+            header_type,
+            bars,
+            intx: self.intx(addr, slot_sum),
+        });
+
+        let mut command = command | COMMAND_BUS_MASTER;
+        for bar in bars.iter().flatten() {
+            command |= match bar.kind {
+                BarKind::Io => COMMAND_IO,
+                _ => COMMAND_MEM,
+            };
+        }
+        if header_type == 1 {
+            self.setup_bridge(addr, slot_sum);
+            command |= COMMAND_IO | COMMAND_MEM;
+        }
+        self.write(addr, REG_COMMAND, command);
+    }
+
+    /// Sizes and assigns the BAR at the given index of the `bar_num` BARs, and
+    /// returns it along with the number of slots it takes.
+    fn setup_bar(
+        &mut self,
+        addr: PciAddress,
+        index: usize,
+        bar_num: usize,
+    ) -> (Option<Bar>, usize) {
+        let offset = REG_BAR0 + index as u16 * 4;
+        let orig = self.read(addr, offset);
+        self.write(addr, offset, u32::MAX);
+        let mask = self.read(addr, offset);
+        self.write(addr, offset, orig);
+        if mask == 0 {
+            return (None, 1);
+        }
+
+        if orig & 1 != 0 {
+            let size = ((!(mask & !0x3)).wrapping_add(1) & 0xffff) as usize;
+            let port = match self.io.as_mut().and_then(|io| io.alloc(size)) {
+                Some(port) => {
+                    self.write(addr, offset, port as u32);
+                    port
+                }
+                None => (orig & !0x3) as usize,
+            };
+            let pio_base = self.bridge.windows.pio.map_or(0, |(base, _)| base);
+            let bar = (size != 0 && port != 0).then_some(Bar {
+                kind: BarKind::Io,
+                prefetchable: false,
+                addr: pio_base + port,
+                size,
+            });
+            return (bar, 1);
+        }
+
+        let prefetchable = orig & 0x8 != 0;
+        let is_64bit = (orig >> 1) & 0x3 == 2 && index + 1 < bar_num;
+        let (orig, mask) = if is_64bit {
+            let orig_hi = self.read(addr, offset + 4);
+            self.write(addr, offset + 4, u32::MAX);
+            let mask_hi = self.read(addr, offset + 4);
+            self.write(addr, offset + 4, orig_hi);
+            (
+                ((orig_hi as u64) << 32) | (orig & !0xf) as u64,
+                ((mask_hi as u64) << 32) | (mask & !0xf) as u64,
+            )
+        } else {
+            (
+                (orig & !0xf) as u64,
+                (mask & !0xf) as u64 | 0xffff_ffff_0000_0000,
+            )
+        };
+        let slots = if is_64bit { 2 } else { 1 };
+        let Ok(size) = usize::try_from((!mask).wrapping_add(1)) else {
+            return (None, slots);
+        };
+        if size == 0 {
+            return (None, slots);
+        }
+
+        let window = if is_64bit && prefetchable && self.mem64.is_some() {
+            &mut self.mem64
+        } else {
+            &mut self.mem32
+        };
+        let paddr = match window.as_mut().and_then(|w| w.alloc(size)) {
+            Some(paddr) => {
+                let paddr = paddr as u64;
+                self.write(addr, offset, paddr as u32);
+                if is_64bit {
+                    self.write(addr, offset + 4, (paddr >> 32) as u32);
+                }
+                paddr
+            }
+            None => orig,
+        };
+        let bar = usize::try_from(paddr)
+            .ok()
+            .filter(|&paddr| paddr != 0)
+            .map(|paddr| Bar {
+                kind: if is_64bit {
+                    BarKind::Mem64
+                } else {
+                    BarKind::Mem32
+                },
+                prefetchable,
+                addr: paddr,
+                size,
+            });
+        (bar, slots)
+    }
+
+    /// Assigns the bus numbers and windows of a PCI-to-PCI bridge, and scans
+    /// the buses behind it.
+    fn setup_bridge(&mut self, addr: PciAddress, slot_sum: usize) {
+        let slot_sum = slot_sum + addr.device as usize;
+        if !self.assign {
+            let secondary = (self.read(addr, REG_BUS_NUMBERS) >> 8) as u8;
+            if secondary > addr.bus && secondary <= self.bridge.bus_end {
+                self.scan_bus(secondary, slot_sum);
+            }
+            return;
+        }
+        if self.last_bus >= self.bridge.bus_end {
+            return;
+        }
+        self.last_bus += 1;
+        let secondary = self.last_bus;
+        let bus_numbers = |subordinate: u8| {
+            addr.bus as u32 | (secondary as u32) << 8 | (subordinate as u32) << 16
+        };
+        self.write(addr, REG_BUS_NUMBERS, bus_numbers(self.bridge.bus_end));
+
+        let starts = self.align_windows();
+        self.scan_bus(secondary, slot_sum);
+        self.write(addr, REG_BUS_NUMBERS, bus_numbers(self.last_bus));
+        let ends = self.align_windows();
+
+        let [io, mem, pref] =
+            [0, 1, 2].map(|i| (starts[i] < ends[i]).then(|| (starts[i], ends[i] - 1)));
+        match io {
+            Some((base, limit)) => {
+                let (base, limit) = (base as u32, limit as u32);
+                let window = ((base >> 8) & 0xf0) | (((limit >> 8) & 0xf0) << 8);
+                self.write(addr, REG_IO_WINDOW, window);
+                self.write(
+                    addr,
+                    REG_IO_WINDOW_UPPER,
+                    (base >> 16) | (limit >> 16) << 16,
+                );
+            }
+            None => {
+                self.write(addr, REG_IO_WINDOW, 0xf0);
+                self.write(addr, REG_IO_WINDOW_UPPER, 0);
+            }
+        }
+        self.write(addr, REG_MEM_WINDOW, mem_window(mem));
+        self.write(addr, REG_PREF_WINDOW, mem_window(pref));
+        let (base, limit) = pref.map_or((0, 0), |(base, limit)| (base as u64, limit as u64));
+        self.write(addr, REG_PREF_BASE_UPPER, (base >> 32) as u32);
+        self.write(addr, REG_PREF_LIMIT_UPPER, (limit >> 32) as u32);
+    }
+
+    /// Aligns the allocators to the granularity of bridge windows, and returns
+    /// their next addresses (I/O, 32-bit memory and 64-bit memory).
+    fn align_windows(&mut self) -> [usize; 3] {
+        let mut next = [0; 3];
+        for (i, (allocator, align)) in [
+            (&mut self.io, BRIDGE_IO_ALIGN),
+            (&mut self.mem32, BRIDGE_MEM_ALIGN),
+            (&mut self.mem64, BRIDGE_MEM_ALIGN),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if let Some(allocator) = allocator {
+                allocator.align(align);
+                next[i] = allocator.next;
+            }
+        }
+        next
+    }
+
+    fn intx(&self, addr: PciAddress, slot_sum: usize) -> Option<Intx> {
+        let interrupt = self.read(addr, REG_INTERRUPT);
+        let pin = IntxPin::from_register((interrupt >> 8) as u8)?;
+        let irq = match self.bridge.intx {
+            IntxRouting::Swizzle { base } => {
+                Some(base + (pin as usize + addr.device as usize + slot_sum) % 4)
+            }
+            IntxRouting::InterruptLine { offset } => {
+                let line = interrupt as u8;
+                (line != 0xff).then_some(offset + line as usize)
+            }
+            IntxRouting::None => None,
+        };
+        Some(Intx { pin, irq })
+    }
+}
+
+/// Encodes the memory window (or prefetchable memory window) of a bridge,
+/// which is disabled if `None`.
+fn mem_window(window: Option<(usize, usize)>) -> u32 {
+    match window {
+        Some((base, limit)) => {
+            (((base >> 16) as u32) & 0xfff0) | ((((limit >> 16) as u32) & 0xfff0) << 16)
+        }
+        None => 0xfff0,
+    }
+}
+
+struct Pci {
+    bridge: HostBridge,
+    devices: PciDeviceList,
+}
+
+static PCI: LazyInit<Pci> = LazyInit::new();
+
+/// Enumerates and configures the functions behind the host bridge by
+/// [`HostBridge::enumerate`], and records them for [`devices`].
+///
+/// It should be called only once on the primary CPU, after the configuration
+/// space and the windows are mapped.
+pub fn init(bridge: HostBridge) -> &'static [PciDevice] {
+    let devices = bridge.enumerate();
+    &PCI.init_once(Pci { bridge, devices }).devices
+}
+
+/// Returns the host bridge recorded by [`init`], to access the configuration
+/// space.
+pub fn host_bridge() -> Option<&'static HostBridge> {
+    PCI.get().map(|pci| &pci.bridge)
+}
+
+/// Returns the PCI functions found by [`init`], or an empty list if it is not
+/// called.
+pub fn devices() -> &'static [PciDevice] {
+    PCI.get().map_or(&[], |pci| &pci.devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A function whose BARs decode the bits in `bar_masks`.
+    #[derive(Clone, Copy)]
+    struct Function {
+        regs: [u32; 64],
+        bar_masks: [u32; 6],
+    }
+
+    impl Function {
+        fn new(id: u32, class: u32, header_type: u32, pin: u32) -> Self {
+            let mut regs = [0; 64];
+            regs[0] = id;
+            regs[2] = class << 8;
+            regs[3] = header_type << 16;
+            regs[15] = (pin << 8) | 0xff;
+            Self {
+                regs,
+                bar_masks: [0; 6],
+            }
+        }
+
+        fn bar(mut self, index: usize, flags: u32, mask: u32) -> Self {
+            self.regs[4 + index] = flags;
+            self.bar_masks[index] = mask;
+            self
+        }
+    }
+
+    struct MockConfig(Mutex<BTreeMap<PciAddress, Function>>);
+
+    impl ConfigAccess for MockConfig {
+        fn read(&self, addr: PciAddress, offset: u16) -> u32 {
+            let functions = self.0.lock().unwrap();
+            functions
+                .get(&addr)
+                .map_or(u32::MAX, |f| f.regs[offset as usize / 4])
+        }
+
+        fn write(&self, addr: PciAddress, offset: u16, value: u32) {
+            let mut functions = self.0.lock().unwrap();
+            let Some(f) = functions.get_mut(&addr) else {
+                return;
+            };
+            let reg = offset as usize / 4;
+            let bar_num = if f.regs[3] >> 16 & 0x7f == 0 { 6 } else { 2 };
+            if (4..4 + bar_num).contains(&reg) {
+                let mask = f.bar_masks[reg - 4];
+                f.regs[reg] = (value & mask) | (f.regs[reg] & !mask);
+            } else {
+                f.regs[reg] = value;
+            }
+        }
+    }
+
+    fn mock_config(functions: &[(PciAddress, Function)]) -> &'static MockConfig {
+        Box::leak(Box::new(MockConfig(Mutex::new(
+            functions.iter().copied().collect(),
+        ))))
+    }
+
+    #[test]
+    fn windows_from_ranges() {
+        let windows = PciWindows::from_ranges(&[(0, 0), (0x4000_0000, 0x2_0000)]);
+        assert_eq!(
+            windows,
+            PciWindows {
+                pio: None,
+                mmio32: Some((0x4000_0000, 0x2_0000)),
+                mmio64: None,
+            }
+        );
+        assert!(PciWindows::from_ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn enumerate() {
+        let bridge_addr = PciAddress::new(0, 2, 0);
+        let config = mock_config(&[
+            (
+                PciAddress::new(0, 0, 0),
+                Function::new(0x0008_1b36, 0x06_00_00, 0, 0),
+            ),
+            (
+                PciAddress::new(0, 1, 0),
+                Function::new(0x1000_1af4, 0x02_00_00, 0, 1)
+                    .bar(0, 0x1, 0xffff_ffe0)
+                    .bar(1, 0x0, 0xffff_f000)
+                    .bar(4, 0xc, 0xffff_c000)
+                    .bar(5, 0x0, 0xffff_ffff),
+            ),
+            (bridge_addr, Function::new(0x000c_1b36, 0x06_04_00, 1, 0)),
+            (
+                PciAddress::new(1, 0, 0),
+                Function::new(0x1001_1af4, 0x01_00_00, 0, 2).bar(0, 0x0, 0xfff0_0000),
+            ),
+            (
+                PciAddress::new(0, 3, 0),
+                Function::new(0x0001_8086, 0x0c_03_00, 0x80, 0),
+            ),
+            (
+                PciAddress::new(0, 3, 2),
+                Function::new(0x0002_8086, 0x0c_03_00, 0, 0),
+            ),
+        ]);
+        let bridge = HostBridge::new(
+            config,
+            0xff,
+            PciWindows::from_ranges(&[
+                (0x0300_0000, 0x1_0000),
+                (0x4000_0000, 0x4000_0000),
+                (0x4_0000_0000, 0x4_0000_0000),
+            ]),
+            IntxRouting::Swizzle { base: 0x20 },
+        );
+        let devices = bridge.enumerate();
+        assert_eq!(
+            devices
+                .iter()
+                .map(|d| d.addr.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "00:00.0", "00:01.0", "00:02.0", "01:00.0", "00:03.0", "00:03.2"
+            ]
+        );
+
+        let net = &devices[1];
+        assert_eq!(
+            (net.vendor_id, net.device_id, net.class),
+            (0x1af4, 0x1000, 2)
+        );
+        let bar = |kind, prefetchable, addr, size| {
+            Some(Bar {
+                kind,
+                prefetchable,
+                addr,
+                size,
+            })
+        };
+        assert_eq!(
+            net.bars,
+            [
+                bar(BarKind::Io, false, 0x0300_1000, 0x20),
+                bar(BarKind::Mem32, false, 0x4000_0000, 0x1000),
+                None,
+                None,
+                bar(BarKind::Mem64, true, 0x4_0000_0000, 0x4000),
+                None,
+            ]
+        );
+        assert_eq!(
+            net.intx,
+            Some(Intx {
+                pin: IntxPin::A,
+                irq: Some(0x21),
+            })
+        );
+        assert_eq!(bridge.read_config(net.addr, REG_BAR0), 0x1001);
+        assert_eq!(bridge.read_config(net.addr, REG_COMMAND), 0x7);
+
+        // The device behind the bridge is in the bridge's windows, and its pin
+        // is swizzled twice.
+        let blk = &devices[3];
+        assert_eq!(
+            blk.bars[0],
+            bar(BarKind::Mem32, false, 0x4010_0000, 0x10_0000)
+        );
+        assert_eq!(blk.intx.unwrap().irq, Some(0x23));
+        assert_eq!(bridge.read_config(bridge_addr, REG_BUS_NUMBERS), 0x01_01_00);
+        assert_eq!(bridge.read_config(bridge_addr, REG_IO_WINDOW), 0xf0);
+        assert_eq!(bridge.read_config(bridge_addr, REG_MEM_WINDOW), 0x4010_4010);
+        assert_eq!(bridge.read_config(bridge_addr, REG_PREF_WINDOW), 0xfff0);
+        assert_eq!(devices[0].intx, None);
+    }
+
+    #[test]
+    fn bridge_with_64bit_bar() {
+        let bridge_addr = PciAddress::new(0, 1, 0);
+        let gpu_addr = PciAddress::new(1, 0, 0);
+        let config = mock_config(&[
+            (
+                PciAddress::new(0, 0, 0),
+                Function::new(0x0008_1b36, 0x06_00_00, 0, 0),
+            ),
+            (bridge_addr, Function::new(0x000c_1b36, 0x06_04_00, 1, 0)),
+            (
+                gpu_addr,
+                Function::new(0x1050_1af4, 0x03_00_00, 0, 1)
+                    .bar(0, 0xc, 0xfff0_0000)
+                    .bar(1, 0x0, 0xffff_ffff),
+            ),
+        ]);
+        let bridge = HostBridge::new(
+            config,
+            0xff,
+            PciWindows::from_ranges(&[
+                (0, 0),
+                (0x4000_0000, 0x4000_0000),
+                (0x4_0000_0000, 0x4_0000_0000),
+            ]),
+            IntxRouting::Swizzle { base: 16 },
+        );
+        let devices = bridge.enumerate();
+        assert_eq!(devices.len(), 3);
+
+        // The 64-bit prefetchable BAR is placed above 4G, in the prefetchable
+        // window of the bridge.
+        let gpu = &devices[2];
+        assert_eq!(gpu.addr, gpu_addr);
+        assert_eq!(
+            gpu.bars[..2],
+            [
+                Some(Bar {
+                    kind: BarKind::Mem64,
+                    prefetchable: true,
+                    addr: 0x4_0000_0000,
+                    size: 0x10_0000,
+                }),
+                None,
+            ]
+        );
+        assert_eq!(bridge.read_config(gpu_addr, REG_BAR0), 0xc);
+        assert_eq!(bridge.read_config(gpu_addr, REG_BAR0 + 4), 0x4);
+        assert_eq!(bridge.read_config(gpu_addr, REG_COMMAND), 0x6);
+        assert_eq!(gpu.intx.unwrap().irq, Some(17));
+
+        assert_eq!(bridge.read_config(bridge_addr, REG_BUS_NUMBERS), 0x01_01_00);
+        assert_eq!(bridge.read_config(bridge_addr, REG_IO_WINDOW), 0xf0);
+        assert_eq!(bridge.read_config(bridge_addr, REG_MEM_WINDOW), 0xfff0);
+        assert_eq!(bridge.read_config(bridge_addr, REG_PREF_WINDOW), 0);
+        assert_eq!(bridge.read_config(bridge_addr, REG_PREF_BASE_UPPER), 0x4);
+        assert_eq!(bridge.read_config(bridge_addr, REG_PREF_LIMIT_UPPER), 0x4);
+    }
+
+    #[test]
+    fn keep_firmware_config() {
+        let mut net = Function::new(0x1000_1af4, 0x02_00_00, 0, 1)
+            .bar(0, 0xc001, 0xffff_ffe0)
+            .bar(1, 0xfebf_0000, 0xffff_f000);
+        net.regs[15] = 0x10b; // INTA, line 11
+        let config = mock_config(&[(PciAddress::new(0, 1, 0), net)]);
+
+        let bridge = HostBridge::new(
+            config,
+            0xff,
+            PciWindows::default(),
+            IntxRouting::InterruptLine { offset: 0x20 },
+        );
+        let devices = bridge.enumerate();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(
+            devices[0].bars[0].map(|b| (b.addr, b.size)),
+            Some((0xc000, 0x20))
+        );
+        assert_eq!(
+            devices[0].bars[1].map(|b| (b.addr, b.size)),
+            Some((0xfebf_0000, 0x1000))
+        );
+        assert_eq!(devices[0].intx.unwrap().irq, Some(0x2b));
+    }
+}
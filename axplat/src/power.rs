@@ -1,5 +1,74 @@
 //! Power management.
 
+pub mod topology;
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// The kind of a system reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetKind {
+    /// Resets the whole system, as if it is powered off and on again.
+    Cold,
+    /// Resets the CPUs but keeps the contents of the memory (if supported by
+    /// the platform).
+    Warm,
+}
+
+/// The reason for the last system reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResetReason {
+    /// The system is powered on.
+    PowerOn,
+    /// The system is reset by software, e.g., by [`PowerIf::system_reset`].
+    Software(ResetKind),
+    /// The system is reset by a watchdog timeout.
+    Watchdog,
+}
+
+/// The power state of a CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CpuState {
+    /// The CPU is powered off, or has not been started yet.
+    Off = 0,
+    /// The CPU is being started, but has not entered the kernel yet.
+    OnPending = 1,
+    /// The CPU is running.
+    On = 2,
+}
+
+/// The error type of [`PowerIf::cpu_boot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuBootError {
+    /// The CPU does not exist.
+    NotPresent,
+    /// The CPU is already on, or being started.
+    AlreadyOn,
+    /// The firmware denied to start the CPU, or does not support it.
+    Denied,
+    /// The CPU did not enter the kernel in time.
+    TimedOut,
+}
+
+/// A [`Result`] type with [`CpuBootError`] as the error type.
+pub type CpuBootResult = Result<(), CpuBootError>;
+
+/// An idle state of CPUs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleState {
+    /// The name of the state.
+    pub name: &'static str,
+    /// The worst-case latency (in nanoseconds) to enter the state.
+    pub entry_latency_ns: u64,
+    /// The worst-case latency (in nanoseconds) to exit the state after the CPU
+    /// is woken up.
+    pub exit_latency_ns: u64,
+    /// The minimum time (in nanoseconds) to stay in the state to save power,
+    /// including the entry and exit latencies.
+    pub min_residency_ns: u64,
+}
+
 /// Power management interface.
 #[def_interface(gen_caller)]
 pub trait PowerIf {
@@ -8,12 +77,54 @@ pub trait PowerIf {
     ///
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
+    ///
+    /// It returns once the CPU is requested to start, which may not have
+    /// entered the kernel yet. Use [`cpu_boot_and_wait`] to wait for it.
+    #[cfg(feature = "smp")]
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> CpuBootResult;
+
+    /// Powers off the calling CPU.
+    ///
+    /// It should be called on a secondary CPU with interrupts disabled. The CPU
+    /// can be brought back by [`cpu_boot`](PowerIf::cpu_boot), and then enters
+    /// the function decorated by [`axplat::secondary_main`](crate::secondary_main)
+    /// again.
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> !;
+
+    /// Returns the power state of the given CPU, or `None` if it is unknown.
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize);
+    fn cpu_state(cpu_id: usize) -> Option<CpuState>;
+
+    /// Returns the idle states that CPUs can enter, ordered from the shallowest
+    /// to the deepest.
+    ///
+    /// There is at least one state, which is usually the one entered by
+    /// `axcpu::asm::halt`.
+    fn idle_states() -> &'static [IdleState];
+
+    /// Enters the given idle state on the current CPU, where `index` is the
+    /// index in [`idle_states`](PowerIf::idle_states).
+    ///
+    /// It should be called with interrupts disabled, and returns with
+    /// interrupts disabled after the CPU is woken up by an interrupt. The
+    /// interrupt is usually handled after interrupts are enabled again, but
+    /// some platforms may handle it before returning. If `index` is out of
+    /// range, the shallowest state is entered.
+    fn cpu_idle(index: usize);
 
     /// Shutdown the whole system.
     fn system_off() -> !;
 
+    /// Resets the whole system.
+    ///
+    /// If the platform can not do a warm reset, it falls back to a cold reset.
+    fn system_reset(kind: ResetKind) -> !;
+
+    /// Returns the reason for the last reset, or `None` if the platform does
+    /// not know it.
+    fn reset_reason() -> Option<ResetReason>;
+
     /// Get the number of CPU cores available on this platform.
     ///
     /// The platform should either get this value statically from its
@@ -23,3 +134,205 @@ pub trait PowerIf {
     /// the same as `MAX_CPU_NUM` defined in the platform configuration.
     fn cpu_num() -> usize;
 }
+
+/// The maximum number of CPUs that can be waited by [`cpu_boot_and_wait`].
+#[cfg(feature = "smp")]
+pub const MAX_HANDSHAKE_CPUS: usize = 64;
+
+/// The number of times each CPU has entered the secondary main function.
+#[cfg(feature = "smp")]
+static SECONDARY_ENTERED: [AtomicUsize; MAX_HANDSHAKE_CPUS] =
+    [const { AtomicUsize::new(0) }; MAX_HANDSHAKE_CPUS];
+
+/// Records that the given CPU has entered the secondary main function.
+#[cfg(feature = "smp")]
+pub(crate) fn secondary_entered(cpu_id: usize) {
+    if let Some(count) = SECONDARY_ENTERED.get(cpu_id) {
+        count.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Bootstraps the given CPU core like [`cpu_boot`], and waits until it enters
+/// the function decorated by [`axplat::secondary_main`](crate::secondary_main).
+///
+/// Returns [`CpuBootError::TimedOut`] if it does not enter in `timeout`. CPUs
+/// with IDs not less than [`MAX_HANDSHAKE_CPUS`] are not waited for.
+#[cfg(feature = "smp")]
+pub fn cpu_boot_and_wait(
+    cpu_id: usize,
+    stack_top_paddr: usize,
+    timeout: crate::time::Duration,
+) -> CpuBootResult {
+    let Some(count) = SECONDARY_ENTERED.get(cpu_id) else {
+        return cpu_boot(cpu_id, stack_top_paddr);
+    };
+    let old = count.load(Ordering::Acquire);
+    cpu_boot(cpu_id, stack_top_paddr)?;
+    let deadline = crate::time::monotonic_time() + timeout;
+    while count.load(Ordering::Acquire) == old {
+        if crate::time::monotonic_time() >= deadline {
+            return Err(CpuBootError::TimedOut);
+        }
+        core::hint::spin_loop();
+    }
+    Ok(())
+}
+
+/// The address of the function that returns the current CPU ID, or `0` if
+/// not set.
+static THIS_CPU_ID_FN: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the function that returns the ID of the current CPU.
+///
+/// The platform does not know which CPU it runs on, as the per-CPU states are
+/// kept by the kernel. The generic services of `axplat` (e.g., IRQ statistics)
+/// use this function to find the current CPU. It must be callable in the
+/// interrupt context.
+pub fn set_this_cpu_id_fn(f: fn() -> usize) {
+    THIS_CPU_ID_FN.store(f as usize, Ordering::Release);
+}
+
+/// Returns the ID of the current CPU by the function set with
+/// [`set_this_cpu_id_fn`], or `0` if it is not set.
+pub fn this_cpu_id() -> usize {
+    try_this_cpu_id().unwrap_or(0)
+}
+
+/// Returns the ID of the current CPU, or `None` if it is unknown as
+/// [`set_this_cpu_id_fn`] has not been called.
+pub(crate) fn try_this_cpu_id() -> Option<usize> {
+    let f = THIS_CPU_ID_FN.load(Ordering::Acquire);
+    if f == 0 {
+        return None;
+    }
+    // SAFETY: `THIS_CPU_ID_FN` is only set from a `fn() -> usize`.
+    let f = unsafe { core::mem::transmute::<usize, fn() -> usize>(f) };
+    Some(f())
+}
+
+/// Selects the deepest idle state to enter, if the current CPU is going to be
+/// idle for `idle_ns` nanoseconds (e.g., until the next timer deadline).
+///
+/// Only states that save power in `idle_ns`, and whose exit latencies are
+/// within `max_latency_ns`, are chosen. Returns the index in [`idle_states`]
+/// for [`cpu_idle`], which is `0` if no deeper state fits.
+pub fn select_idle_state(idle_ns: u64, max_latency_ns: u64) -> usize {
+    select_from(idle_states(), idle_ns, max_latency_ns)
+}
+
+fn select_from(states: &[IdleState], idle_ns: u64, max_latency_ns: u64) -> usize {
+    states
+        .iter()
+        .rposition(|s| s.min_residency_ns <= idle_ns && s.exit_latency_ns <= max_latency_ns)
+        .unwrap_or(0)
+}
+
+/// A table of CPU power states, for platforms that track them by software.
+///
+/// All CPUs are [`CpuState::Off`] initially.
+pub struct CpuStateTable<const N: usize> {
+    states: [AtomicU8; N],
+}
+
+impl<const N: usize> CpuStateTable<N> {
+    /// Creates a new table with all CPUs off.
+    pub const fn new() -> Self {
+        Self {
+            states: [const { AtomicU8::new(CpuState::Off as u8) }; N],
+        }
+    }
+
+    /// Returns the state of the given CPU, or `None` if it is out of range.
+    pub fn get(&self, cpu_id: usize) -> Option<CpuState> {
+        let state = self.states.get(cpu_id)?.load(Ordering::Acquire);
+        Some(match state {
+            0 => CpuState::Off,
+            1 => CpuState::OnPending,
+            _ => CpuState::On,
+        })
+    }
+
+    /// Sets the state of the given CPU.
+    pub fn set(&self, cpu_id: usize, state: CpuState) {
+        if let Some(s) = self.states.get(cpu_id) {
+            s.store(state as u8, Ordering::Release);
+        }
+    }
+
+    /// Changes the state of the given CPU from [`CpuState::Off`] to
+    /// [`CpuState::OnPending`], before starting it.
+    ///
+    /// Returns [`CpuBootError::NotPresent`] if the CPU is out of range, or
+    /// [`CpuBootError::AlreadyOn`] if it is not off.
+    pub fn start_boot(&self, cpu_id: usize) -> CpuBootResult {
+        if cpu_id >= N {
+            Err(CpuBootError::NotPresent)
+        } else if self.transition(cpu_id, CpuState::Off, CpuState::OnPending) {
+            Ok(())
+        } else {
+            Err(CpuBootError::AlreadyOn)
+        }
+    }
+
+    /// Changes the state of the given CPU from `current` to `new`.
+    ///
+    /// Returns `false` if the CPU is not in the `current` state, or is out of
+    /// range.
+    pub fn transition(&self, cpu_id: usize, current: CpuState, new: CpuState) -> bool {
+        self.states.get(cpu_id).is_some_and(|s| {
+            s.compare_exchange(
+                current as u8,
+                new as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+        })
+    }
+}
+
+impl<const N: usize> Default for CpuStateTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CpuBootError, CpuState, CpuStateTable, IdleState, select_from};
+
+    #[test]
+    fn cpu_state_table() {
+        let table = CpuStateTable::<2>::new();
+        assert_eq!(table.get(1), Some(CpuState::Off));
+        assert_eq!(table.get(2), None);
+        assert!(table.transition(1, CpuState::Off, CpuState::OnPending));
+        assert!(!table.transition(1, CpuState::Off, CpuState::OnPending));
+        table.set(1, CpuState::On);
+        assert_eq!(table.get(1), Some(CpuState::On));
+        assert!(!table.transition(2, CpuState::Off, CpuState::On));
+        assert_eq!(table.start_boot(0), Ok(()));
+        assert_eq!(table.start_boot(0), Err(CpuBootError::AlreadyOn));
+        assert_eq!(table.start_boot(2), Err(CpuBootError::NotPresent));
+        table.set(2, CpuState::On);
+    }
+
+    #[test]
+    fn select_idle_state() {
+        const fn state(exit_latency_ns: u64, min_residency_ns: u64) -> IdleState {
+            IdleState {
+                name: "",
+                entry_latency_ns: 0,
+                exit_latency_ns,
+                min_residency_ns,
+            }
+        }
+        let states = [state(1, 1), state(10, 100), state(100, 1000)];
+        assert_eq!(select_from(&states, 0, u64::MAX), 0);
+        assert_eq!(select_from(&states, 999, u64::MAX), 1);
+        assert_eq!(select_from(&states, 1000, u64::MAX), 2);
+        assert_eq!(select_from(&states, u64::MAX, 50), 1);
+        assert_eq!(select_from(&states, u64::MAX, 0), 0);
+        assert_eq!(select_from(&[], 1000, 1000), 0);
+    }
+}
@@ -0,0 +1,270 @@
+//! CPU topology and the mapping between logical and hardware CPU IDs.
+//!
+//! The kernel identifies CPUs by logical IDs (0, 1, ..., N-1), where the boot
+//! CPU is always 0. The hardware identifies them by MPIDRs on ARM, hart IDs
+//! on RISC-V or APIC IDs on x86, which may be sparse, or start from a non-zero
+//! value.
+//!
+//! Platforms register all CPUs by [`register`] at the early stage, from the
+//! device tree, the ACPI MADT or the static configuration. Then they translate
+//! the IDs by [`hw_id`] and [`cpu_id`] when booting CPUs or sending IPIs. The
+//! boot CPU is registered first by [`call_main_hw`](crate::call_main_hw), so
+//! that it gets the logical ID 0.
+//!
+//! If no CPU is registered, logical IDs are the same as hardware IDs.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// The maximum number of CPUs that can be registered.
+pub const MAX_TOPOLOGY_CPUS: usize = 64;
+
+const HW_ID_NONE: usize = usize::MAX;
+
+/// The hardware ID and the location in the topology of a CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTopology {
+    /// The hardware ID, i.e., the affinity fields of MPIDR on ARM, the hart ID
+    /// on RISC-V, or the APIC ID on x86.
+    pub hw_id: usize,
+    /// The index of the physical package (socket).
+    pub package: u16,
+    /// The index of the cluster in the package.
+    pub cluster: u16,
+    /// The index of the core in the cluster.
+    pub core: u16,
+    /// The index of the hardware thread in the core.
+    pub thread: u16,
+}
+
+impl CpuTopology {
+    /// A CPU without known topology, which is taken as the only thread of
+    /// core `hw_id` in package 0.
+    pub const fn flat(hw_id: usize) -> Self {
+        Self {
+            hw_id,
+            package: 0,
+            cluster: 0,
+            core: hw_id as u16,
+            thread: 0,
+        }
+    }
+
+    /// Decodes the topology from the affinity fields of an ARM MPIDR.
+    ///
+    /// If the MT bit is set, Aff0, Aff1 and Aff2 are the thread, core and
+    /// cluster. Otherwise, they are the core, cluster and package. The
+    /// hardware ID is the affinity fields Aff0 to Aff2.
+    pub const fn from_mpidr(mpidr: usize) -> Self {
+        const MPIDR_MT: usize = 1 << 24;
+        let aff0 = (mpidr & 0xff) as u16;
+        let aff1 = ((mpidr >> 8) & 0xff) as u16;
+        let aff2 = ((mpidr >> 16) & 0xff) as u16;
+        let hw_id = mpidr & 0xff_ffff;
+        if mpidr & MPIDR_MT != 0 {
+            Self {
+                hw_id,
+                package: 0,
+                cluster: aff2,
+                core: aff1,
+                thread: aff0,
+            }
+        } else {
+            Self {
+                hw_id,
+                package: aff2,
+                cluster: aff1,
+                core: aff0,
+                thread: 0,
+            }
+        }
+    }
+
+    const fn location(&self) -> u64 {
+        ((self.package as u64) << 48)
+            | ((self.cluster as u64) << 32)
+            | ((self.core as u64) << 16)
+            | self.thread as u64
+    }
+
+    const fn with_location(hw_id: usize, location: u64) -> Self {
+        Self {
+            hw_id,
+            package: (location >> 48) as u16,
+            cluster: (location >> 32) as u16,
+            core: (location >> 16) as u16,
+            thread: location as u16,
+        }
+    }
+}
+
+/// A table of CPUs indexed by logical IDs.
+///
+/// It is written only at the early stage, but can be read lock-free in any
+/// context (e.g., when sending IPIs).
+struct TopologyTable<const N: usize> {
+    hw_ids: [AtomicUsize; N],
+    locations: [AtomicU64; N],
+    len: AtomicUsize,
+    write_lock: SpinNoIrq<()>,
+}
+
+impl<const N: usize> TopologyTable<N> {
+    const fn new() -> Self {
+        Self {
+            hw_ids: [const { AtomicUsize::new(HW_ID_NONE) }; N],
+            locations: [const { AtomicU64::new(0) }; N],
+            len: AtomicUsize::new(0),
+            write_lock: SpinNoIrq::new(()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    fn register(&self, cpu: CpuTopology) -> Option<usize> {
+        let _guard = self.write_lock.lock();
+        let cpu_id = match self.cpu_id(cpu.hw_id) {
+            Some(cpu_id) if self.len() > 0 => cpu_id,
+            _ => {
+                let cpu_id = self.len();
+                if cpu_id == N || cpu.hw_id == HW_ID_NONE {
+                    return None;
+                }
+                self.hw_ids[cpu_id].store(cpu.hw_id, Ordering::Relaxed);
+                cpu_id
+            }
+        };
+        self.locations[cpu_id].store(cpu.location(), Ordering::Relaxed);
+        let len = self.len().max(cpu_id + 1);
+        self.len.store(len, Ordering::Release);
+        Some(cpu_id)
+    }
+
+    fn get(&self, cpu_id: usize) -> Option<CpuTopology> {
+        if cpu_id >= self.len() {
+            return None;
+        }
+        let location = self.locations[cpu_id].load(Ordering::Relaxed);
+        Some(CpuTopology::with_location(
+            self.hw_ids[cpu_id].load(Ordering::Relaxed),
+            location,
+        ))
+    }
+
+    fn hw_id(&self, cpu_id: usize) -> Option<usize> {
+        match self.len() {
+            0 => Some(cpu_id),
+            len if cpu_id < len => Some(self.hw_ids[cpu_id].load(Ordering::Relaxed)),
+            _ => None,
+        }
+    }
+
+    fn cpu_id(&self, hw_id: usize) -> Option<usize> {
+        match self.len() {
+            0 => Some(hw_id),
+            len => self.hw_ids[..len]
+                .iter()
+                .position(|id| id.load(Ordering::Relaxed) == hw_id),
+        }
+    }
+}
+
+static TOPOLOGY: TopologyTable<MAX_TOPOLOGY_CPUS> = TopologyTable::new();
+
+/// Registers a CPU, and returns its logical ID.
+///
+/// CPUs are given logical IDs in the order they are registered. If a CPU with
+/// the same hardware ID is already registered, only its location is updated.
+/// Returns `None` if there are already [`MAX_TOPOLOGY_CPUS`] CPUs.
+///
+/// It should be called only on the primary CPU before starting any secondary
+/// CPUs.
+pub fn register(cpu: CpuTopology) -> Option<usize> {
+    TOPOLOGY.register(cpu)
+}
+
+/// Registers all the given CPUs like [`register`], and returns the number of
+/// registered CPUs.
+pub fn register_all(cpus: impl IntoIterator<Item = CpuTopology>) -> usize {
+    for cpu in cpus {
+        if register(cpu).is_none() {
+            break;
+        }
+    }
+    cpu_num()
+}
+
+/// Returns the number of registered CPUs.
+pub fn cpu_num() -> usize {
+    TOPOLOGY.len()
+}
+
+/// Returns the topology of the CPU with the given logical ID, or `None` if it
+/// is not registered.
+pub fn get(cpu_id: usize) -> Option<CpuTopology> {
+    TOPOLOGY.get(cpu_id)
+}
+
+/// Returns the hardware ID of the CPU with the given logical ID.
+///
+/// Returns `cpu_id` itself if no CPU is registered, or `None` if other CPUs
+/// are registered but this one is not.
+pub fn hw_id(cpu_id: usize) -> Option<usize> {
+    TOPOLOGY.hw_id(cpu_id)
+}
+
+/// Returns the logical ID of the CPU with the given hardware ID.
+///
+/// Returns `hw_id` itself if no CPU is registered, or `None` if other CPUs
+/// are registered but this one is not.
+pub fn cpu_id(hw_id: usize) -> Option<usize> {
+    TOPOLOGY.cpu_id(hw_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CpuTopology, TopologyTable};
+
+    #[test]
+    fn register_and_lookup() {
+        let table = TopologyTable::<3>::new();
+        assert_eq!((table.hw_id(5), table.cpu_id(5)), (Some(5), Some(5)));
+
+        // The boot hart is 2, the others are sparse.
+        assert_eq!(table.register(CpuTopology::flat(2)), Some(0));
+        for (hw_id, cpu_id) in [(0, 1), (2, 0), (8, 2)] {
+            let cpu = CpuTopology {
+                cluster: (hw_id / 4) as u16,
+                ..CpuTopology::flat(hw_id)
+            };
+            assert_eq!(table.register(cpu), Some(cpu_id));
+        }
+        assert_eq!(table.register(CpuTopology::flat(9)), None);
+        assert_eq!(table.len(), 3);
+
+        assert_eq!(table.hw_id(0), Some(2));
+        assert_eq!(table.hw_id(2), Some(8));
+        assert_eq!(table.hw_id(3), None);
+        assert_eq!(table.cpu_id(8), Some(2));
+        assert_eq!(table.cpu_id(1), None);
+        assert_eq!(table.get(2).map(|c| (c.cluster, c.core)), Some((2, 8)));
+        assert_eq!(table.get(3), None);
+    }
+
+    #[test]
+    fn from_mpidr() {
+        let cpu = CpuTopology::from_mpidr(0x8000_0102);
+        assert_eq!(
+            (cpu.hw_id, cpu.package, cpu.cluster, cpu.core, cpu.thread),
+            (0x102, 0, 1, 2, 0)
+        );
+        let cpu = CpuTopology::from_mpidr(0x8103_0201);
+        assert_eq!(
+            (cpu.hw_id, cpu.package, cpu.cluster, cpu.core, cpu.thread),
+            (0x03_0201, 0, 3, 2, 1)
+        );
+    }
+}
@@ -1,5 +1,8 @@
 //! Time-related operations.
 
+#[cfg(feature = "timer")]
+pub mod timer;
+
 pub use core::time::Duration;
 
 /// A measurement of the system clock.
@@ -35,12 +38,44 @@ pub trait TimeIf {
     /// clock start).
     fn epochoffset_nanos() -> u64;
 
+    /// Sets the wall time (also known as realtime) in nanoseconds since
+    /// epoch.
+    ///
+    /// The platform should update the epoch offset atomically, so that
+    /// [`wall_time_nanos`] returns the new time immediately. If the platform
+    /// has a writable RTC, the new time should also be written back to it, so
+    /// that it survives a reboot.
+    fn set_wall_time_nanos(nanos: u64);
+
     /// Set a one-shot timer.
     ///
     /// A timer interrupt will be triggered at the specified monotonic time
     /// deadline (in nanoseconds).
     #[cfg(feature = "irq")]
     fn set_oneshot_timer(deadline_ns: u64);
+
+    /// Returns the IRQ number of the RTC alarm, or `None` if the platform has
+    /// no RTC alarm.
+    #[cfg(feature = "irq")]
+    fn rtc_alarm_irq() -> Option<usize>;
+
+    /// Sets the RTC alarm at the specified wall time (in nanoseconds since
+    /// epoch).
+    ///
+    /// When the alarm fires, the IRQ returned by [`rtc_alarm_irq`] is raised,
+    /// which can also wake up a CPU from idle. The platform acknowledges the
+    /// alarm by itself, and the kernel can register its own handlers for the
+    /// IRQ. Only one alarm is pending at a time, a new one replaces the old.
+    /// The resolution is platform-specific (e.g., one second).
+    ///
+    /// It returns `false` if the RTC alarm is not supported, or the wall time
+    /// can not be represented by the RTC alarm.
+    #[cfg(feature = "irq")]
+    fn set_rtc_alarm_nanos(wall_time_nanos: u64) -> bool;
+
+    /// Cancels the pending RTC alarm, if any.
+    #[cfg(feature = "irq")]
+    fn cancel_rtc_alarm();
 }
 
 /// Returns nanoseconds elapsed since system boot.
@@ -63,6 +98,21 @@ pub fn wall_time() -> TimeValue {
     TimeValue::from_nanos(monotonic_time_nanos() + epochoffset_nanos())
 }
 
+/// Sets the wall time (also known as realtime) in [`TimeValue`].
+///
+/// See [`set_wall_time_nanos`] for details.
+pub fn set_wall_time(time: TimeValue) {
+    set_wall_time_nanos(time.as_nanos() as u64)
+}
+
+/// Sets the RTC alarm at the specified wall time in [`TimeValue`].
+///
+/// See [`set_rtc_alarm_nanos`] for details.
+#[cfg(feature = "irq")]
+pub fn set_rtc_alarm(wall_time: TimeValue) -> bool {
+    set_rtc_alarm_nanos(wall_time.as_nanos() as u64)
+}
+
 /// Busy waiting for the given duration.
 pub fn busy_wait(dur: Duration) {
     busy_wait_until(wall_time() + dur);
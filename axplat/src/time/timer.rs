@@ -0,0 +1,466 @@
+//! Software timers multiplexed over the one-shot timer.
+//!
+//! Each CPU has its own timer queue ordered by deadline, and the hardware
+//! one-shot timer ([`set_oneshot_timer`]) of the CPU is always programmed to
+//! the earliest deadline in it. To use it, the kernel should register
+//! [`handle_timer_irq`] for the timer IRQ of the platform, and must not call
+//! [`set_oneshot_timer`] by itself.
+//!
+//! Timers are added to the queue of the CPU returned by [`this_cpu_id`], and
+//! fired on that CPU.
+//!
+//! **On SMP systems, the kernel must set it up by [`set_this_cpu_id_fn`]**
+//! before adding timers. Otherwise, it is `0` on all CPUs, so all CPUs share
+//! the queue of CPU 0: a timer is fired on whichever CPU handles its timer
+//! IRQ first, and may be fired late since only one hardware timer is tracked
+//! for all of them.
+//!
+//! The timer queues are locked with IRQs masked, so timers can be added and
+//! cancelled with IRQs enabled. Hosted platforms mask them by the function
+//! set with [`set_irq_mask_fn`].
+//!
+//! [`set_oneshot_timer`]: super::set_oneshot_timer
+//! [`this_cpu_id`]: crate::power::this_cpu_id
+//! [`set_this_cpu_id_fn`]: crate::power::set_this_cpu_id_fn
+//! [`set_irq_mask_fn`]: crate::irq::set_irq_mask_fn
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use kspin::SpinNoIrq;
+
+use super::{NANOS_PER_MILLIS, monotonic_time_nanos, set_oneshot_timer};
+use crate::irq::IrqMask;
+
+/// The maximum number of CPUs that have timer queues.
+pub const MAX_TIMER_CPUS: usize = 32;
+
+/// The maximum number of pending timers on each CPU.
+pub const MAX_TIMERS_PER_CPU: usize = 32;
+
+/// The maximum interval to program the hardware one-shot timer.
+///
+/// Some platforms can only count down a 32-bit number of ticks, so the timer
+/// is re-armed at least this often while there are pending timers, even if
+/// none of them is due.
+const MAX_PROGRAM_INTERVAL_NANOS: u64 = 100 * NANOS_PER_MILLIS;
+
+/// The type of a timer callback.
+///
+/// It is called in the interrupt context with the current monotonic time (in
+/// nanoseconds) and the opaque context given when the timer is added.
+pub type TimerCallback = fn(now_ns: u64, ctx: usize);
+
+/// The identifier of a timer, used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId {
+    cpu_id: usize,
+    seq: u64,
+}
+
+impl TimerId {
+    /// The ID of the CPU on which the timer is fired.
+    pub const fn cpu_id(&self) -> usize {
+        self.cpu_id
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Timer {
+    deadline_ns: u64,
+    /// The period of the timer, or `0` for one-shot timers.
+    period_ns: u64,
+    seq: u64,
+    callback: TimerCallback,
+    ctx: usize,
+}
+
+impl Timer {
+    const EMPTY: Self = Self {
+        deadline_ns: 0,
+        period_ns: 0,
+        seq: 0,
+        callback: |_, _| {},
+        ctx: 0,
+    };
+
+    /// Timers with the same deadline are fired in the order they are added.
+    fn key(&self) -> (u64, u64) {
+        (self.deadline_ns, self.seq)
+    }
+}
+
+/// A fixed-capacity binary min-heap of timers.
+struct TimerQueue<const N: usize> {
+    heap: [Timer; N],
+    len: usize,
+}
+
+impl<const N: usize> TimerQueue<N> {
+    const fn new() -> Self {
+        Self {
+            heap: [Timer::EMPTY; N],
+            len: 0,
+        }
+    }
+
+    fn next_deadline(&self) -> Option<u64> {
+        (self.len > 0).then(|| self.heap[0].deadline_ns)
+    }
+
+    fn push(&mut self, timer: Timer) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.heap[self.len] = timer;
+        self.len += 1;
+        self.sift_up(self.len - 1);
+        true
+    }
+
+    /// Removes and returns the earliest timer if it expires at `now_ns`.
+    fn pop_expired(&mut self, now_ns: u64) -> Option<Timer> {
+        (self.next_deadline()? <= now_ns).then(|| self.remove_at(0))
+    }
+
+    fn cancel(&mut self, seq: u64) -> bool {
+        match self.heap[..self.len].iter().position(|t| t.seq == seq) {
+            Some(i) => {
+                self.remove_at(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_at(&mut self, i: usize) -> Timer {
+        let timer = self.heap[i];
+        self.len -= 1;
+        self.heap[i] = self.heap[self.len];
+        if i < self.len {
+            self.sift_down(i);
+            self.sift_up(i);
+        }
+        timer
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.heap[i].key() >= self.heap[parent].key() {
+                break;
+            }
+            self.heap.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut min = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.len && self.heap[child].key() < self.heap[min].key() {
+                    min = child;
+                }
+            }
+            if min == i {
+                break;
+            }
+            self.heap.swap(i, min);
+            i = min;
+        }
+    }
+}
+
+struct CpuTimers {
+    queue: TimerQueue<MAX_TIMERS_PER_CPU>,
+    /// The deadline that the hardware one-shot timer is programmed to.
+    programmed_ns: u64,
+}
+
+impl CpuTimers {
+    const fn new() -> Self {
+        Self {
+            queue: TimerQueue::new(),
+            programmed_ns: u64::MAX,
+        }
+    }
+
+    /// Returns the deadline to program the hardware one-shot timer to, which
+    /// is the earliest deadline capped by [`MAX_PROGRAM_INTERVAL_NANOS`].
+    ///
+    /// It returns `None` if the hardware timer needs no change, i.e., the
+    /// deadline is not before the programmed one and `force` is not set, or
+    /// there are no pending timers.
+    fn reprogram(&mut self, now_ns: u64, force: bool) -> Option<u64> {
+        let Some(next_ns) = self.queue.next_deadline() else {
+            // Nothing to wait for. A deadline programmed before still fires,
+            // and finds nothing to do.
+            self.programmed_ns = u64::MAX;
+            return None;
+        };
+        let deadline = next_ns.min(now_ns.saturating_add(MAX_PROGRAM_INTERVAL_NANOS));
+        if force || deadline < self.programmed_ns {
+            self.programmed_ns = deadline;
+            return Some(deadline);
+        }
+        None
+    }
+
+    /// Removes and returns the earliest timer if it expires at `now_ns`.
+    ///
+    /// A periodic timer is added back with its next deadline after `now_ns`,
+    /// skipping the missed periods, so that it can be cancelled in its
+    /// callback. It is not added back if the next deadline overflows, as it
+    /// would never expire.
+    fn pop_expired(&mut self, now_ns: u64) -> Option<Timer> {
+        let timer = self.queue.pop_expired(now_ns)?;
+        // It is `None` for one-shot timers, whose periods are zero.
+        let next_ns = (now_ns - timer.deadline_ns)
+            .checked_div(timer.period_ns)
+            .and_then(|missed| missed.checked_add(1)?.checked_mul(timer.period_ns))
+            .and_then(|delay| timer.deadline_ns.checked_add(delay));
+        if let Some(deadline_ns) = next_ns {
+            // There is always room as it was just removed.
+            self.queue.push(Timer {
+                deadline_ns,
+                ..timer
+            });
+        }
+        Some(timer)
+    }
+}
+
+static TIMERS: [SpinNoIrq<CpuTimers>; MAX_TIMER_CPUS] =
+    [const { SpinNoIrq::new(CpuTimers::new()) }; MAX_TIMER_CPUS];
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+fn add(deadline_ns: u64, period_ns: u64, callback: TimerCallback, ctx: usize) -> Option<TimerId> {
+    let cpu_id = crate::power::this_cpu_id();
+    let _irq = IrqMask::new();
+    let mut timers = TIMERS.get(cpu_id)?.lock();
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    let timer = Timer {
+        deadline_ns,
+        period_ns,
+        seq,
+        callback,
+        ctx,
+    };
+    if !timers.queue.push(timer) {
+        return None;
+    }
+    if let Some(deadline) = timers.reprogram(monotonic_time_nanos(), false) {
+        set_oneshot_timer(deadline);
+    }
+    Some(TimerId { cpu_id, seq })
+}
+
+/// Adds a one-shot timer on the current CPU, which fires at the given
+/// monotonic time deadline (in nanoseconds).
+///
+/// `ctx` is passed to the callback as-is. It returns `None` if the timer
+/// queue of the current CPU is full, or the current CPU ID is not less than
+/// [`MAX_TIMER_CPUS`].
+pub fn add_timer(deadline_ns: u64, callback: TimerCallback, ctx: usize) -> Option<TimerId> {
+    add(deadline_ns, 0, callback, ctx)
+}
+
+/// Adds a periodic timer on the current CPU, which first fires at the given
+/// monotonic time deadline (in nanoseconds), then every `period_ns`
+/// nanoseconds.
+///
+/// If the timer IRQ is delayed for more than one period, the missed periods
+/// are skipped. `ctx` is passed to the callback as-is. It returns `None` if
+/// `period_ns` is `0`, the timer queue of the current CPU is full, or the
+/// current CPU ID is not less than [`MAX_TIMER_CPUS`].
+pub fn add_periodic_timer(
+    deadline_ns: u64,
+    period_ns: u64,
+    callback: TimerCallback,
+    ctx: usize,
+) -> Option<TimerId> {
+    if period_ns == 0 {
+        return None;
+    }
+    add(deadline_ns, period_ns, callback, ctx)
+}
+
+/// Cancels a pending timer.
+///
+/// It can be called on any CPU, and in the timer callbacks. Cancelling a
+/// periodic timer in its own callback stops it from firing again. It returns
+/// `true` if the timer was pending, `false` if it has fired (for one-shot
+/// timers) or has been cancelled.
+pub fn cancel_timer(id: TimerId) -> bool {
+    // The hardware timer is not re-programmed, it fires as before and finds
+    // nothing to do.
+    let _irq = IrqMask::new();
+    TIMERS
+        .get(id.cpu_id)
+        .is_some_and(|timers| timers.lock().queue.cancel(id.seq))
+}
+
+/// Returns the earliest deadline (in nanoseconds) of the timers on the
+/// current CPU, or `None` if there are no pending timers.
+pub fn next_deadline() -> Option<u64> {
+    let _irq = IrqMask::new();
+    TIMERS
+        .get(crate::power::this_cpu_id())
+        .and_then(|timers| timers.lock().queue.next_deadline())
+}
+
+/// Handles the timer IRQ of the current CPU.
+///
+/// It fires all expired timers on the current CPU, and programs the hardware
+/// one-shot timer for the next one. The kernel should register it for the
+/// timer IRQ of the platform, e.g.,
+/// `axplat::irq::register(TIMER_IRQ, handle_timer_irq)`.
+pub fn handle_timer_irq() {
+    let Some(timers) = TIMERS.get(crate::power::this_cpu_id()) else {
+        return;
+    };
+    loop {
+        let now_ns = monotonic_time_nanos();
+        let irq = IrqMask::new();
+        let mut guard = timers.lock();
+        let Some(timer) = guard.pop_expired(now_ns) else {
+            if let Some(deadline) = guard.reprogram(now_ns, true) {
+                set_oneshot_timer(deadline);
+            }
+            break;
+        };
+        // Allow the callback to add or cancel timers.
+        drop(guard);
+        drop(irq);
+        (timer.callback)(now_ns, timer.ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CpuTimers, MAX_PROGRAM_INTERVAL_NANOS, Timer, TimerQueue};
+
+    fn timer(deadline_ns: u64, seq: u64) -> Timer {
+        Timer {
+            deadline_ns,
+            seq,
+            ..Timer::EMPTY
+        }
+    }
+
+    fn periodic(deadline_ns: u64, period_ns: u64, seq: u64) -> Timer {
+        Timer {
+            period_ns,
+            ..timer(deadline_ns, seq)
+        }
+    }
+
+    fn drain<const N: usize>(queue: &mut TimerQueue<N>) -> [u64; N] {
+        let mut seqs = [0; N];
+        let mut i = 0;
+        while let Some(t) = queue.pop_expired(u64::MAX) {
+            seqs[i] = t.seq;
+            i += 1;
+        }
+        seqs
+    }
+
+    #[test]
+    fn ordered_by_deadline() {
+        let mut queue = TimerQueue::<6>::new();
+        for (seq, deadline) in [(1, 30), (2, 10), (3, 50), (4, 10), (5, 20), (6, 40)] {
+            assert!(queue.push(timer(deadline, seq)));
+        }
+        assert!(!queue.push(timer(0, 7)));
+        assert_eq!(queue.next_deadline(), Some(10));
+        assert!(queue.pop_expired(9).is_none());
+        assert_eq!(drain(&mut queue), [2, 4, 5, 1, 6, 3]);
+        assert_eq!(queue.next_deadline(), None);
+    }
+
+    #[test]
+    fn cancel() {
+        let mut queue = TimerQueue::<5>::new();
+        for (seq, deadline) in [(1, 50), (2, 40), (3, 30), (4, 20), (5, 10)] {
+            assert!(queue.push(timer(deadline, seq)));
+        }
+        assert!(queue.cancel(5));
+        assert!(!queue.cancel(5));
+        assert!(queue.cancel(2));
+        assert_eq!(queue.next_deadline(), Some(20));
+        assert!(queue.push(timer(35, 6)));
+        assert_eq!(drain(&mut queue), [4, 3, 6, 1, 0]);
+    }
+
+    #[test]
+    fn periodic_catch_up() {
+        let mut timers = CpuTimers::new();
+        assert!(timers.queue.push(periodic(100, 10, 1)));
+        assert!(timers.queue.push(timer(120, 2)));
+        assert!(timers.pop_expired(99).is_none());
+
+        // Fired late by 3 periods, the missed ones are skipped.
+        let t = timers.pop_expired(135).unwrap();
+        assert_eq!((t.seq, t.deadline_ns), (1, 100));
+        assert_eq!(timers.pop_expired(135).unwrap().seq, 2);
+        assert!(timers.pop_expired(135).is_none());
+        assert_eq!(timers.queue.next_deadline(), Some(140));
+
+        // Fired exactly on time.
+        assert_eq!(timers.pop_expired(140).unwrap().deadline_ns, 140);
+        assert_eq!(timers.queue.next_deadline(), Some(150));
+    }
+
+    #[test]
+    fn periodic_overflow() {
+        let mut timers = CpuTimers::new();
+        assert!(timers.queue.push(periodic(u64::MAX - 10, 10, 1)));
+        assert!(timers.queue.push(periodic(100, u64::MAX, 2)));
+
+        // The next deadlines do not fit in `u64`, so they are dropped.
+        assert_eq!(timers.pop_expired(200).unwrap().seq, 2);
+        assert_eq!(timers.queue.next_deadline(), Some(u64::MAX - 10));
+        assert_eq!(timers.pop_expired(u64::MAX).unwrap().seq, 1);
+        assert_eq!(timers.queue.next_deadline(), None);
+
+        // Fired late by `u64::MAX` periods.
+        assert!(timers.queue.push(periodic(0, 1, 3)));
+        assert_eq!(timers.pop_expired(u64::MAX).unwrap().seq, 3);
+        assert_eq!(timers.queue.next_deadline(), None);
+    }
+
+    #[test]
+    fn cancel_periodic_in_callback() {
+        let mut timers = CpuTimers::new();
+        assert!(timers.queue.push(periodic(100, 10, 1)));
+        assert!(timers.queue.push(timer(200, 2)));
+        let t = timers.pop_expired(100).unwrap();
+        // As the callback would do, after the timer is added back.
+        assert!(timers.queue.cancel(t.seq));
+        assert!(timers.pop_expired(150).is_none());
+        assert_eq!(timers.queue.next_deadline(), Some(200));
+    }
+
+    #[test]
+    fn reprogram() {
+        let mut timers = CpuTimers::new();
+        assert_eq!(timers.reprogram(0, true), None);
+
+        // Far deadlines are capped.
+        assert!(timers.queue.push(timer(u64::MAX, 1)));
+        assert_eq!(timers.reprogram(0, false), Some(MAX_PROGRAM_INTERVAL_NANOS));
+        assert_eq!(timers.reprogram(0, false), None);
+        assert!(timers.queue.push(timer(50, 2)));
+        assert_eq!(timers.reprogram(0, false), Some(50));
+        assert!(timers.queue.push(timer(60, 3)));
+        assert_eq!(timers.reprogram(0, false), None);
+        assert_eq!(timers.reprogram(10, true), Some(50));
+
+        // Nothing is armed when the queue becomes empty.
+        assert!(timers.queue.cancel(1) && timers.queue.cancel(2) && timers.queue.cancel(3));
+        assert_eq!(timers.reprogram(60, true), None);
+        assert!(timers.queue.push(timer(70, 4)));
+        assert_eq!(timers.reprogram(60, false), Some(70));
+    }
+}
@@ -15,4 +15,11 @@ impl ConsoleIf for ConsoleIfImpl {
     fn read_bytes(bytes: &mut [u8]) -> usize {
         todo!()
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize> {
+        todo!()
+    }
 }
@@ -0,0 +1,11 @@
+use axplat::devices::{Device, DeviceIf};
+
+struct DeviceIfImpl;
+
+#[impl_interface]
+impl DeviceIf for DeviceIfImpl {
+    /// Returns the devices on the platform that drivers may use.
+    fn devices() -> &'static [Device] {
+        todo!()
+    }
+}
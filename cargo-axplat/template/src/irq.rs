@@ -1,4 +1,4 @@
-use axplat::irq::{IrqHandler, IrqIf, IpiTarget};
+use axplat::irq::{HandlerToken, IrqContextHandler, IrqIf, IrqResult, IpiTarget, TriggerMode};
 
 struct IrqIfImpl;
 
@@ -9,19 +9,24 @@ impl IrqIf for IrqIfImpl {
         todo!()
     }
 
-    /// Registers an IRQ handler for the given IRQ.
+    /// Registers an IRQ handler with context for the given IRQ.
     ///
-    /// It also enables the IRQ if the registration succeeds. It returns `false`
-    /// if the registration failed.
-    fn register(irq: usize, handler: IrqHandler) -> bool {
+    /// It also enables the IRQ if the registration succeeds. It returns the
+    /// token to unregister the handler, or `None` if the registration failed.
+    fn register_with_context(
+        irq: usize,
+        handler: IrqContextHandler,
+        ctx: usize,
+    ) -> Option<HandlerToken> {
         todo!()
     }
 
-    /// Unregisters the IRQ handler for the given IRQ.
+    /// Unregisters the IRQ handler registered with the given token for the
+    /// given IRQ.
     ///
-    /// It also disables the IRQ if the unregistration succeeds. It returns the
-    /// existing handler if it is registered, `None` otherwise.
-    fn unregister(irq: usize) -> Option<IrqHandler> {
+    /// The IRQ is disabled only if the last handler is unregistered. It
+    /// returns `true` if the handler was registered, `false` otherwise.
+    fn unregister(irq: usize, token: HandlerToken) -> bool {
         todo!()
     }
 
@@ -38,4 +43,19 @@ impl IrqIf for IrqIfImpl {
     fn send_ipi(irq_num: usize, target: IpiTarget) {
         todo!()
     }
+
+    /// Routes the given IRQ to the CPUs in `cpu_mask`.
+    fn set_affinity(irq: usize, cpu_mask: usize) -> IrqResult {
+        todo!()
+    }
+
+    /// Sets the priority of the given IRQ.
+    fn set_priority(irq: usize, priority: u8) -> IrqResult {
+        todo!()
+    }
+
+    /// Sets the trigger mode (edge or level) of the given IRQ.
+    fn set_trigger(irq: usize, mode: TriggerMode) -> IrqResult {
+        todo!()
+    }
 }
@@ -4,6 +4,7 @@
 extern crate axplat;
 
 mod console;
+mod devices;
 mod init;
 #[cfg(feature = "irq")]
 mod irq;
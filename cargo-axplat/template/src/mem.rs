@@ -1,4 +1,4 @@
-use axplat::mem::{MemIf, PhysAddr, RawRange, VirtAddr};
+use axplat::mem::{DmaRange, DmaZone, MemIf, PhysAddr, RawRange, VirtAddr};
 
 struct MemIfImpl;
 
@@ -45,4 +45,51 @@ impl MemIf for MemIfImpl {
     fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
         todo!()
     }
+
+    /// Whether the DMA of devices is coherent with the CPU caches.
+    ///
+    /// If not, drivers must clean the cache by [`cache_clean_range`] before a
+    /// device reads a buffer, and invalidate the cache by
+    /// [`cache_invalidate_range`] after a device writes a buffer.
+    fn dma_coherent() -> bool {
+        todo!()
+    }
+
+    /// Cleans (writes back) the data cache lines that cover the given virtual
+    /// address range, so that the data is visible to devices.
+    fn cache_clean_range(vaddr: VirtAddr, size: usize) {
+        todo!()
+    }
+
+    /// Invalidates the data cache lines that cover the given virtual address
+    /// range, so that the data written by devices is visible to the CPU.
+    ///
+    /// The lines partially covered by the range are also cleaned, so that the
+    /// data outside the range is not discarded.
+    fn cache_invalidate_range(vaddr: VirtAddr, size: usize) {
+        todo!()
+    }
+
+    /// Cleans and invalidates the data cache lines that cover the given
+    /// virtual address range.
+    fn cache_flush_range(vaddr: VirtAddr, size: usize) {
+        todo!()
+    }
+
+    /// Returns the windows through which devices access physical memory by
+    /// DMA.
+    ///
+    /// Physical memory outside the windows can not be accessed by DMA, e.g.,
+    /// on platforms whose DMA controllers only reach the first 1 GiB.
+    fn dma_ranges() -> &'static [DmaRange] {
+        todo!()
+    }
+
+    /// Returns the end physical address (exclusive) of the given DMA zone.
+    ///
+    /// The zone starts from physical address `0`. Returns `None` if all
+    /// physical memory is in the zone.
+    fn dma_zone_end(zone: DmaZone) -> Option<usize> {
+        todo!()
+    }
 }
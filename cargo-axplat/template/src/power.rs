@@ -1,4 +1,4 @@
-use axplat::power::PowerIf;
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
 
 struct PowerImpl;
 
@@ -10,7 +10,34 @@ impl PowerIf for PowerImpl {
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> axplat::power::CpuBootResult {
+        todo!()
+    }
+
+    /// Powers off the calling CPU.
+    ///
+    /// The CPU can be brought back by `cpu_boot`, and then enters the function
+    /// decorated by `axplat::secondary_main` again.
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        todo!()
+    }
+
+    /// Returns the power state of the given CPU, or `None` if it is unknown.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        todo!()
+    }
+
+    /// Returns the idle states that CPUs can enter, ordered from the shallowest
+    /// to the deepest.
+    fn idle_states() -> &'static [IdleState] {
+        todo!()
+    }
+
+    /// Enters the given idle state on the current CPU, where `index` is the
+    /// index in `idle_states`.
+    fn cpu_idle(index: usize) {
         todo!()
     }
 
@@ -19,6 +46,19 @@ impl PowerIf for PowerImpl {
         todo!()
     }
 
+    /// Resets the whole system.
+    ///
+    /// If the platform can not do a warm reset, it falls back to a cold reset.
+    fn system_reset(kind: ResetKind) -> ! {
+        todo!()
+    }
+
+    /// Returns the reason for the last reset, or `None` if the platform does
+    /// not know it.
+    fn reset_reason() -> Option<ResetReason> {
+        todo!()
+    }
+
     /// Get the number of CPU cores available on this platform.
     fn cpu_num() -> usize {
         todo!()
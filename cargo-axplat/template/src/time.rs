@@ -25,6 +25,12 @@ impl TimeIf for TimeIfImpl {
         todo!()
     }
 
+    /// Sets the wall time (also known as realtime) in nanoseconds since
+    /// epoch.
+    fn set_wall_time_nanos(nanos: u64) {
+        todo!()
+    }
+
     /// Set a one-shot timer.
     ///
     /// A timer interrupt will be triggered at the specified monotonic time
@@ -33,4 +39,24 @@ impl TimeIf for TimeIfImpl {
     fn set_oneshot_timer(deadline_ns: u64) {
         todo!()
     }
+
+    /// Returns the IRQ number of the RTC alarm, or `None` if the platform has
+    /// no RTC alarm.
+    #[cfg(feature = "irq")]
+    fn rtc_alarm_irq() -> Option<usize> {
+        todo!()
+    }
+
+    /// Sets the RTC alarm at the specified wall time (in nanoseconds since
+    /// epoch).
+    #[cfg(feature = "irq")]
+    fn set_rtc_alarm_nanos(wall_time_nanos: u64) -> bool {
+        todo!()
+    }
+
+    /// Cancels the pending RTC alarm, if any.
+    #[cfg(feature = "irq")]
+    fn cancel_rtc_alarm() {
+        todo!()
+    }
 }
@@ -2,7 +2,9 @@
 #![no_main]
 
 cfg_if::cfg_if! {
-    if #[cfg(target_arch = "x86_64")] {
+    if #[cfg(target_os = "linux")] {
+        extern crate axplat_linux_user;
+    } else if #[cfg(target_arch = "x86_64")] {
         extern crate axplat_x86_pc;
     } else if #[cfg(target_arch = "aarch64")] {
         extern crate axplat_aarch64_qemu_virt;
@@ -19,7 +21,7 @@ cfg_if::cfg_if! {
 
 fn init_kernel(cpu_id: usize, arg: usize) {
     // x86_64 requires the `percpu` crate to be initialized first.
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(target_os = "none", target_arch = "x86_64"))]
     axcpu::init::init_percpu(cpu_id);
 
     // Initialize trap, console, time.
@@ -48,6 +50,6 @@ fn main(cpu_id: usize, arg: usize) -> ! {
 #[cfg(all(target_os = "none", not(test)))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    axplat::console_println!("{info}");
+    axplat::console_panic_print!("{info}");
     axplat::power::system_off()
 }
@@ -22,6 +22,10 @@ fn gen_linker_script(arch: &str) -> Result<()> {
 }
 
 fn main() {
+    // Hosted targets (e.g. Linux) are linked as ordinary executables.
+    if std::env::var("CARGO_CFG_TARGET_OS").unwrap() != "none" {
+        return;
+    }
     let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
     gen_linker_script(&arch).unwrap();
     println!("cargo:rustc-link-arg=-no-pie");
@@ -1,6 +1,7 @@
-use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering::{Acquire, Release};
+use core::sync::atomic::{AtomicBool, AtomicU64};
 
+#[cfg(target_os = "none")]
 use axcpu::trap::{IRQ, register_trap_handler};
 
 const TICKS_PER_SEC: u64 = 100;
@@ -11,6 +12,7 @@ pub fn irq_count() -> u64 {
     IRQ_COUNTER.load(Acquire)
 }
 
+#[cfg(target_os = "none")]
 #[register_trap_handler(IRQ)]
 fn irq_handler(vector: usize) -> bool {
     axplat::irq::handle(vector);
@@ -18,29 +20,31 @@ fn irq_handler(vector: usize) -> bool {
 }
 
 pub fn init_irq() {
-    fn update_timer() {
-        static PERIODIC_INTERVAL_NANOS: u64 = axplat::time::NANOS_PER_SEC / TICKS_PER_SEC;
-
+    fn tick(_now_ns: u64, _ctx: usize) {
         IRQ_COUNTER.fetch_add(1, Release);
-        // Reset the timer for the next interrupt.
-        static NEXT_DEADLINE: AtomicU64 = AtomicU64::new(0);
-
-        let now_ns = axplat::time::monotonic_time_nanos();
-        let mut deadline = NEXT_DEADLINE.load(Acquire);
-        if now_ns >= deadline {
-            deadline = now_ns + PERIODIC_INTERVAL_NANOS;
-        }
-
-        NEXT_DEADLINE.store(deadline + PERIODIC_INTERVAL_NANOS, Release);
-        axplat::time::set_oneshot_timer(deadline);
     }
 
-    // Register the timer IRQ handler.
-    axplat::irq::register(axplat_crate::config::devices::TIMER_IRQ, update_timer);
+    // Register the timer IRQ handler, which fires the software timers.
+    axplat::irq::register(
+        axplat_crate::config::devices::TIMER_IRQ,
+        axplat::time::timer::handle_timer_irq,
+    );
+    let period_ns = axplat::time::NANOS_PER_SEC / TICKS_PER_SEC;
+    axplat::time::timer::add_periodic_timer(
+        axplat::time::monotonic_time_nanos() + period_ns,
+        period_ns,
+        tick,
+        0,
+    )
+    .expect("failed to add the periodic timer");
     axplat::console_println!("Timer IRQ handler registered.");
 
     // Enable the timer IRQ.
+    #[cfg(target_os = "none")]
     axcpu::asm::enable_irqs();
+    // IRQs are delivered as signals, and dispatched by the platform itself.
+    #[cfg(target_os = "linux")]
+    axplat_crate::enable_irqs();
 }
 
 pub fn test_irq() {
@@ -68,5 +72,47 @@ pub fn test_irq() {
         );
     }
 
+    // The platform counts every IRQ it dispatches, which includes ours.
+    let recorded = axplat::irq::stats::line_stats(axplat_crate::config::devices::TIMER_IRQ)
+        .map_or(0, |line| line.total());
+    axplat::console_println!("Timer IRQ count recorded by the platform: {recorded}");
+    if recorded < irq_count {
+        panic!("Timer IRQ statistics mismatch, expected at least {irq_count}, got {recorded}");
+    }
+
     axplat::console_println!("Timer IRQ test passed.");
 }
+
+pub fn test_timer_with_irqs_enabled() {
+    static FIRED: AtomicBool = AtomicBool::new(false);
+
+    fn fire(_now_ns: u64, _ctx: usize) {
+        FIRED.store(true, Release);
+    }
+
+    axplat::console_println!("Adding and cancelling timers with IRQs enabled...");
+
+    // The periodic timer keeps firing meanwhile, so the timer IRQs interrupt
+    // some of the calls while they are holding the timer queue lock.
+    let until = axplat::time::monotonic_time_nanos() + axplat::time::NANOS_PER_SEC;
+    while axplat::time::monotonic_time_nanos() < until {
+        let deadline = axplat::time::monotonic_time_nanos() + axplat::time::NANOS_PER_SEC;
+        let id = axplat::time::timer::add_timer(deadline, fire, 0)
+            .expect("failed to add the one-shot timer");
+        axplat::time::timer::next_deadline().expect("no pending timers");
+        if !axplat::time::timer::cancel_timer(id) {
+            panic!("The one-shot timer fired too early");
+        }
+    }
+
+    // A one-shot timer added with IRQs enabled still fires.
+    let period_ns = axplat::time::NANOS_PER_SEC / TICKS_PER_SEC;
+    axplat::time::timer::add_timer(axplat::time::monotonic_time_nanos() + period_ns, fire, 0)
+        .expect("failed to add the one-shot timer");
+    axplat::time::busy_wait(axplat::time::TimeValue::from_nanos(period_ns * 10));
+    if !FIRED.load(Acquire) {
+        panic!("The one-shot timer added with IRQs enabled did not fire");
+    }
+
+    axplat::console_println!("Timer test with IRQs enabled passed.");
+}
@@ -2,7 +2,9 @@
 #![no_main]
 
 cfg_if::cfg_if! {
-    if #[cfg(target_arch = "x86_64")] {
+    if #[cfg(target_os = "linux")] {
+        extern crate axplat_linux_user as axplat_crate;
+    } else if #[cfg(target_arch = "x86_64")] {
         extern crate axplat_x86_pc as axplat_crate;
     } else if #[cfg(target_arch = "aarch64")] {
         extern crate axplat_aarch64_qemu_virt as axplat_crate;
@@ -22,7 +24,7 @@ use irq::*;
 
 fn init_kernel(cpu_id: usize, arg: usize) {
     // x86_64 requires the `percpu` crate to be initialized first.
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(target_os = "none", target_arch = "x86_64"))]
     axcpu::init::init_percpu(cpu_id);
 
     // Initialize trap, console, time.
@@ -41,6 +43,7 @@ fn main(cpu_id: usize, arg: usize) -> ! {
 
     init_irq();
     test_irq();
+    test_timer_with_irqs_enabled();
 
     axplat::power::system_off();
 }
@@ -48,6 +51,6 @@ fn main(cpu_id: usize, arg: usize) -> ! {
 #[cfg(all(target_os = "none", not(test)))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    axplat::console_println!("{info}");
+    axplat::console_panic_print!("{info}");
     axplat::power::system_off()
 }
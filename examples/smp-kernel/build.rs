@@ -25,9 +25,12 @@ fn gen_linker_script(arch: &str) -> Result<()> {
 }
 
 fn main() {
+    println!("cargo:rerun-if-env-changed=AX_CPU_NUM");
+    // Hosted targets (e.g. Linux) are linked as ordinary executables.
+    if std::env::var("CARGO_CFG_TARGET_OS").unwrap() != "none" {
+        return;
+    }
     let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
     gen_linker_script(&arch).unwrap();
     println!("cargo:rustc-link-arg=-no-pie");
-
-    println!("cargo:rerun-if-env-changed=AX_CPU_NUM");
 }
@@ -9,9 +9,10 @@ pub fn init_smp_ok() -> bool {
 }
 
 pub fn init_kernel(cpu_id: usize, arg: usize) {
+    #[cfg(target_os = "none")]
     percpu::init_in_place().expect("failed to initialize per-CPU data area");
-    percpu::init_percpu_reg(cpu_id);
     init_cpu_id(cpu_id);
+    axplat::power::set_this_cpu_id_fn(this_cpu_id);
 
     // Initialize trap, console, time.
     axplat::init::init_early(cpu_id, arg);
@@ -21,7 +22,6 @@ pub fn init_kernel(cpu_id: usize, arg: usize) {
 }
 
 pub fn init_kernel_secondary(cpu_id: usize) {
-    percpu::init_percpu_reg(cpu_id);
     init_cpu_id(cpu_id);
 
     // Initialize trap, console, time.
@@ -31,16 +31,26 @@ pub fn init_kernel_secondary(cpu_id: usize) {
     axplat::init::init_later_secondary(cpu_id);
 }
 
-#[percpu::def_percpu]
-static CPU_ID: usize = 0;
-
-pub fn this_cpu_id() -> usize {
-    unsafe { CPU_ID.read_current_raw() }
-}
-
-/// Initialize the CPU ID for the current thread.
-pub fn init_cpu_id(cpu_id: usize) {
-    unsafe {
-        CPU_ID.write_current_raw(cpu_id);
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        // Each CPU is a host thread, whose ID is kept by the platform.
+        pub use axplat_crate::this_cpu_id;
+
+        fn init_cpu_id(_cpu_id: usize) {}
+    } else {
+        #[percpu::def_percpu]
+        static CPU_ID: usize = 0;
+
+        pub fn this_cpu_id() -> usize {
+            unsafe { CPU_ID.read_current_raw() }
+        }
+
+        /// Initialize the per-CPU data area and the CPU ID for the current CPU.
+        fn init_cpu_id(cpu_id: usize) {
+            percpu::init_percpu_reg(cpu_id);
+            unsafe {
+                CPU_ID.write_current_raw(cpu_id);
+            }
+        }
     }
 }
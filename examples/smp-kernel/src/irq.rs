@@ -1,5 +1,11 @@
+use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+#[cfg(target_os = "none")]
 use axcpu::trap::{IRQ, register_trap_handler};
 
+use crate::CPU_NUM;
+
+#[cfg(target_os = "none")]
 #[register_trap_handler(IRQ)]
 fn irq_handler(vector: usize) -> bool {
     axplat::irq::handle(vector);
@@ -11,8 +17,7 @@ pub fn init_irq() {
         // One timer interrupt per second.
         static PERIODIC_INTERVAL_NANOS: u64 = axplat::time::NANOS_PER_SEC;
         // Reset the timer for the next interrupt.
-        #[percpu::def_percpu]
-        static NEXT_DEADLINE: u64 = 0;
+        static NEXT_DEADLINE: [AtomicU64; CPU_NUM] = [const { AtomicU64::new(0) }; CPU_NUM];
 
         axplat::console_println!(
             "{:?} elapsed. Timer IRQ processed on CPU {}.",
@@ -20,14 +25,13 @@ pub fn init_irq() {
             crate::this_cpu_id()
         );
 
+        let next_deadline = &NEXT_DEADLINE[crate::this_cpu_id()];
         let now_ns = axplat::time::monotonic_time_nanos();
-        let mut deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
+        let mut deadline = next_deadline.load(Relaxed);
         if now_ns >= deadline {
             deadline = now_ns + PERIODIC_INTERVAL_NANOS;
         }
-        unsafe {
-            NEXT_DEADLINE.write_current_raw(deadline + PERIODIC_INTERVAL_NANOS);
-        }
+        next_deadline.store(deadline + PERIODIC_INTERVAL_NANOS, Relaxed);
         axplat::time::set_oneshot_timer(deadline);
     }
 
@@ -36,5 +40,13 @@ pub fn init_irq() {
     axplat::console_println!("Timer IRQ handler registered.");
 
     // Enable the timer IRQ.
+    enable_irqs();
+}
+
+pub fn enable_irqs() {
+    #[cfg(target_os = "none")]
     axcpu::asm::enable_irqs();
+    // IRQs are delivered as signals, and dispatched by the platform itself.
+    #[cfg(target_os = "linux")]
+    axplat_crate::enable_irqs();
 }
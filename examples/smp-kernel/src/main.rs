@@ -2,7 +2,9 @@
 #![no_main]
 
 cfg_if::cfg_if! {
-    if #[cfg(target_arch = "x86_64")] {
+    if #[cfg(target_os = "linux")] {
+        extern crate axplat_linux_user as axplat_crate;
+    } else if #[cfg(target_arch = "x86_64")] {
         extern crate axplat_x86_pc as axplat_crate;
     } else if #[cfg(target_arch = "aarch64")] {
         extern crate axplat_aarch64_qemu_virt as axplat_crate;
@@ -61,6 +63,6 @@ fn main(cpu_id: usize, arg: usize) -> ! {
 #[cfg(all(target_os = "none", not(test)))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    axplat::console_println!("{info}");
+    axplat::console_panic_print!("{info}");
     axplat::power::system_off()
 }
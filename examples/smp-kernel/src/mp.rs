@@ -1,4 +1,5 @@
 use core::sync::atomic::Ordering::{Acquire, Release};
+use core::time::Duration;
 use memory_addr::VirtAddr;
 
 use crate::{CPU_NUM, INITED_CPUS, init_kernel_secondary};
@@ -17,7 +18,10 @@ pub fn start_secondary_cpus(primary_cpu_id: usize) {
                 SECONDARY_BOOT_STACK[logic_cpu_id].as_ptr_range().end as usize
             }));
 
-            axplat::power::cpu_boot(i, stack_top.as_usize());
+            let timeout = Duration::from_secs(1);
+            if let Err(e) = axplat::power::cpu_boot_and_wait(i, stack_top.as_usize(), timeout) {
+                panic!("Failed to boot CPU {i}: {e:?}");
+            }
 
             logic_cpu_id += 1;
 
@@ -40,7 +44,7 @@ fn secondary_main(cpu_id: usize) -> ! {
         core::hint::spin_loop();
     }
 
-    axcpu::asm::enable_irqs();
+    crate::enable_irqs();
 
     // Infinite loop to receive and handle timer interrupts
     loop {
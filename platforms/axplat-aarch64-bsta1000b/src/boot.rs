@@ -83,7 +83,7 @@ unsafe extern "C" fn _start_primary() -> ! {
     // X0 = dtb
     core::arch::naked_asm!("
         mrs     x19, mpidr_el1
-        and     x19, x19, #0xffffff     // get current CPU id
+        and     x19, x19, #0xffffff     // get hardware CPU id
         mov     x20, x0                 // save DTB pointer
 
         adrp    x8, {boot_stack}        // setup boot stack
@@ -99,7 +99,7 @@ unsafe extern "C" fn _start_primary() -> ! {
         mov     x8, {phys_virt_offset}  // set SP to the high address
         add     sp, sp, x8
 
-        mov     x0, x19                 // call_main(cpu_id, dtb)
+        mov     x0, x19                 // call_main_hw(hw_id, dtb)
         mov     x1, x20
         ldr     x8, ={entry}
         blr     x8
@@ -112,7 +112,7 @@ unsafe extern "C" fn _start_primary() -> ! {
         boot_stack_size = const BOOT_STACK_SIZE,
         boot_pt = sym BOOT_PT_L0,
         phys_virt_offset = const PHYS_VIRT_OFFSET,
-        entry = sym axplat::call_main,
+        entry = sym axplat::call_main_hw,
     )
 }
 
@@ -123,7 +123,7 @@ pub(crate) unsafe extern "C" fn _start_secondary() -> ! {
     // X0 = stack pointer
     core::arch::naked_asm!("
         mrs     x19, mpidr_el1
-        and     x19, x19, #0xffffff     // get current CPU id
+        and     x19, x19, #0xffffff     // get hardware CPU id
 
         mov     sp, x0
         bl      {switch_to_el1}
@@ -134,7 +134,7 @@ pub(crate) unsafe extern "C" fn _start_secondary() -> ! {
         mov     x8, {phys_virt_offset}  // set SP to the high address
         add     sp, sp, x8
 
-        mov     x0, x19                 // call_secondary_main(cpu_id)
+        mov     x0, x19                 // call_secondary_main_hw(hw_id)
         ldr     x8, ={entry}
         blr     x8
         b      .",
@@ -143,6 +143,6 @@ pub(crate) unsafe extern "C" fn _start_secondary() -> ! {
         enable_fp = sym enable_fp,
         boot_pt = sym BOOT_PT_L0,
         phys_virt_offset = const PHYS_VIRT_OFFSET,
-        entry = sym axplat::call_secondary_main,
+        entry = sym axplat::call_secondary_main_hw,
     )
 }
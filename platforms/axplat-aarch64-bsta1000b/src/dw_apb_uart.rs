@@ -1,5 +1,8 @@
 //! snps,dw-apb-uart serial driver
 
+#[cfg(feature = "irq")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::mem::phys_to_virt;
 use axplat::console::ConsoleIf;
 use axplat::mem::{PhysAddr, pa};
@@ -10,10 +13,11 @@ const UART_BASE: PhysAddr = pa!(crate::config::devices::UART_PADDR);
 
 static UART: SpinNoIrq<DW8250> = SpinNoIrq::new(DW8250::new(phys_to_virt(UART_BASE).as_usize()));
 
-/// Writes a byte to the console.
-#[allow(dead_code)]
-pub fn putchar(c: u8) {
-    let mut uart = UART.lock();
+/// Whether the UART IRQ handler is registered.
+#[cfg(feature = "irq")]
+static IRQ_ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn do_putchar(uart: &mut DW8250, c: u8) {
     match c {
         b'\r' | b'\n' => {
             uart.putchar(b'\r');
@@ -23,6 +27,20 @@ pub fn putchar(c: u8) {
     }
 }
 
+/// Writes a byte to the console.
+#[allow(dead_code)]
+pub fn putchar(c: u8) {
+    do_putchar(&mut UART.lock(), c);
+}
+
+/// Writes bytes to the UART by a new `DW8250` instance, bypassing [`UART`].
+fn emergency_write_bytes(bytes: &[u8]) {
+    let mut uart = DW8250::new(phys_to_virt(UART_BASE).as_usize());
+    for c in bytes {
+        do_putchar(&mut uart, *c);
+    }
+}
+
 /// Reads a byte from the console, or returns [`None`] if no input is available.
 fn getchar() -> Option<u8> {
     UART.lock().getchar()
@@ -31,19 +49,36 @@ fn getchar() -> Option<u8> {
 /// UART simply initialize
 pub fn init_early() {
     UART.lock().init();
+    axplat::console::set_emergency_write_fn(emergency_write_bytes);
 }
 
-/// Set UART IRQ Enable
-#[cfg(feature = "irq")]
+/// Set UART IRQ Enable, and switches the console input to the
+/// interrupt-driven mode.
+#[cfg(feature = "console-irq")]
 pub fn init_irq() {
     UART.lock().set_ier(true);
-    axplat_arm_peripherals::gic::register_handler(crate::config::devices::UART_IRQ, handle);
+    if axplat_arm_peripherals::gic::register_handler(crate::config::devices::UART_IRQ, handle)
+        .is_some()
+    {
+        IRQ_ENABLED.store(true, Ordering::Release);
+        axplat::console::rx::enable();
+    }
 }
 
 /// UART IRQ Handler
-#[allow(dead_code)]
+///
+/// It drains the receive FIFO into [`axplat::console::rx`], then notifies the
+/// waiting tasks.
+#[cfg(feature = "console-irq")]
 pub fn handle() {
     trace!("Uart IRQ Handler");
+    {
+        let mut uart = UART.lock();
+        while let Some(c) = uart.getchar() {
+            axplat::console::rx::push(c);
+        }
+    }
+    axplat::console::rx::notify();
 }
 
 struct ConsoleIfImpl;
@@ -60,6 +95,10 @@ impl ConsoleIf for ConsoleIfImpl {
     /// Reads bytes from the console into the given mutable slice.
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
+        #[cfg(feature = "irq")]
+        if axplat::console::rx::is_enabled() {
+            return axplat::console::rx::read(bytes);
+        }
         let mut read_len = 0;
         while read_len < bytes.len() {
             if let Some(c) = getchar() {
@@ -71,4 +110,13 @@ impl ConsoleIf for ConsoleIfImpl {
         }
         read_len
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize> {
+        IRQ_ENABLED
+            .load(Ordering::Acquire)
+            .then_some(crate::config::devices::UART_IRQ)
+    }
 }
@@ -2,7 +2,8 @@ use axplat::init::InitIf;
 
 #[allow(unused_imports)]
 use crate::config::devices::{GICC_PADDR, GICD_PADDR, TIMER_IRQ};
-use crate::config::plat::PSCI_METHOD;
+use crate::config::plat::{CPU_ID_LIST, PSCI_METHOD};
+use axplat::power::topology::{self, CpuTopology};
 
 struct InitIfImpl;
 
@@ -13,11 +14,22 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _dtb: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
         axcpu::init::init_trap();
+        // Fall back to the static configuration if no valid DTB is present.
+        unsafe { axplat::fdt::init(dtb) }.ok();
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init_from_fdt();
+        match axplat::fdt::cpus() {
+            Some(cpus) => topology::register_all(cpus.iter().copied()),
+            None => {
+                topology::register_all(CPU_ID_LIST.iter().map(|&id| CpuTopology::from_mpidr(id)))
+            }
+        };
         axplat_arm_peripherals::psci::init(PSCI_METHOD);
         super::dw_apb_uart::init_early();
         axplat_arm_peripherals::generic_timer::init_early();
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -44,6 +56,7 @@ impl InitIf for InitIfImpl {
             axplat_arm_peripherals::generic_timer::enable_irqs(TIMER_IRQ);
 
             // enable UART IRQs
+            #[cfg(feature = "console-irq")]
             crate::dw_apb_uart::init_irq();
         }
     }
@@ -21,6 +21,7 @@ pub mod config {
 }
 
 mod boot;
+mod devices;
 mod dw_apb_uart;
 mod init;
 mod mem;
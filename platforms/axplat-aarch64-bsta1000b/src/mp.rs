@@ -1,17 +1,13 @@
-use crate::config::plat::CPU_ID_LIST;
 use axplat::mem::{PhysAddr, va, virt_to_phys};
+use axplat::power::{CpuBootError, CpuBootResult, topology};
 
 /// Starts the given secondary CPU with its boot stack.
-pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
-    if cpu_id >= CPU_ID_LIST.len() {
+pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) -> CpuBootResult {
+    let Some(target_cpu) = topology::hw_id(cpu_id) else {
         error!("No support for bsta1000b core {}", cpu_id);
-        return;
-    }
+        return Err(CpuBootError::NotPresent);
+    };
 
     let entry = virt_to_phys(va!(crate::boot::_start_secondary as *const () as usize));
-    axplat_arm_peripherals::psci::cpu_on(
-        CPU_ID_LIST[cpu_id],
-        entry.as_usize(),
-        stack_top.as_usize(),
-    );
+    axplat_arm_peripherals::psci::cpu_on(target_cpu, entry.as_usize(), stack_top.as_usize())
 }
@@ -39,13 +39,6 @@ unsafe fn enable_fp() {
     axcpu::asm::enable_fp();
 }
 
-fn hart_to_logid(hard_id: usize) -> usize {
-    crate::config::plat::CPU_ID_LIST
-        .iter()
-        .position(|&x| x == hard_id)
-        .unwrap()
-}
-
 /// Kernel entry point with Linux image header.
 ///
 /// Some bootloaders require this header to be present at the beginning of the
@@ -84,7 +77,7 @@ unsafe extern "C" fn _start_primary() -> ! {
     // X0 = dtb
     core::arch::naked_asm!("
         mrs     x19, mpidr_el1
-        and     x19, x19, #0xffffff     // get current CPU id
+        and     x19, x19, #0xffffff     // get hardware CPU id
         mov     x20, x0                 // save DTB pointer
 
         adrp    x8, {boot_stack}        // setup boot stack
@@ -101,9 +94,8 @@ unsafe extern "C" fn _start_primary() -> ! {
         add     sp, sp, x8
 
         mov     x0, x19
-        bl      {hart_to_logid}         // x0 = logical CPU ID
         mov     x1, x20
-        ldr     x8, ={entry}            // call_main(cpu_id, dtb)
+        ldr     x8, ={entry}            // call_main_hw(hw_id, dtb)
         blr     x8
         b      .",
         switch_to_el1 = sym axcpu::init::switch_to_el1,
@@ -114,8 +106,7 @@ unsafe extern "C" fn _start_primary() -> ! {
         boot_stack_size = const BOOT_STACK_SIZE,
         boot_pt = sym BOOT_PT_L0,
         phys_virt_offset = const PHYS_VIRT_OFFSET,
-        hart_to_logid = sym hart_to_logid,
-        entry = sym axplat::call_main,
+        entry = sym axplat::call_main_hw,
     )
 }
 
@@ -126,7 +117,7 @@ pub(crate) unsafe extern "C" fn _start_secondary() -> ! {
     // X0 = stack pointer
     core::arch::naked_asm!("
         mrs     x19, mpidr_el1
-        and     x19, x19, #0xffffff     // get current CPU id
+        and     x19, x19, #0xffffff     // get hardware CPU id
 
         mov     sp, x0
         bl      {switch_to_el1}
@@ -138,8 +129,7 @@ pub(crate) unsafe extern "C" fn _start_secondary() -> ! {
         add     sp, sp, x8
 
         mov     x0, x19
-        bl      {hart_to_logid}         // x0 = logical CPU ID
-        ldr     x8, ={entry}            // call_secondary_main(cpu_id)
+        ldr     x8, ={entry}            // call_secondary_main_hw(hw_id)
         blr     x8
         b      .",
         switch_to_el1 = sym axcpu::init::switch_to_el1,
@@ -147,7 +137,6 @@ pub(crate) unsafe extern "C" fn _start_secondary() -> ! {
         enable_fp = sym enable_fp,
         boot_pt = sym BOOT_PT_L0,
         phys_virt_offset = const PHYS_VIRT_OFFSET,
-        hart_to_logid = sym hart_to_logid,
-        entry = sym axplat::call_secondary_main,
+        entry = sym axplat::call_secondary_main_hw,
     )
 }
@@ -0,0 +1,35 @@
+use axplat::devices::{Device, DeviceIf, DeviceKind, DeviceList};
+
+use crate::config::devices::{
+    PCI_BUS_END, PCI_ECAM_BASE, UART_IRQ, UART_PADDR, VIRTIO_MMIO_RANGES,
+};
+
+/// The devices in the static configuration.
+static DEVICES: DeviceList = DeviceList::new()
+    .with(Device::new(
+        DeviceKind::Uart,
+        "arm,pl011",
+        (UART_PADDR, 0x1000),
+        Some(UART_IRQ),
+    ))
+    .with_each(DeviceKind::VirtioMmio, "virtio,mmio", VIRTIO_MMIO_RANGES)
+    // Each bus takes 1M bytes of the ECAM space.
+    .with(Device::new(
+        DeviceKind::PciHostBridge,
+        "pci-host-ecam-generic",
+        (PCI_ECAM_BASE, (PCI_BUS_END + 1) << 20),
+        None,
+    ));
+
+struct DeviceIfImpl;
+
+#[impl_interface]
+impl DeviceIf for DeviceIfImpl {
+    /// Returns the devices on the platform that drivers may use.
+    ///
+    /// They are taken from the device tree, or from the static configuration
+    /// if no DTB is present.
+    fn devices() -> &'static [Device] {
+        axplat::fdt::devices().unwrap_or(&DEVICES)
+    }
+}
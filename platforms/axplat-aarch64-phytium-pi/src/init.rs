@@ -2,8 +2,9 @@ use axplat::init::InitIf;
 
 #[allow(unused_imports)]
 use crate::config::devices::{GICC_PADDR, GICD_PADDR, TIMER_IRQ, UART_IRQ, UART_PADDR};
-use crate::config::plat::PSCI_METHOD;
+use crate::config::plat::{CPU_ID_LIST, PSCI_METHOD};
 use axplat::mem::{pa, phys_to_virt};
+use axplat::power::topology::{self, CpuTopology};
 
 struct InitIfImpl;
 
@@ -14,11 +15,22 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _dtb: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
         axcpu::init::init_trap();
+        // Fall back to the static configuration if no valid DTB is present.
+        unsafe { axplat::fdt::init(dtb) }.ok();
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init_from_fdt();
+        match axplat::fdt::cpus() {
+            Some(cpus) => topology::register_all(cpus.iter().copied()),
+            None => {
+                topology::register_all(CPU_ID_LIST.iter().map(|&id| CpuTopology::from_mpidr(id)))
+            }
+        };
         axplat_arm_peripherals::pl011::init_early(phys_to_virt(pa!(UART_PADDR)));
         axplat_arm_peripherals::psci::init(PSCI_METHOD);
         axplat_arm_peripherals::generic_timer::init_early();
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -43,7 +55,8 @@ impl InitIf for InitIfImpl {
             axplat_arm_peripherals::generic_timer::enable_irqs(TIMER_IRQ);
 
             // enable UART IRQs
-            axplat::irq::register(UART_IRQ, axplat_arm_peripherals::pl011::irq_handler);
+            #[cfg(feature = "console-irq")]
+            axplat_arm_peripherals::pl011::init_irq(UART_IRQ);
         }
     }
 
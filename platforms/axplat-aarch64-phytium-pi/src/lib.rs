@@ -21,6 +21,7 @@ pub mod config {
 }
 
 mod boot;
+mod devices;
 mod init;
 mod mem;
 mod power;
@@ -1,7 +1,7 @@
-use axplat::mem::{MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
+use axplat::mem::{DmaRange, DmaZone, MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
 
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use crate::config::plat::{PHYS_BUS_OFFSET, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
 
 struct MemIfImpl;
 
@@ -12,7 +12,7 @@ impl MemIf for MemIfImpl {
     /// All memory ranges except reserved ranges (including the kernel loaded
     /// range) are free for allocation.
     fn phys_ram_ranges() -> &'static [RawRange] {
-        &[(PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE)]
+        axplat::fdt::phys_ram_ranges().unwrap_or(&[(PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE)])
     }
 
     /// Returns all reserved physical memory ranges on the platform.
@@ -23,12 +23,12 @@ impl MemIf for MemIfImpl {
     /// Note that the ranges returned should not include the range where the
     /// kernel is loaded.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[]
+        axplat::fdt::reserved_phys_ram_ranges().unwrap_or(&[])
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
-        &MMIO_RANGES
+        axplat::fdt::mmio_ranges().unwrap_or(&MMIO_RANGES)
     }
 
     /// Translates a physical address to a virtual address.
@@ -40,4 +40,47 @@ impl MemIf for MemIfImpl {
     fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
         pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
     }
+
+    /// Whether the DMA of devices is coherent with the CPU caches.
+    ///
+    /// Devices are not assumed to snoop the CPU caches.
+    fn dma_coherent() -> bool {
+        false
+    }
+
+    /// Cleans the data cache lines that cover the given virtual address range.
+    fn cache_clean_range(vaddr: VirtAddr, size: usize) {
+        axplat_arm_peripherals::cache::clean_range(vaddr, size)
+    }
+
+    /// Invalidates the data cache lines that cover the given virtual address
+    /// range.
+    fn cache_invalidate_range(vaddr: VirtAddr, size: usize) {
+        axplat_arm_peripherals::cache::invalidate_range(vaddr, size)
+    }
+
+    /// Cleans and invalidates the data cache lines that cover the given
+    /// virtual address range.
+    fn cache_flush_range(vaddr: VirtAddr, size: usize) {
+        axplat_arm_peripherals::cache::flush_range(vaddr, size)
+    }
+
+    /// Returns the windows through which devices access physical memory by
+    /// DMA.
+    ///
+    /// All physical memory is accessed at the bus addresses offset by
+    /// `PHYS_BUS_OFFSET`.
+    fn dma_ranges() -> &'static [DmaRange] {
+        &[DmaRange::new(0, PHYS_BUS_OFFSET, usize::MAX)]
+    }
+
+    /// Returns the end physical address (exclusive) of the given DMA zone.
+    ///
+    /// There is no device more restricted than those with 32-bit DMA
+    /// addresses.
+    fn dma_zone_end(zone: DmaZone) -> Option<usize> {
+        match zone {
+            DmaZone::Dma | DmaZone::Dma32 => Some(0x1_0000_0000),
+        }
+    }
 }
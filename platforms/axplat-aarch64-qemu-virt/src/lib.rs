@@ -4,8 +4,11 @@
 extern crate axplat;
 
 mod boot;
+mod devices;
 mod init;
 mod mem;
+#[cfg(feature = "pci")]
+mod pci;
 mod power;
 
 pub mod config {
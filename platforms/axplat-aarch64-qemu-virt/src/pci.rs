@@ -0,0 +1,27 @@
+use axplat::mem::pa;
+use axplat::pci::{Ecam, HostBridge, IntxRouting, PciWindows};
+
+use crate::config::devices::{PCI_BUS_END, PCI_ECAM_BASE, PCI_IRQ_BASE, PCI_RANGES};
+
+static ECAM: Ecam = Ecam::new(pa!(PCI_ECAM_BASE));
+
+/// Enumerates the PCI functions behind the ECAM host bridge, and assigns their
+/// BARs from `pci-ranges`.
+pub fn init() {
+    let bridge = HostBridge::new(
+        &ECAM,
+        PCI_BUS_END as u8,
+        PciWindows::from_ranges(PCI_RANGES),
+        IntxRouting::Swizzle { base: PCI_IRQ_BASE },
+    );
+    for dev in axplat::pci::init(bridge) {
+        log::debug!(
+            "PCI {}: {:04x}:{:04x}, class {:02x}{:02x}",
+            dev.addr,
+            dev.vendor_id,
+            dev.device_id,
+            dev.class,
+            dev.subclass
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use axplat::power::PowerIf;
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
 
 struct PowerImpl;
 
@@ -10,10 +10,36 @@ impl PowerIf for PowerImpl {
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> axplat::power::CpuBootResult {
         use axplat::mem::{va, virt_to_phys};
+        use axplat::power::{CpuBootError, topology};
+
+        let target_cpu = topology::hw_id(cpu_id).ok_or(CpuBootError::NotPresent)?;
         let entry_paddr = virt_to_phys(va!(crate::boot::_start_secondary as *const () as usize));
-        axplat_arm_peripherals::psci::cpu_on(cpu_id, entry_paddr.as_usize(), stack_top_paddr);
+        axplat_arm_peripherals::psci::cpu_on(target_cpu, entry_paddr.as_usize(), stack_top_paddr)
+    }
+
+    /// Powers off the calling CPU by PSCI `CPU_OFF`.
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        axplat_arm_peripherals::psci::cpu_off()
+    }
+
+    /// Returns the power state of the given CPU by PSCI `AFFINITY_INFO`.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        let target_cpu = axplat::power::topology::hw_id(cpu_id)?;
+        axplat_arm_peripherals::psci::affinity_info(target_cpu)
+    }
+
+    /// Returns the idle states that CPUs can enter.
+    fn idle_states() -> &'static [IdleState] {
+        axplat_arm_peripherals::psci::IDLE_STATES
+    }
+
+    /// Enters the given idle state by WFI or PSCI `CPU_SUSPEND`.
+    fn cpu_idle(index: usize) {
+        axplat_arm_peripherals::psci::cpu_idle(index)
     }
 
     /// Shutdown the whole system.
@@ -21,8 +47,19 @@ impl PowerIf for PowerImpl {
         axplat_arm_peripherals::psci::system_off()
     }
 
+    /// Resets the whole system.
+    fn system_reset(kind: ResetKind) -> ! {
+        axplat_arm_peripherals::psci::system_reset(kind)
+    }
+
+    /// Returns the reason for the last reset, which is not known.
+    fn reset_reason() -> Option<ResetReason> {
+        None
+    }
+
     /// Get the number of CPU cores available on this platform.
     fn cpu_num() -> usize {
-        crate::config::plat::MAX_CPU_NUM
+        use crate::config::plat::MAX_CPU_NUM;
+        axplat::fdt::cpu_num().map_or(MAX_CPU_NUM, |n| n.min(MAX_CPU_NUM))
     }
 }
@@ -1,8 +1,10 @@
 use axplat::init::InitIf;
 use axplat::mem::{pa, phys_to_virt};
+use axplat::power::topology::{self, CpuTopology};
 
 #[allow(unused_imports)]
 use crate::config::devices::{GICC_PADDR, GICD_PADDR, TIMER_IRQ, UART_IRQ, UART_PADDR};
+use crate::config::plat::MAX_CPU_NUM;
 
 struct InitIfImpl;
 
@@ -13,15 +15,28 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _dtb: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
+        #[cfg(feature = "smp")]
+        crate::mp::CPU_STATES.set(_cpu_id, axplat::power::CpuState::On);
         axcpu::init::init_trap();
+        // Fall back to the static configuration if no valid DTB is present.
+        // The spin table is always reserved, whatever the DTB says.
+        unsafe { axplat::fdt::init_with_reserved(dtb, &[crate::mem::SPIN_TABLE]) }.ok();
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init_from_fdt();
+        match axplat::fdt::cpus() {
+            Some(cpus) => topology::register_all(cpus.iter().copied()),
+            None => topology::register_all((0..MAX_CPU_NUM).map(CpuTopology::flat)),
+        };
         axplat_arm_peripherals::pl011::init_early(phys_to_virt(pa!(UART_PADDR)));
         axplat_arm_peripherals::generic_timer::init_early();
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
     #[cfg(feature = "smp")]
-    fn init_early_secondary(_cpu_id: usize) {
+    fn init_early_secondary(cpu_id: usize) {
+        crate::mp::CPU_STATES.set(cpu_id, axplat::power::CpuState::On);
         axcpu::init::init_trap();
     }
 
@@ -41,7 +56,8 @@ impl InitIf for InitIfImpl {
             axplat_arm_peripherals::generic_timer::enable_irqs(TIMER_IRQ);
 
             // enable UART IRQs
-            axplat::irq::register(UART_IRQ, axplat_arm_peripherals::pl011::irq_handler);
+            #[cfg(feature = "console-irq")]
+            axplat_arm_peripherals::pl011::init_irq(UART_IRQ);
         }
     }
 
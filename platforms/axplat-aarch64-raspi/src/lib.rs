@@ -4,6 +4,7 @@
 extern crate axplat;
 
 mod boot;
+mod devices;
 mod init;
 mod mem;
 mod power;
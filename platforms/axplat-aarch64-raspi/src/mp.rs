@@ -1,7 +1,25 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use aarch64_cpu::registers::{MPIDR_EL1, Readable};
 use axplat::mem::{PhysAddr, pa, phys_to_virt, va, virt_to_phys};
+use axplat::power::{CpuBootError, CpuBootResult, CpuState, CpuStateTable, topology};
+
+use crate::config::plat::MAX_CPU_NUM;
 
 static mut SECONDARY_STACK_TOP: usize = 0;
 
+/// The power states of CPUs.
+pub static CPU_STATES: CpuStateTable<MAX_CPU_NUM> = CpuStateTable::new();
+
+/// Whether the CPU waits in [`park_current_cpu`] rather than in the spin table
+/// of the firmware.
+static PARKED: [AtomicBool; MAX_CPU_NUM] = [const { AtomicBool::new(false) }; MAX_CPU_NUM];
+
+/// The boot stack tops (in virtual address) to restart the parked CPUs with,
+/// or `0` if not released.
+static RELEASE_STACK_TOP: [AtomicUsize; MAX_CPU_NUM] = [const { AtomicUsize::new(0) }; MAX_CPU_NUM];
+
+/// The release addresses of the spin table, indexed by the hardware CPU IDs.
 const CPU_SPIN_TABLE: [PhysAddr; 4] = [pa!(0xd8), pa!(0xe0), pa!(0xe8), pa!(0xf0)];
 
 #[unsafe(naked)]
@@ -19,7 +37,21 @@ unsafe extern "C" fn modify_stack_and_start() {
 }
 
 /// Starts the given secondary CPU with its boot stack.
-pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
+///
+/// The CPU must be off, i.e., it has not been started, or has been taken
+/// offline by [`park_current_cpu`].
+pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) -> CpuBootResult {
+    let hw_id = topology::hw_id(cpu_id)
+        .filter(|&id| id < CPU_SPIN_TABLE.len())
+        .ok_or(CpuBootError::NotPresent)?;
+    CPU_STATES.start_boot(cpu_id)?;
+    if PARKED[cpu_id].load(Ordering::Acquire) {
+        let stack_top = phys_to_virt(stack_top).as_usize();
+        RELEASE_STACK_TOP[cpu_id].store(stack_top, Ordering::Release);
+        aarch64_cpu::asm::sev();
+        return Ok(());
+    }
+
     let entry_paddr = virt_to_phys(va!(modify_stack_and_start as usize)).as_usize();
 
     // set the boot stack of the given secondary CPU
@@ -28,10 +60,47 @@ pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     axcpu::asm::flush_dcache_line(va!(stack_top_ptr as usize));
 
     // set the boot code address of the given secondary CPU
-    let spintable_vaddr = phys_to_virt(CPU_SPIN_TABLE[cpu_id]);
+    let spintable_vaddr = phys_to_virt(CPU_SPIN_TABLE[hw_id]);
     let release_ptr = spintable_vaddr.as_mut_ptr() as *mut usize;
     unsafe { release_ptr.write_volatile(entry_paddr) };
     axcpu::asm::flush_dcache_line(spintable_vaddr);
 
     aarch64_cpu::asm::sev();
+    Ok(())
+}
+
+/// Takes the current CPU offline.
+///
+/// CPUs can not go back to the spin table of the firmware, so it waits for
+/// events with interrupts disabled, until it is released by
+/// [`start_secondary_cpu`]. Then it restarts from the secondary entry on the
+/// new boot stack.
+pub fn park_current_cpu() -> ! {
+    let hw_id = (MPIDR_EL1.get() & 0xff_ffff) as usize;
+    // The CPU has entered the kernel, so it must have been registered.
+    let cpu_id = topology::cpu_id(hw_id).unwrap();
+    axcpu::asm::disable_irqs();
+
+    PARKED[cpu_id].store(true, Ordering::Release);
+    CPU_STATES.set(cpu_id, CpuState::Off);
+    let stack_top = loop {
+        let stack_top = RELEASE_STACK_TOP[cpu_id].swap(0, Ordering::Acquire);
+        if stack_top != 0 {
+            break stack_top;
+        }
+        aarch64_cpu::asm::wfe();
+    };
+    PARKED[cpu_id].store(false, Ordering::Relaxed);
+
+    // The MMU is already set up, enter the kernel directly.
+    unsafe {
+        core::arch::asm!("
+            mov     sp, {stack_top}
+            b       {entry}",
+            stack_top = in(reg) stack_top,
+            entry = sym axplat::call_secondary_main,
+            in("x0") cpu_id,
+            options(noreturn),
+        )
+    }
 }
@@ -1,4 +1,24 @@
-use axplat::power::PowerIf;
+use axplat::mem::{pa, phys_to_virt};
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
+
+/// Registers of the power management block of BCM2711, which contains the
+/// watchdog.
+mod pm {
+    pub const RSTC: usize = 0x1c;
+    pub const RSTS: usize = 0x20;
+    pub const WDOG: usize = 0x24;
+
+    /// All writes must contain this password in the top byte.
+    pub const PASSWORD: u32 = 0x5a00_0000;
+    pub const RSTC_WRCFG_MASK: u32 = 0x30;
+    pub const RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+    pub const RSTS_HADPOR: u32 = 0x1000;
+    pub const RSTS_HADWRH: u32 = 0x40;
+}
+
+fn pm_reg(offset: usize) -> *mut u32 {
+    phys_to_virt(pa!(crate::config::devices::PM_PADDR + offset)).as_mut_ptr() as *mut u32
+}
 
 struct PowerImpl;
 
@@ -10,8 +30,39 @@ impl PowerIf for PowerImpl {
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
-        crate::mp::start_secondary_cpu(cpu_id, axplat::mem::pa!(stack_top_paddr));
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> axplat::power::CpuBootResult {
+        crate::mp::start_secondary_cpu(cpu_id, axplat::mem::pa!(stack_top_paddr))
+    }
+
+    /// Takes the calling CPU offline.
+    ///
+    /// The CPU waits for events until it is started again by
+    /// [`cpu_boot`](Self::cpu_boot).
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        crate::mp::park_current_cpu()
+    }
+
+    /// Returns the power state of the given CPU.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        crate::mp::CPU_STATES.get(cpu_id)
+    }
+
+    /// Returns the idle states that CPUs can enter.
+    fn idle_states() -> &'static [IdleState] {
+        // No PSCI firmware, only WFI is available.
+        &[IdleState {
+            name: "WFI",
+            entry_latency_ns: 1_000,
+            exit_latency_ns: 1_000,
+            min_residency_ns: 2_000,
+        }]
+    }
+
+    /// Enters the given idle state by WFI.
+    fn cpu_idle(_index: usize) {
+        axcpu::asm::halt();
     }
 
     /// Shutdown the whole system.
@@ -23,8 +74,42 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Resets the whole system by the watchdog.
+    ///
+    /// Only cold reset is supported.
+    fn system_reset(_kind: ResetKind) -> ! {
+        log::info!("Resetting...");
+        unsafe {
+            // Time out after 10 watchdog ticks (about 150 us), then do a full
+            // reset.
+            pm_reg(pm::WDOG).write_volatile(pm::PASSWORD | 10);
+            let rstc = pm_reg(pm::RSTC).read_volatile() & !pm::RSTC_WRCFG_MASK;
+            pm_reg(pm::RSTC).write_volatile(pm::PASSWORD | rstc | pm::RSTC_WRCFG_FULL_RESET);
+        }
+        log::warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns the reason for the last reset from the reset status register.
+    ///
+    /// [`system_reset`](Self::system_reset) is done by the watchdog, so it is
+    /// reported as [`ResetReason::Watchdog`] as well.
+    fn reset_reason() -> Option<ResetReason> {
+        let rsts = unsafe { pm_reg(pm::RSTS).read_volatile() };
+        if rsts & pm::RSTS_HADWRH != 0 {
+            Some(ResetReason::Watchdog)
+        } else if rsts & pm::RSTS_HADPOR != 0 {
+            Some(ResetReason::PowerOn)
+        } else {
+            None
+        }
+    }
+
     /// Get the number of CPU cores available on this platform.
     fn cpu_num() -> usize {
-        crate::config::plat::MAX_CPU_NUM
+        use crate::config::plat::MAX_CPU_NUM;
+        axplat::fdt::cpu_num().map_or(MAX_CPU_NUM, |n| n.min(MAX_CPU_NUM))
     }
 }
@@ -0,0 +1,106 @@
+//! Data cache maintenance by virtual address, to the point of coherency.
+//!
+//! They are used to implement the cache operations of
+//! [`MemIf`](axplat::mem::MemIf) on platforms whose DMA is not coherent.
+
+use core::arch::asm;
+
+use axplat::mem::VirtAddr;
+
+/// Returns the size of the smallest data cache line in bytes, from the
+/// `DminLine` field of the Cache Type Register.
+fn dcache_line_size() -> usize {
+    let ctr: usize;
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("mrs {}, ctr_el0", out(reg) ctr)
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        asm!("mrc p15, 0, {}, c0, c0, 1", out(reg) ctr)
+    };
+    4 << ((ctr >> 16) & 0xf)
+}
+
+/// Calls `op` with the address of each cache line that covers the range, then
+/// waits for the operations to complete.
+fn for_each_line(vaddr: VirtAddr, size: usize, op: impl Fn(usize)) {
+    if size == 0 {
+        return;
+    }
+    let line_size = dcache_line_size();
+    let end = vaddr.as_usize() + size;
+    let mut line = vaddr.as_usize() & !(line_size - 1);
+    while line < end {
+        op(line);
+        line += line_size;
+    }
+    dsb();
+}
+
+fn dsb() {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("dsb sy")
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        asm!("dsb")
+    };
+}
+
+/// Cleans and invalidates a data cache line (`DC CIVAC` or `DCCIMVAC`).
+fn flush_line(line: usize) {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("dc civac, {}", in(reg) line)
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        asm!("mcr p15, 0, {}, c7, c14, 1", in(reg) line)
+    };
+}
+
+/// Cleans the data cache lines that cover the range (`DC CVAC` or
+/// `DCCMVAC`), so that the data is visible to devices.
+pub fn clean_range(vaddr: VirtAddr, size: usize) {
+    for_each_line(vaddr, size, |line| {
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            asm!("dc cvac, {}", in(reg) line)
+        };
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            asm!("mcr p15, 0, {}, c7, c10, 1", in(reg) line)
+        };
+    });
+}
+
+/// Invalidates the data cache lines that cover the range (`DC IVAC` or
+/// `DCIMVAC`), so that the data written by devices is visible to the CPU.
+///
+/// The lines partially covered by the range are cleaned and invalidated
+/// instead, so that the data outside the range is not discarded.
+pub fn invalidate_range(vaddr: VirtAddr, size: usize) {
+    let (start, end) = (vaddr.as_usize(), vaddr.as_usize() + size);
+    let mask = dcache_line_size() - 1;
+    for_each_line(vaddr, size, |line| {
+        if line < start || line + mask >= end {
+            flush_line(line);
+            return;
+        }
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            asm!("dc ivac, {}", in(reg) line)
+        };
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            asm!("mcr p15, 0, {}, c7, c6, 1", in(reg) line)
+        };
+    });
+}
+
+/// Cleans and invalidates the data cache lines that cover the range.
+pub fn flush_range(vaddr: VirtAddr, size: usize) {
+    for_each_line(vaddr, size, flush_line);
+}
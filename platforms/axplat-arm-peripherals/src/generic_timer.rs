@@ -89,6 +89,14 @@ macro_rules! time_if_impl {
                 $crate::pl031::epochoffset_nanos()
             }
 
+            /// Sets the wall time (also known as realtime) in nanoseconds
+            /// since epoch.
+            ///
+            /// It also writes the new time back to the PL031 RTC if present.
+            fn set_wall_time_nanos(nanos: u64) {
+                $crate::pl031::set_wall_time_nanos(nanos)
+            }
+
             /// Set a one-shot timer.
             ///
             /// A timer interrupt will be triggered at the specified monotonic time
@@ -97,6 +105,28 @@ macro_rules! time_if_impl {
             fn set_oneshot_timer(deadline_ns: u64) {
                 $crate::generic_timer::set_oneshot_timer(deadline_ns)
             }
+
+            /// Returns the IRQ number of the RTC alarm, or `None` if the
+            /// platform has no RTC alarm.
+            #[cfg(feature = "irq")]
+            fn rtc_alarm_irq() -> Option<usize> {
+                $crate::pl031::alarm_irq()
+            }
+
+            /// Sets the RTC alarm at the specified wall time (in nanoseconds
+            /// since epoch).
+            ///
+            /// It uses the PL031 RTC, whose resolution is one second.
+            #[cfg(feature = "irq")]
+            fn set_rtc_alarm_nanos(wall_time_nanos: u64) -> bool {
+                $crate::pl031::set_alarm_nanos(wall_time_nanos)
+            }
+
+            /// Cancels the pending RTC alarm, if any.
+            #[cfg(feature = "irq")]
+            fn cancel_rtc_alarm() {
+                $crate::pl031::cancel_alarm()
+            }
         }
     };
 }
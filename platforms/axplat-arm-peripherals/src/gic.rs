@@ -1,19 +1,74 @@
 //! ARM Generic Interrupt Controller (GIC).
 
-use arm_gic_driver::v2::{Ack, Gic, IntId, SGITarget, TargetList, TrapOp, VirtAddr};
-use axplat::irq::{HandlerTable, IpiTarget, IrqHandler};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use arm_gic_driver::v2::{Ack, Gic, IntId, SGITarget, TargetList, TrapOp, Trigger, VirtAddr};
+use axplat::irq::{
+    HandlerTable, HandlerToken, IpiTarget, IrqContextHandler, IrqError, IrqHandler, IrqResult,
+    TriggerMode, stats,
+};
+use axplat::power::topology::MAX_TOPOLOGY_CPUS;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 
 /// The maximum number of IRQs.
 const MAX_IRQ_COUNT: usize = 1024;
 
+/// The first private peripheral interrupt (PPI) ID. IDs below it are SGIs.
+const PPI_START: usize = 16;
+
+/// The first shared peripheral interrupt (SPI) ID. IDs below it are SGIs and
+/// PPIs, which are private to each CPU.
+const SPI_START: usize = 32;
+
+/// The maximum number of CPUs that GICv2 can route interrupts to.
+const MAX_TARGET_CPUS: usize = 8;
+
 static GIC: LazyInit<SpinNoIrq<Gic>> = LazyInit::new();
 
 static TRAP_OP: LazyInit<TrapOp> = LazyInit::new();
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The offset of `GICD_ITARGETSR0`, whose bytes read as the mask of the CPU
+/// interface that performs the read.
+const GICD_ITARGETSR0: usize = 0x800;
+
+/// The value in [`CPU_INTERFACES`] of CPUs whose interfaces are unknown.
+const CPU_INTERFACE_UNKNOWN: u8 = u8::MAX;
+
+/// The virtual address of the GIC distributor.
+static GICD_BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// The GIC CPU interface numbers of CPUs, indexed by the logical CPU IDs.
+static CPU_INTERFACES: [AtomicU8; MAX_TOPOLOGY_CPUS] =
+    [const { AtomicU8::new(CPU_INTERFACE_UNKNOWN) }; MAX_TOPOLOGY_CPUS];
+
+/// Returns the logical ID of the current CPU, translated from its MPIDR.
+fn current_cpu_id() -> Option<usize> {
+    let mpidr: usize;
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("mrs {}, mpidr_el1", out(reg) mpidr)
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("mrc p15, 0, {}, c0, c0, 5", out(reg) mpidr)
+    };
+    axplat::power::topology::cpu_id(mpidr & 0xff_ffff)
+}
+
+/// Returns the GIC CPU interface number of the given logical CPU.
+///
+/// It is the logical ID itself if the CPU has not initialized its interface
+/// by [`init_gicc`].
+fn cpu_interface(cpu_id: usize) -> Option<usize> {
+    match CPU_INTERFACES.get(cpu_id)?.load(Ordering::Relaxed) {
+        CPU_INTERFACE_UNKNOWN => Some(cpu_id).filter(|&id| id < MAX_TARGET_CPUS),
+        interface => Some(interface as usize),
+    }
+}
+
 /// Enables or disables the given IRQ.
 pub fn set_enable(irq_num: usize, enabled: bool) {
     trace!("GIC set enable: {} {}", irq_num, enabled);
@@ -23,26 +78,103 @@ pub fn set_enable(irq_num: usize, enabled: bool) {
 
 /// Registers an IRQ handler for the given IRQ.
 ///
-/// It also enables the IRQ if the registration succeeds. It returns `false`
-/// if the registration failed.
-pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+/// It also enables the IRQ if the registration succeeds. It returns the token
+/// to unregister the handler, or `None` if the registration failed.
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> Option<HandlerToken> {
     trace!("register handler IRQ {}", irq_num);
-    if IRQ_HANDLER_TABLE.register_handler(irq_num, handler) {
-        set_enable(irq_num, true);
-        return true;
+    let token = IRQ_HANDLER_TABLE.register_handler(irq_num, handler);
+    match token {
+        Some(_) => set_enable(irq_num, true),
+        None => warn!("register handler for IRQ {} failed", irq_num),
+    }
+    token
+}
+
+/// Registers an IRQ handler with context for the given IRQ.
+///
+/// Multiple handlers can be registered for a shared IRQ. It also enables the
+/// IRQ if the registration succeeds. It returns the token to unregister the
+/// handler, or `None` if the registration failed.
+pub fn register_handler_with_context(
+    irq_num: usize,
+    handler: IrqContextHandler,
+    ctx: usize,
+) -> Option<HandlerToken> {
+    trace!("register handler IRQ {} with context {:#x}", irq_num, ctx);
+    let token = IRQ_HANDLER_TABLE.register_handler_with_context(irq_num, handler, ctx);
+    match token {
+        Some(_) => set_enable(irq_num, true),
+        None => warn!("register handler for IRQ {} failed", irq_num),
     }
-    warn!("register handler for IRQ {} failed", irq_num);
-    false
+    token
 }
 
-/// Unregisters the IRQ handler for the given IRQ.
+/// Unregisters the IRQ handler registered with the given token for the given
+/// IRQ.
 ///
-/// It also disables the IRQ if the unregistration succeeds. It returns the
-/// existing handler if it is registered, `None` otherwise.
-pub fn unregister_handler(irq_num: usize) -> Option<IrqHandler> {
+/// The IRQ is disabled only if the last handler is unregistered. It returns
+/// `true` if the handler was registered, `false` otherwise.
+pub fn unregister_handler(irq_num: usize, token: HandlerToken) -> bool {
     trace!("unregister handler IRQ {}", irq_num);
-    set_enable(irq_num, false);
-    IRQ_HANDLER_TABLE.unregister_handler(irq_num)
+    if !IRQ_HANDLER_TABLE.unregister_handler(irq_num, token) {
+        return false;
+    }
+    if !IRQ_HANDLER_TABLE.is_registered(irq_num) {
+        set_enable(irq_num, false);
+    }
+    true
+}
+
+/// Routes the given IRQ to the CPUs in `cpu_mask`.
+///
+/// Only SPIs can be routed, and GICv2 supports at most 8 target CPUs.
+pub fn set_affinity(irq_num: usize, cpu_mask: usize) -> IrqResult {
+    trace!("GIC set affinity: {} {:#x}", irq_num, cpu_mask);
+    if irq_num >= MAX_IRQ_COUNT || cpu_mask == 0 || cpu_mask >> MAX_TARGET_CPUS != 0 {
+        return Err(IrqError::InvalidInput);
+    }
+    if irq_num < SPI_START {
+        return Err(IrqError::Unsupported);
+    }
+    let mut targets = 0;
+    for cpu_id in (0..MAX_TARGET_CPUS).filter(|i| cpu_mask & (1 << i) != 0) {
+        targets |= 1 << cpu_interface(cpu_id).ok_or(IrqError::InvalidInput)?;
+    }
+    let intid = unsafe { IntId::raw(irq_num as u32) };
+    let mut cpus = (0..MAX_TARGET_CPUS).filter(|i| targets & (1 << i) != 0);
+    GIC.lock().set_target_cpu(intid, TargetList::new(&mut cpus));
+    Ok(())
+}
+
+/// Sets the priority of the given IRQ, a lower value means a higher priority.
+pub fn set_priority(irq_num: usize, priority: u8) -> IrqResult {
+    trace!("GIC set priority: {} {}", irq_num, priority);
+    if irq_num >= MAX_IRQ_COUNT {
+        return Err(IrqError::InvalidInput);
+    }
+    let intid = unsafe { IntId::raw(irq_num as u32) };
+    GIC.lock().set_priority(intid, priority);
+    Ok(())
+}
+
+/// Sets the trigger mode of the given IRQ.
+///
+/// The trigger mode of SGIs is always edge-triggered and can not be changed.
+pub fn set_trigger(irq_num: usize, mode: TriggerMode) -> IrqResult {
+    trace!("GIC set trigger: {} {:?}", irq_num, mode);
+    if irq_num >= MAX_IRQ_COUNT {
+        return Err(IrqError::InvalidInput);
+    }
+    if irq_num < PPI_START {
+        return Err(IrqError::Unsupported);
+    }
+    let trigger = match mode {
+        TriggerMode::Edge => Trigger::Edge,
+        TriggerMode::Level => Trigger::Level,
+    };
+    let intid = unsafe { IntId::raw(irq_num as u32) };
+    GIC.lock().set_cfg(intid, trigger);
+    Ok(())
 }
 
 /// Handles the IRQ.
@@ -58,20 +190,25 @@ pub fn handle_irq(_unused: usize) {
         Ack::Other(intid) => intid,
         Ack::SGI { intid, cpu_id: _ } => intid,
     };
-    if !IRQ_HANDLER_TABLE.handle(irq_num.to_u32() as _) {
+    if ack.is_special() {
+        stats::record_spurious();
+        return;
+    }
+    let handled = IRQ_HANDLER_TABLE.handle(irq_num.to_u32() as _);
+    stats::record_irq(irq_num.to_u32() as _, handled);
+    if !handled {
         warn!("Unhandled IRQ {:?}", irq_num);
     }
-    if !ack.is_special() {
-        TRAP_OP.eoi(ack);
-        if TRAP_OP.eoi_mode_ns() {
-            TRAP_OP.dir(ack);
-        }
+    TRAP_OP.eoi(ack);
+    if TRAP_OP.eoi_mode_ns() {
+        TRAP_OP.dir(ack);
     }
 }
 
 /// Initializes GIC
 pub fn init_gic(gicd_base: axplat::mem::VirtAddr, gicc_base: axplat::mem::VirtAddr) {
     info!("Initialize GICv2...");
+    GICD_BASE.store(gicd_base.as_usize(), Ordering::Release);
     let gicd_base = VirtAddr::new(gicd_base.into());
     let gicc_base = VirtAddr::new(gicc_base.into());
 
@@ -85,23 +222,37 @@ pub fn init_gic(gicd_base: axplat::mem::VirtAddr, gicc_base: axplat::mem::VirtAd
 
 /// Initializes GICC (for all CPUs).
 ///
-/// It must be called after [`init_gic`].
+/// It also records the CPU interface number of the current CPU, which is
+/// used to target IPIs and IRQs at it. It must be called after [`init_gic`].
 pub fn init_gicc() {
     debug!("Initialize GIC CPU Interface...");
     let mut cpu = GIC.lock().cpu_interface();
     cpu.init_current_cpu();
     cpu.set_eoi_mode_ns(false);
+
+    let itargetsr0 = (GICD_BASE.load(Ordering::Acquire) + GICD_ITARGETSR0) as *const u8;
+    // It reads as zero on uniprocessor implementations.
+    let mask = unsafe { itargetsr0.read_volatile() };
+    let slot = current_cpu_id().and_then(|cpu_id| CPU_INTERFACES.get(cpu_id));
+    if let Some(slot) = slot.filter(|_| mask != 0) {
+        slot.store(mask.trailing_zeros() as u8, Ordering::Relaxed);
+    }
 }
 
 /// Sends an inter-processor interrupt (IPI) to the specified target CPU or all CPUs.
 pub fn send_ipi(irq_num: usize, target: IpiTarget) {
+    stats::record_ipi_sent(irq_num);
     match target {
         IpiTarget::Current { cpu_id: _ } => {
             GIC.lock()
                 .send_sgi(IntId::sgi(irq_num as u32), SGITarget::Current);
         }
         IpiTarget::Other { cpu_id } => {
-            let target_list = TargetList::new(&mut [cpu_id].into_iter());
+            let Some(target) = cpu_interface(cpu_id) else {
+                warn!("send_ipi: no GIC CPU interface for CPU {cpu_id}");
+                return;
+            };
+            let target_list = TargetList::new(&mut [target].into_iter());
             GIC.lock().send_sgi(
                 IntId::sgi(irq_num as u32),
                 SGITarget::TargetList(target_list),
@@ -130,20 +281,25 @@ macro_rules! irq_if_impl {
                 $crate::gic::set_enable(irq, enabled);
             }
 
-            /// Registers an IRQ handler for the given IRQ.
+            /// Registers an IRQ handler with context for the given IRQ.
             ///
-            /// It also enables the IRQ if the registration succeeds. It returns `false`
-            /// if the registration failed.
-            fn register(irq: usize, handler: axplat::irq::IrqHandler) -> bool {
-                $crate::gic::register_handler(irq, handler)
+            /// It also enables the IRQ if the registration succeeds. It returns the
+            /// token to unregister the handler, or `None` if the registration failed.
+            fn register_with_context(
+                irq: usize,
+                handler: axplat::irq::IrqContextHandler,
+                ctx: usize,
+            ) -> Option<axplat::irq::HandlerToken> {
+                $crate::gic::register_handler_with_context(irq, handler, ctx)
             }
 
-            /// Unregisters the IRQ handler for the given IRQ.
+            /// Unregisters the IRQ handler registered with the given token for the
+            /// given IRQ.
             ///
-            /// It also disables the IRQ if the unregistration succeeds. It returns the
-            /// existing handler if it is registered, `None` otherwise.
-            fn unregister(irq: usize) -> Option<axplat::irq::IrqHandler> {
-                $crate::gic::unregister_handler(irq)
+            /// The IRQ is disabled only if the last handler is unregistered. It
+            /// returns `true` if the handler was registered, `false` otherwise.
+            fn unregister(irq: usize, token: axplat::irq::HandlerToken) -> bool {
+                $crate::gic::unregister_handler(irq, token)
             }
 
             /// Handles the IRQ.
@@ -159,6 +315,21 @@ macro_rules! irq_if_impl {
             fn send_ipi(irq_num: usize, target: axplat::irq::IpiTarget) {
                 $crate::gic::send_ipi(irq_num, target);
             }
+
+            /// Routes the given IRQ to the CPUs in `cpu_mask`.
+            fn set_affinity(irq: usize, cpu_mask: usize) -> axplat::irq::IrqResult {
+                $crate::gic::set_affinity(irq, cpu_mask)
+            }
+
+            /// Sets the priority of the given IRQ.
+            fn set_priority(irq: usize, priority: u8) -> axplat::irq::IrqResult {
+                $crate::gic::set_priority(irq, priority)
+            }
+
+            /// Sets the trigger mode (edge or level) of the given IRQ.
+            fn set_trigger(irq: usize, mode: axplat::irq::TriggerMode) -> axplat::irq::IrqResult {
+                $crate::gic::set_trigger(irq, mode)
+            }
         }
     };
 }
@@ -4,6 +4,7 @@
 #[macro_use]
 extern crate log;
 
+pub mod cache;
 pub mod generic_timer;
 
 #[cfg(feature = "irq")]
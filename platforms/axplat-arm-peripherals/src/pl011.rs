@@ -1,5 +1,7 @@
 //! PL011 UART.
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use arm_pl011::Pl011Uart;
 use axplat::mem::VirtAddr;
 use kspin::SpinNoIrq;
@@ -7,6 +9,22 @@ use lazyinit::LazyInit;
 
 static UART: LazyInit<SpinNoIrq<Pl011Uart>> = LazyInit::new();
 
+/// The IRQ number of the UART, or `usize::MAX` if the input is polled.
+#[cfg(feature = "irq")]
+static UART_IRQ: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The base address of the UART, to access the UART without the lock, or the
+/// registers not exposed by [`Pl011Uart`].
+static UART_BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// The interrupt mask set/clear register.
+#[cfg(feature = "irq")]
+const UARTIMSC: usize = 0x38;
+/// The receive timeout interrupt, raised when the receive FIFO is not empty
+/// but below the trigger level for a while.
+#[cfg(feature = "irq")]
+const UARTIMSC_RTIM: u32 = 1 << 6;
+
 fn do_putchar(uart: &mut Pl011Uart, c: u8) {
     match c {
         b'\n' => {
@@ -35,9 +53,24 @@ pub fn write_bytes(bytes: &[u8]) {
     }
 }
 
+/// Writes a slice of bytes to the UART without taking the lock.
+pub fn emergency_write_bytes(bytes: &[u8]) {
+    let mut uart = Pl011Uart::new(UART_BASE.load(Ordering::Acquire) as *mut u8);
+    for c in bytes {
+        do_putchar(&mut uart, *c);
+    }
+}
+
 /// Reads bytes from the console into the given mutable slice.
 /// Returns the number of bytes read.
+///
+/// If the input is interrupt-driven, it reads from the receive buffer instead
+/// of the UART.
 pub fn read_bytes(bytes: &mut [u8]) -> usize {
+    #[cfg(feature = "irq")]
+    if axplat::console::rx::is_enabled() {
+        return axplat::console::rx::read(bytes);
+    }
     let mut read_len = 0;
     while read_len < bytes.len() {
         if let Some(c) = getchar() {
@@ -54,17 +87,52 @@ pub fn read_bytes(bytes: &mut [u8]) -> usize {
 pub fn init_early(uart_base: VirtAddr) {
     UART.init_once(SpinNoIrq::new(Pl011Uart::new(uart_base.as_mut_ptr())));
     UART.lock().init();
+    UART_BASE.store(uart_base.as_usize(), Ordering::Release);
+    axplat::console::set_emergency_write_fn(emergency_write_bytes);
+}
+
+/// Returns the IRQ number of the UART, or `None` if it is not enabled by
+/// [`init_irq`].
+#[cfg(feature = "irq")]
+pub fn irq_num() -> Option<usize> {
+    let irq = UART_IRQ.load(Ordering::Acquire);
+    (irq != usize::MAX).then_some(irq)
 }
 
 /// UART IRQ Handler
+///
+/// It drains the receive FIFO into [`axplat::console::rx`], then notifies the
+/// waiting tasks.
+#[cfg(feature = "irq")]
 pub fn irq_handler() {
-    let is_receive_interrupt = UART.lock().is_receive_interrupt();
-    UART.lock().ack_interrupts();
-    if is_receive_interrupt {
-        while let Some(c) = getchar() {
-            putchar(c);
+    {
+        let mut uart = UART.lock();
+        uart.ack_interrupts();
+        while let Some(c) = uart.getchar() {
+            axplat::console::rx::push(c);
         }
     }
+    axplat::console::rx::notify();
+}
+
+/// Switches the console input to the interrupt-driven mode, by registering
+/// [`irq_handler`] for the given IRQ.
+///
+/// The UART must have been initialized by [`init_early`].
+#[cfg(feature = "irq")]
+pub fn init_irq(irq_num: usize) {
+    {
+        // Only the receive interrupt is enabled by `Pl011Uart::init`, which is
+        // not raised until the FIFO reaches the trigger level. Also enable the
+        // receive timeout interrupt, so that a single byte is not left behind.
+        let _uart = UART.lock();
+        let imsc = (UART_BASE.load(Ordering::Acquire) + UARTIMSC) as *mut u32;
+        unsafe { imsc.write_volatile(imsc.read_volatile() | UARTIMSC_RTIM) };
+    }
+    if axplat::irq::register(irq_num, irq_handler).is_some() {
+        UART_IRQ.store(irq_num, Ordering::Release);
+        axplat::console::rx::enable();
+    }
 }
 
 /// Default implementation of [`axplat::console::ConsoleIf`] using the
@@ -87,6 +155,13 @@ macro_rules! console_if_impl {
             fn read_bytes(bytes: &mut [u8]) -> usize {
                 $crate::pl011::read_bytes(bytes)
             }
+
+            /// Returns the IRQ number of the console input, or `None` if the
+            /// input is polled only.
+            #[cfg(feature = "irq")]
+            fn irq_num() -> Option<usize> {
+                $crate::pl011::irq_num()
+            }
         }
     };
 }
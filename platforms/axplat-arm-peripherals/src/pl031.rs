@@ -1,17 +1,108 @@
 //! PL031 Real Time Clock (RTC) driver.
 
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
 use arm_pl031::Rtc;
 use axplat::mem::VirtAddr;
 
 use crate::generic_timer::{current_ticks, ticks_to_nanos};
 
 /// RTC wall time offset in nanoseconds at monotonic time base.
-static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+static RTC_EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// The base address of the RTC, or `0` if not present.
+static RTC_BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// The IRQ number of the RTC alarm, or `usize::MAX` if not enabled.
+#[cfg(feature = "irq")]
+static RTC_IRQ: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn rtc() -> Option<Rtc> {
+    let rtc_base = RTC_BASE.load(Ordering::Acquire);
+    (rtc_base != 0).then(|| unsafe { Rtc::new(rtc_base as _) })
+}
 
 /// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
 #[inline]
 pub fn epochoffset_nanos() -> u64 {
-    unsafe { RTC_EPOCHOFFSET_NANOS }
+    RTC_EPOCHOFFSET_NANOS.load(Ordering::Acquire)
+}
+
+/// Sets the wall time in nanoseconds since epoch.
+///
+/// It updates the epoch offset, and writes the new time (in seconds) back to
+/// the RTC if it is present.
+pub fn set_wall_time_nanos(nanos: u64) {
+    let monotonic_nanos = ticks_to_nanos(current_ticks());
+    RTC_EPOCHOFFSET_NANOS.store(nanos.saturating_sub(monotonic_nanos), Ordering::Release);
+
+    if let Some(mut rtc) = rtc() {
+        rtc.set_unix_timestamp((nanos / axplat::time::NANOS_PER_SEC) as u32);
+    }
+}
+
+/// Returns the IRQ number of the RTC alarm, or `None` if it is not enabled
+/// by [`init_irq`].
+#[cfg(feature = "irq")]
+pub fn alarm_irq() -> Option<usize> {
+    let irq = RTC_IRQ.load(Ordering::Acquire);
+    (irq != usize::MAX).then_some(irq)
+}
+
+/// Sets the RTC alarm at the given wall time in nanoseconds since epoch.
+///
+/// The wall time is rounded up to seconds. It returns `false` if the RTC or
+/// its alarm IRQ is not present, or the wall time does not fit in the 32-bit
+/// match register.
+#[cfg(feature = "irq")]
+pub fn set_alarm_nanos(wall_time_nanos: u64) -> bool {
+    let Some(mut rtc) = rtc().filter(|_| alarm_irq().is_some()) else {
+        return false;
+    };
+    let Ok(secs) = u32::try_from(wall_time_nanos.div_ceil(axplat::time::NANOS_PER_SEC)) else {
+        return false;
+    };
+    rtc.clear_interrupt();
+    rtc.set_match_timestamp(secs);
+    rtc.enable_interrupt(true);
+    true
+}
+
+/// Cancels the pending RTC alarm.
+#[cfg(feature = "irq")]
+pub fn cancel_alarm() {
+    if let Some(mut rtc) = rtc() {
+        rtc.enable_interrupt(false);
+        rtc.clear_interrupt();
+    }
+}
+
+/// Acknowledges the RTC alarm interrupt.
+#[cfg(feature = "irq")]
+fn ack_alarm(_irq: usize, _ctx: usize) -> bool {
+    let Some(mut rtc) = rtc() else {
+        return false;
+    };
+    if !rtc.interrupt_pending() {
+        return false;
+    }
+    rtc.clear_interrupt();
+    true
+}
+
+/// Enables the RTC alarm with the given IRQ number.
+///
+/// It registers a handler to acknowledge the alarm interrupts, so it must be
+/// called after the interrupt controller is initialized, and after
+/// [`init_early`].
+#[cfg(feature = "irq")]
+pub fn init_irq(irq_num: usize) {
+    if rtc().is_none() {
+        return;
+    }
+    if axplat::irq::register_with_context(irq_num, ack_alarm, 0).is_some() {
+        RTC_IRQ.store(irq_num, Ordering::Release);
+    }
 }
 
 /// Early stage initialization of the RTC driver.
@@ -24,12 +115,14 @@ pub fn init_early(rtc_base: VirtAddr) {
     }
 
     let rtc = unsafe { Rtc::new(rtc_base.as_mut_ptr() as _) };
+    RTC_BASE.store(rtc_base.as_usize(), Ordering::Release);
 
     // Get the current time in microseconds since the epoch (1970-01-01) from the aarch64 pl031 RTC.
     // Subtract the timer ticks to get the actual time when ArceOS was booted.
     let epoch_time_nanos = rtc.get_unix_timestamp() as u64 * 1_000_000_000;
 
-    unsafe {
-        RTC_EPOCHOFFSET_NANOS = epoch_time_nanos - ticks_to_nanos(current_ticks());
-    }
+    RTC_EPOCHOFFSET_NANOS.store(
+        epoch_time_nanos - ticks_to_nanos(current_ticks()),
+        Ordering::Release,
+    );
 }
@@ -5,17 +5,24 @@
 use core::arch::asm;
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use axplat::power::{CpuBootError, CpuBootResult, CpuState, IdleState, ResetKind};
+
 const PSCI_0_2_FN_BASE: u32 = 0x84000000;
 const PSCI_0_2_64BIT: u32 = 0x40000000;
 const PSCI_0_2_FN_CPU_SUSPEND: u32 = PSCI_0_2_FN_BASE + 1;
 const PSCI_0_2_FN_CPU_OFF: u32 = PSCI_0_2_FN_BASE + 2;
 const PSCI_0_2_FN_CPU_ON: u32 = PSCI_0_2_FN_BASE + 3;
+const PSCI_0_2_FN_AFFINITY_INFO: u32 = PSCI_0_2_FN_BASE + 4;
 const PSCI_0_2_FN_MIGRATE: u32 = PSCI_0_2_FN_BASE + 5;
 const PSCI_0_2_FN_SYSTEM_OFF: u32 = PSCI_0_2_FN_BASE + 8;
 const PSCI_0_2_FN_SYSTEM_RESET: u32 = PSCI_0_2_FN_BASE + 9;
+const PSCI_1_0_FN_PSCI_FEATURES: u32 = PSCI_0_2_FN_BASE + 0xa;
+const PSCI_1_1_FN_SYSTEM_RESET2: u32 = PSCI_0_2_FN_BASE + 0x12;
 const PSCI_0_2_FN64_CPU_SUSPEND: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 1;
 const PSCI_0_2_FN64_CPU_ON: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 3;
+const PSCI_0_2_FN64_AFFINITY_INFO: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 4;
 const PSCI_0_2_FN64_MIGRATE: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 5;
+const PSCI_1_1_FN64_SYSTEM_RESET2: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 0x12;
 
 static PSCI_METHOD_HVC: AtomicBool = AtomicBool::new(false);
 
@@ -107,12 +114,17 @@ fn psci_hvc_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
-fn psci_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> Result<(), PsciError> {
-    let ret = if PSCI_METHOD_HVC.load(Ordering::Acquire) {
+/// Calls a PSCI function and returns the raw result.
+fn psci_call_raw(func: u32, arg0: usize, arg1: usize, arg2: usize) -> usize {
+    if PSCI_METHOD_HVC.load(Ordering::Acquire) {
         psci_hvc_call(func, arg0, arg1, arg2)
     } else {
         arm_smccc_smc(func, arg0, arg1, arg2)
-    };
+    }
+}
+
+fn psci_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> Result<(), PsciError> {
+    let ret = psci_call_raw(func, arg0, arg1, arg2);
     if ret == 0 {
         Ok(())
     } else {
@@ -141,6 +153,88 @@ pub fn system_off() -> ! {
     }
 }
 
+/// Reset the whole system, including all CPUs.
+///
+/// A warm reset is done by `SYSTEM_RESET2` (since PSCI 1.1) if the firmware
+/// supports it, otherwise it falls back to a cold reset by `SYSTEM_RESET`.
+pub fn system_reset(kind: ResetKind) -> ! {
+    info!("Resetting ({:?})...", kind);
+    if kind == ResetKind::Warm {
+        const PSCI_1_1_RESET_TYPE_SYSTEM_WARM_RESET: usize = 0;
+        let fn_num = if cfg!(target_pointer_width = "64") {
+            PSCI_1_1_FN64_SYSTEM_RESET2
+        } else {
+            PSCI_1_1_FN_SYSTEM_RESET2
+        };
+        if psci_call(PSCI_1_0_FN_PSCI_FEATURES, fn_num as usize, 0, 0).is_ok() {
+            psci_call(fn_num, PSCI_1_1_RESET_TYPE_SYSTEM_WARM_RESET, 0, 0).ok();
+        }
+        warn!("Warm reset is not supported, falling back to cold reset");
+    }
+    psci_call(PSCI_0_2_FN_SYSTEM_RESET, 0, 0, 0).ok();
+    warn!("It should reset!");
+    loop {
+        axcpu::asm::halt();
+    }
+}
+
+/// Idle states entered by [`cpu_idle`], which are available on all PSCI
+/// implementations.
+pub const IDLE_STATES: &[IdleState] = &[
+    IdleState {
+        name: "WFI",
+        entry_latency_ns: 1_000,
+        exit_latency_ns: 1_000,
+        min_residency_ns: 2_000,
+    },
+    IdleState {
+        name: "PSCI standby",
+        entry_latency_ns: 10_000,
+        exit_latency_ns: 10_000,
+        min_residency_ns: 100_000,
+    },
+];
+
+/// Enter the idle state `index` of [`IDLE_STATES`] on the calling core.
+///
+/// It returns after the core is woken up by an interrupt, even if interrupts
+/// are disabled.
+pub fn cpu_idle(index: usize) {
+    // The standby state of the core (power level 0), with the state ID 0.
+    const PSCI_POWER_STATE_STANDBY: u32 = 0;
+    // Do not try the standby state again if the firmware does not support it.
+    static STANDBY_FAILED: AtomicBool = AtomicBool::new(false);
+
+    if index == 1 && !STANDBY_FAILED.load(Ordering::Relaxed) {
+        if cpu_suspend_standby(PSCI_POWER_STATE_STANDBY) {
+            return;
+        }
+        STANDBY_FAILED.store(true, Ordering::Relaxed);
+    }
+    axcpu::asm::halt();
+}
+
+/// Suspend the calling core to a standby state by `CPU_SUSPEND`.
+///
+/// `power_state` is the platform specific parameter of the state, whose
+/// state type must be standby. The core returns from the call when it is
+/// woken up. Returns `false` if the firmware fails to enter the state.
+pub fn cpu_suspend_standby(power_state: u32) -> bool {
+    let fn_num = if cfg!(target_pointer_width = "64") {
+        PSCI_0_2_FN64_CPU_SUSPEND
+    } else {
+        PSCI_0_2_FN_CPU_SUSPEND
+    };
+    // The entry point and context ID are not used for standby states.
+    match psci_call(fn_num, power_state as usize, 0, 0) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("failed to suspend the CPU ({:?})", e);
+            false
+        }
+    }
+}
+
 /// Power up a core. This call is used to power up cores that either:
 ///
 /// * Have not yet been booted into the calling supervisory software.
@@ -149,26 +243,63 @@ pub fn system_off() -> ! {
 /// `target_cpu` contains a copy of the affinity fields of the MPIDR register.
 /// `entry_point` is the physical address of the secondary CPU's entry point.
 /// `arg` will be passed to the `X0` register of the secondary CPU.
-pub fn cpu_on(target_cpu: usize, entry_point: usize, arg: usize) {
+pub fn cpu_on(target_cpu: usize, entry_point: usize, arg: usize) -> CpuBootResult {
     info!("Starting CPU {:x} ON ...", target_cpu);
     let fn_num = if cfg!(target_pointer_width = "64") {
         PSCI_0_2_FN64_CPU_ON
     } else {
         PSCI_0_2_FN_CPU_ON
     };
-    let res = psci_call(fn_num, target_cpu, entry_point, arg);
-    if let Err(e) = res {
+    psci_call(fn_num, target_cpu, entry_point, arg).map_err(|e| {
         error!("failed to boot CPU {:x} ({:?})", target_cpu, e);
-    }
+        match e {
+            PsciError::AlreadyOn | PsciError::OnPending => CpuBootError::AlreadyOn,
+            PsciError::InvalidParams | PsciError::NotPresent => CpuBootError::NotPresent,
+            _ => CpuBootError::Denied,
+        }
+    })
 }
 
 /// Power down the calling core. This call is intended for use in hotplug. A
 /// core that is powered down by `cpu_off` can only be powered up again in
 /// response to a `cpu_on`.
-pub fn cpu_off() {
+pub fn cpu_off() -> ! {
     const PSCI_POWER_STATE_TYPE_STANDBY: u32 = 0;
     const PSCI_POWER_STATE_TYPE_POWER_DOWN: u32 = 1;
     const PSCI_0_2_POWER_STATE_TYPE_SHIFT: u32 = 16;
     let state: u32 = PSCI_POWER_STATE_TYPE_POWER_DOWN << PSCI_0_2_POWER_STATE_TYPE_SHIFT;
-    psci_call(PSCI_0_2_FN_CPU_OFF, state as usize, 0, 0).ok();
+    // It does not return on success.
+    if let Err(e) = psci_call(PSCI_0_2_FN_CPU_OFF, state as usize, 0, 0) {
+        error!("failed to power down the CPU ({:?})", e);
+    }
+    loop {
+        axcpu::asm::halt();
+    }
+}
+
+/// Get the power state of a core.
+///
+/// `target_cpu` contains a copy of the affinity fields of the MPIDR register.
+/// Returns `None` if the firmware fails to get it.
+pub fn affinity_info(target_cpu: usize) -> Option<CpuState> {
+    let fn_num = if cfg!(target_pointer_width = "64") {
+        PSCI_0_2_FN64_AFFINITY_INFO
+    } else {
+        PSCI_0_2_FN_AFFINITY_INFO
+    };
+    // Query the affinity level 0, i.e., the core itself.
+    match psci_call_raw(fn_num, target_cpu, 0, 0) as i32 {
+        0 => Some(CpuState::On),
+        1 => Some(CpuState::Off),
+        2 => Some(CpuState::OnPending),
+        code if code < 0 => {
+            warn!(
+                "failed to get the state of CPU {:x} ({:?})",
+                target_cpu,
+                PsciError::from(code)
+            );
+            None
+        }
+        _ => None,
+    }
 }
@@ -170,7 +170,7 @@ pub unsafe extern "C" fn _start() -> ! {
 
         bl {init_page_tables_after_mmu}
 
-        mov r0, r11 // cpu_id
+        mov r0, r11 // hw_id
         mov r1, r10 // dtb
         ldr r3, = {rust_main}
         bx r3
@@ -179,7 +179,7 @@ pub unsafe extern "C" fn _start() -> ! {
         BOOT_PT = sym BOOT_PT,
         BOOT_STACK = sym BOOT_STACK,
         BOOT_STACK_SIZE = const BOOT_STACK_SIZE,
-        rust_main = sym axplat::call_main,
+        rust_main = sym axplat::call_main_hw,
         init_page_tables = sym init_page_tables,
         init_mmu = sym axcpu::init::init_mmu,
         init_page_tables_after_mmu = sym init_page_tables_after_mmu,
@@ -228,7 +228,7 @@ pub unsafe extern "C" fn _start_secondary() -> ! {
         PHYS_VIRT_OFFSET = const PHYS_VIRT_OFFSET,
         BOOT_PT = sym BOOT_PT,
         init_mmu = sym axcpu::init::init_mmu,
-        entry = sym axplat::call_secondary_main,
+        entry = sym axplat::call_secondary_main_hw,
         enable_fp = sym enable_fp,
     )
 }
@@ -1,6 +1,7 @@
 use axplat::mem::{pa, phys_to_virt};
+use axplat::power::topology::{self, CpuTopology};
 
-use crate::config::plat::PSCI_METHOD;
+use crate::config::plat::{MAX_CPU_NUM, PSCI_METHOD};
 
 #[cfg(feature = "irq")]
 const TIMER_IRQ: usize = crate::config::devices::TIMER_IRQ;
@@ -14,8 +15,16 @@ impl axplat::init::InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _dtb: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
         axcpu::init::init_trap();
+        // Fall back to the static configuration if no valid DTB is present.
+        unsafe { axplat::fdt::init(dtb) }.ok();
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init_from_fdt();
+        match axplat::fdt::cpus() {
+            Some(cpus) => topology::register_all(cpus.iter().copied()),
+            None => topology::register_all((0..MAX_CPU_NUM).map(CpuTopology::flat)),
+        };
         axplat_arm_peripherals::pl011::init_early(phys_to_virt(pa!(
             crate::config::devices::UART_PADDR
         )));
@@ -27,6 +36,7 @@ impl axplat::init::InitIf for InitIfImpl {
         axplat_arm_peripherals::pl031::init_early(phys_to_virt(pa!(
             crate::config::devices::RTC_PADDR
         )));
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -49,6 +59,13 @@ impl axplat::init::InitIf for InitIfImpl {
             );
             axplat_arm_peripherals::gic::init_gicc();
             axplat_arm_peripherals::generic_timer::enable_irqs(TIMER_IRQ);
+
+            // enable UART IRQs
+            #[cfg(feature = "console-irq")]
+            axplat_arm_peripherals::pl011::init_irq(crate::config::devices::UART_IRQ);
+
+            #[cfg(feature = "rtc")]
+            axplat_arm_peripherals::pl031::init_irq(crate::config::devices::RTC_IRQ);
         }
     }
 
@@ -0,0 +1,72 @@
+use core::ffi::{c_char, c_int};
+
+/// Entry point of the process.
+///
+/// Sets up the emulated physical memory and jumps to the kernel's main
+/// function on the primary CPU (the main thread). `arg` is always 0.
+///
+/// It is not exported in unit tests, where the test harness provides `main`.
+#[cfg_attr(not(test), unsafe(no_mangle))]
+#[cfg_attr(test, allow(dead_code))]
+extern "C" fn main(_argc: c_int, _argv: *const *const c_char) -> c_int {
+    crate::mem::init_early();
+    axplat::call_main(0, 0)
+}
+
+/// The power states of the emulated CPUs.
+#[cfg(feature = "smp")]
+pub(crate) static CPU_STATES: axplat::power::CpuStateTable<{ crate::config::plat::MAX_CPU_NUM }> =
+    axplat::power::CpuStateTable::new();
+
+std::thread_local! {
+    /// The ID of the CPU emulated by the current host thread.
+    static CURRENT_CPU_ID: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+/// Returns the ID of the CPU emulated by the current host thread.
+///
+/// The kernel can pass it to [`axplat::power::set_this_cpu_id_fn`], as there
+/// is no per-CPU register to keep the CPU ID in a user-space process.
+pub fn this_cpu_id() -> usize {
+    CURRENT_CPU_ID.get()
+}
+
+/// Starts a host thread that emulates the given secondary CPU.
+///
+/// The CPU must be off, i.e., it has not been started, or has been taken
+/// offline by [`stop_current_cpu`].
+#[cfg(feature = "smp")]
+pub(crate) fn start_secondary_cpu(cpu_id: usize) -> axplat::power::CpuBootResult {
+    use crate::config::plat::BOOT_STACK_SIZE;
+    use axplat::power::{CpuBootError, CpuState};
+
+    CPU_STATES.start_boot(cpu_id)?;
+    let res = std::thread::Builder::new()
+        .name(format!("cpu{cpu_id}"))
+        .stack_size(BOOT_STACK_SIZE)
+        .spawn(move || {
+            CURRENT_CPU_ID.set(cpu_id);
+            axplat::call_secondary_main(cpu_id)
+        });
+    if let Err(e) = res {
+        warn!("failed to start CPU {cpu_id}: {e}");
+        CPU_STATES.set(cpu_id, CpuState::Off);
+        return Err(CpuBootError::Denied);
+    }
+    Ok(())
+}
+
+/// Takes the CPU emulated by the current host thread offline.
+///
+/// The host thread is blocked forever, and [`start_secondary_cpu`] starts a
+/// new one when the CPU is brought back.
+#[cfg(feature = "smp")]
+pub(crate) fn stop_current_cpu() -> ! {
+    let cpu_id = CURRENT_CPU_ID.get();
+    #[cfg(feature = "irq")]
+    crate::irq::stop_percpu(cpu_id);
+    CPU_STATES.set(cpu_id, axplat::power::CpuState::Off);
+    loop {
+        std::thread::park();
+    }
+}
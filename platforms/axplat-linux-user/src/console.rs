@@ -0,0 +1,51 @@
+use std::io::{Error, ErrorKind};
+
+use axplat::console::ConsoleIf;
+
+struct ConsoleIfImpl;
+
+#[impl_interface]
+impl ConsoleIf for ConsoleIfImpl {
+    /// Writes bytes to the console from input u8 slice.
+    fn write_bytes(bytes: &[u8]) {
+        // Use `write(2)` directly rather than `std::io::stdout()`, which takes
+        // a lock and may deadlock when printing from a signal handler.
+        let mut written = 0;
+        while written < bytes.len() {
+            let rest = &bytes[written..];
+            let ret = unsafe { libc::write(libc::STDOUT_FILENO, rest.as_ptr().cast(), rest.len()) };
+            if ret < 0 {
+                if Error::last_os_error().kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+            written += ret as usize;
+        }
+    }
+
+    /// Reads bytes from the console into the given mutable slice.
+    /// Returns the number of bytes read.
+    fn read_bytes(bytes: &mut [u8]) -> usize {
+        // Do not block if there is no pending input.
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, 0) } <= 0 || fd.revents & libc::POLLIN == 0 {
+            return 0;
+        }
+        let ret = unsafe { libc::read(libc::STDIN_FILENO, bytes.as_mut_ptr().cast(), bytes.len()) };
+        ret.max(0) as usize
+    }
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    ///
+    /// The standard input is always polled.
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize> {
+        None
+    }
+}
@@ -0,0 +1,13 @@
+use axplat::devices::{Device, DeviceIf};
+
+struct DeviceIfImpl;
+
+#[impl_interface]
+impl DeviceIf for DeviceIfImpl {
+    /// Returns the devices on the platform that drivers may use.
+    ///
+    /// There is no device in a user-space process.
+    fn devices() -> &'static [Device] {
+        &[]
+    }
+}
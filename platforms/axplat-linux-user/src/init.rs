@@ -0,0 +1,42 @@
+use axplat::init::InitIf;
+
+struct InitIfImpl;
+
+#[impl_interface]
+impl InitIf for InitIfImpl {
+    /// This function should be called immediately after the kernel has booted,
+    /// and performed earliest platform configuration and initialization (e.g.,
+    /// early console, clocking).
+    fn init_early(_cpu_id: usize, _arg: usize) {
+        #[cfg(feature = "smp")]
+        crate::boot::CPU_STATES.set(_cpu_id, axplat::power::CpuState::On);
+        crate::time::init_early();
+        #[cfg(feature = "irq")]
+        crate::irq::init_early(_cpu_id);
+    }
+
+    /// Initializes the platform at the early stage for secondary cores.
+    #[cfg(feature = "smp")]
+    fn init_early_secondary(cpu_id: usize) {
+        crate::boot::CPU_STATES.set(cpu_id, axplat::power::CpuState::On);
+        #[cfg(feature = "irq")]
+        crate::irq::init_early_secondary(cpu_id);
+    }
+
+    /// Initializes the platform at the later stage for the primary core.
+    ///
+    /// This function should be called after the kernel has done part of its
+    /// initialization (e.g, logging, memory management), and finalized the rest of
+    /// platform configuration and initialization.
+    fn init_later(_cpu_id: usize, _arg: usize) {
+        #[cfg(feature = "irq")]
+        crate::time::init_percpu();
+    }
+
+    /// Initializes the platform at the later stage for secondary cores.
+    #[cfg(feature = "smp")]
+    fn init_later_secondary(_cpu_id: usize) {
+        #[cfg(feature = "irq")]
+        crate::time::init_percpu();
+    }
+}
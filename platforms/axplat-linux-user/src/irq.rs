@@ -0,0 +1,310 @@
+//! IRQs emulated with signals, where the IRQ number is the signal number.
+
+use core::ffi::c_int;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use axplat::irq::{
+    HandlerTable, HandlerToken, IpiTarget, IrqContextHandler, IrqError, IrqIf, IrqResult,
+    TriggerMode, stats,
+};
+
+use crate::config::devices::{IPI_IRQ, TIMER_IRQ};
+use crate::config::plat::MAX_CPU_NUM;
+
+/// The maximum number of IRQs.
+const MAX_IRQ_COUNT: usize = 64;
+
+/// Signals that can not be used as IRQs: synchronous faults, and signals
+/// used to terminate the process.
+const NON_IRQ_SIGNALS: [c_int; 12] = [
+    libc::SIGILL,
+    libc::SIGTRAP,
+    libc::SIGABRT,
+    libc::SIGBUS,
+    libc::SIGFPE,
+    libc::SIGSEGV,
+    libc::SIGSYS,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGKILL,
+    libc::SIGSTOP,
+];
+
+static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
+
+/// Bitmap of enabled IRQs.
+static IRQ_ENABLED: AtomicU64 = AtomicU64::new(0);
+
+/// Bitmaps of IRQs that arrived while disabled, or were not handled by any
+/// handler, since they were last reported by [`report_dropped_irqs`].
+///
+/// The logger formats and takes locks, which is not async-signal-safe, so the
+/// signal handler only records them here.
+static DISABLED_IRQS: AtomicU64 = AtomicU64::new(0);
+static UNHANDLED_IRQS: AtomicU64 = AtomicU64::new(0);
+
+/// The host thread (`pthread_t`) of each CPU, used to deliver IPIs.
+static CPU_THREADS: [AtomicUsize; MAX_CPU_NUM] = [const { AtomicUsize::new(0) }; MAX_CPU_NUM];
+
+fn is_irq_signal(irq: usize) -> bool {
+    (1..MAX_IRQ_COUNT).contains(&irq) && !NON_IRQ_SIGNALS.contains(&(irq as c_int))
+}
+
+fn irq_sigset() -> libc::sigset_t {
+    let mut set: libc::sigset_t = unsafe { core::mem::zeroed() };
+    unsafe { libc::sigemptyset(&mut set) };
+    for irq in (1..MAX_IRQ_COUNT).filter(|&irq| is_irq_signal(irq)) {
+        unsafe { libc::sigaddset(&mut set, irq as c_int) };
+    }
+    set
+}
+
+/// Blocks or unblocks the IRQ signals of the current thread, and returns
+/// whether they were blocked before.
+fn set_irqs_masked(masked: bool) -> bool {
+    let how = if masked {
+        libc::SIG_BLOCK
+    } else {
+        libc::SIG_UNBLOCK
+    };
+    let mut old: libc::sigset_t = unsafe { core::mem::zeroed() };
+    unsafe { libc::pthread_sigmask(how, &irq_sigset(), &mut old) };
+    unsafe { libc::sigismember(&old, TIMER_IRQ as c_int) == 1 }
+}
+
+/// Logs the IRQs recorded by the signal handler in [`DISABLED_IRQS`] and
+/// [`UNHANDLED_IRQS`].
+///
+/// It must be called outside of the signal handler.
+fn report_dropped_irqs() {
+    let disabled = DISABLED_IRQS.swap(0, Ordering::AcqRel);
+    let unhandled = UNHANDLED_IRQS.swap(0, Ordering::AcqRel);
+    for irq in 1..MAX_IRQ_COUNT {
+        if disabled & (1 << irq) != 0 {
+            trace!("IRQ {irq} is disabled");
+        }
+        if unhandled & (1 << irq) != 0 {
+            warn!("Unhandled IRQ {irq}");
+        }
+    }
+}
+
+/// Allows IRQs to be delivered to the current CPU.
+///
+/// It is the counterpart of unmasking interrupts on a real CPU.
+pub fn enable_irqs() {
+    report_dropped_irqs();
+    set_irqs_masked(false);
+}
+
+/// Blocks IRQs on the current CPU.
+///
+/// Blocked IRQs stay pending and will be handled after [`enable_irqs`].
+pub fn disable_irqs() {
+    set_irqs_masked(true);
+}
+
+/// Returns whether IRQs are allowed to be delivered to the current CPU.
+pub fn irqs_enabled() -> bool {
+    let mut set: libc::sigset_t = unsafe { core::mem::zeroed() };
+    unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, core::ptr::null(), &mut set) };
+    unsafe { libc::sigismember(&set, TIMER_IRQ as c_int) == 0 }
+}
+
+/// Waits until an IRQ is delivered to the current CPU.
+///
+/// IRQs are allowed during waiting, so the IRQ is handled before returning.
+pub(crate) fn wait_for_irq() {
+    let mut set: libc::sigset_t = unsafe { core::mem::zeroed() };
+    unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, core::ptr::null(), &mut set) };
+    for irq in (1..MAX_IRQ_COUNT).filter(|&irq| is_irq_signal(irq)) {
+        unsafe { libc::sigdelset(&mut set, irq as c_int) };
+    }
+    // It always returns after a signal handler is called.
+    unsafe { libc::sigsuspend(&set) };
+    report_dropped_irqs();
+}
+
+extern "C" fn irq_signal_handler(sig: c_int) {
+    // Do not let the handlers clobber `errno` of the interrupted code.
+    let errno = unsafe { *libc::__errno_location() };
+    axplat::irq::handle(sig as usize);
+    unsafe { *libc::__errno_location() = errno };
+}
+
+fn install_signal_handler(irq: usize) -> bool {
+    let mut act: libc::sigaction = unsafe { core::mem::zeroed() };
+    act.sa_sigaction = irq_signal_handler as *const () as libc::sighandler_t;
+    act.sa_flags = libc::SA_RESTART;
+    // Other IRQs are masked while handling an IRQ.
+    act.sa_mask = irq_sigset();
+    unsafe { libc::sigaction(irq as c_int, &act, core::ptr::null_mut()) == 0 }
+}
+
+fn init_percpu(cpu_id: usize) {
+    disable_irqs();
+    CPU_THREADS[cpu_id].store(unsafe { libc::pthread_self() } as usize, Ordering::Release);
+}
+
+pub(crate) fn init_early(cpu_id: usize) {
+    // `SpinNoIrq` does not block signals, let the locks shared with the
+    // handlers (e.g., the timer queues) block them instead.
+    axplat::irq::set_irq_mask_fn(set_irqs_masked);
+    // Install the handlers in advance so that an unexpected timer IRQ or IPI
+    // does not terminate the process.
+    for irq in [TIMER_IRQ, IPI_IRQ] {
+        if !install_signal_handler(irq) {
+            panic!("failed to install the handler for IRQ {irq}");
+        }
+    }
+    init_percpu(cpu_id);
+}
+
+#[cfg(feature = "smp")]
+pub(crate) fn init_early_secondary(cpu_id: usize) {
+    init_percpu(cpu_id);
+}
+
+/// Stops delivering IRQs to the current CPU, which is going offline.
+#[cfg(feature = "smp")]
+pub(crate) fn stop_percpu(cpu_id: usize) {
+    disable_irqs();
+    CPU_THREADS[cpu_id].store(0, Ordering::Release);
+}
+
+struct IrqIfImpl;
+
+#[impl_interface]
+impl IrqIf for IrqIfImpl {
+    /// Enables or disables the given IRQ.
+    fn set_enable(irq: usize, enabled: bool) {
+        if !is_irq_signal(irq) {
+            warn!("invalid IRQ {irq}");
+            return;
+        }
+        if enabled {
+            IRQ_ENABLED.fetch_or(1 << irq, Ordering::AcqRel);
+        } else {
+            IRQ_ENABLED.fetch_and(!(1 << irq), Ordering::AcqRel);
+        }
+    }
+
+    /// Registers an IRQ handler with context for the given IRQ.
+    ///
+    /// It also enables the IRQ if the registration succeeds. It returns the
+    /// token to unregister the handler, or `None` if the registration failed.
+    ///
+    /// The `irq` is the number of the signal that triggers the IRQ.
+    fn register_with_context(
+        irq: usize,
+        handler: IrqContextHandler,
+        ctx: usize,
+    ) -> Option<HandlerToken> {
+        if !is_irq_signal(irq) {
+            warn!("signal {irq} can not be used as an IRQ");
+            return None;
+        }
+        let Some(token) = IRQ_HANDLER_TABLE.register_handler_with_context(irq, handler, ctx) else {
+            warn!("register handler for IRQ {irq} failed");
+            return None;
+        };
+        if !install_signal_handler(irq) {
+            warn!("failed to install the handler for IRQ {irq}");
+            IRQ_HANDLER_TABLE.unregister_handler(irq, token);
+            return None;
+        }
+        Self::set_enable(irq, true);
+        Some(token)
+    }
+
+    /// Unregisters the IRQ handler registered with the given token for the
+    /// given IRQ.
+    ///
+    /// The IRQ is disabled only if the last handler is unregistered. It
+    /// returns `true` if the handler was registered, `false` otherwise.
+    fn unregister(irq: usize, token: HandlerToken) -> bool {
+        if !is_irq_signal(irq) || !IRQ_HANDLER_TABLE.unregister_handler(irq, token) {
+            return false;
+        }
+        if !IRQ_HANDLER_TABLE.is_registered(irq) {
+            Self::set_enable(irq, false);
+        }
+        true
+    }
+
+    /// Handles the IRQ.
+    ///
+    /// It is called by the signal handler. It looks up in the IRQ handler
+    /// table and calls the corresponding handler.
+    ///
+    /// Nothing is logged here, as the logger is not async-signal-safe. IRQs
+    /// that are disabled or not handled are logged later, when IRQs are
+    /// enabled again or [`cpu_idle`](axplat::power::PowerIf::cpu_idle)
+    /// returns.
+    fn handle(irq: usize) {
+        if IRQ_ENABLED.load(Ordering::Acquire) & (1 << irq) == 0 {
+            DISABLED_IRQS.fetch_or(1 << irq, Ordering::AcqRel);
+            stats::record_spurious();
+            return;
+        }
+        let handled = IRQ_HANDLER_TABLE.handle(irq);
+        stats::record_irq(irq, handled);
+        if !handled {
+            UNHANDLED_IRQS.fetch_or(1 << irq, Ordering::AcqRel);
+        }
+    }
+
+    /// Sends an inter-processor interrupt (IPI) to the specified target CPU or all CPUs.
+    fn send_ipi(irq_num: usize, target: IpiTarget) {
+        stats::record_ipi_sent(irq_num);
+        let send_to = |cpu_id: usize| {
+            let thread = CPU_THREADS
+                .get(cpu_id)
+                .map_or(0, |t| t.load(Ordering::Acquire));
+            if thread == 0 {
+                warn!("send_ipi: CPU {cpu_id} is not online");
+                return;
+            }
+            let ret = unsafe { libc::pthread_kill(thread as libc::pthread_t, irq_num as c_int) };
+            if ret != 0 {
+                warn!("send_ipi to CPU {cpu_id} failed: {ret}");
+            }
+        };
+        match target {
+            IpiTarget::Current { cpu_id } | IpiTarget::Other { cpu_id } => send_to(cpu_id),
+            IpiTarget::AllExceptCurrent { cpu_id, cpu_num } => {
+                for i in (0..cpu_num).filter(|&i| i != cpu_id) {
+                    send_to(i);
+                }
+            }
+        }
+    }
+
+    /// Routes the given IRQ to the CPUs in `cpu_mask`.
+    ///
+    /// Signals are delivered to whichever thread does not block them, so the
+    /// routing can not be controlled.
+    fn set_affinity(irq: usize, _cpu_mask: usize) -> IrqResult {
+        if !is_irq_signal(irq) {
+            return Err(IrqError::InvalidInput);
+        }
+        Err(IrqError::Unsupported)
+    }
+
+    /// Sets the priority of the given IRQ.
+    fn set_priority(irq: usize, _priority: u8) -> IrqResult {
+        if !is_irq_signal(irq) {
+            return Err(IrqError::InvalidInput);
+        }
+        Err(IrqError::Unsupported)
+    }
+
+    /// Sets the trigger mode (edge or level) of the given IRQ.
+    fn set_trigger(irq: usize, _mode: TriggerMode) -> IrqResult {
+        if !is_irq_signal(irq) {
+            return Err(IrqError::InvalidInput);
+        }
+        Err(IrqError::Unsupported)
+    }
+}
@@ -0,0 +1,47 @@
+//! [`axplat`] implementation that runs the kernel as an ordinary Linux process.
+//!
+//! The platform is emulated with host facilities:
+//!
+//! - Console: standard input and output.
+//! - Physical memory: an anonymous `mmap` region, identity mapped.
+//! - Time: `CLOCK_MONOTONIC` and `CLOCK_REALTIME`.
+//! - IRQs (`irq` feature): signals. The IRQ number is the signal number, the
+//!   timer IRQ is `SIGALRM` and the IPI is `SIGUSR1`.
+//! - CPUs (`smp` feature): each secondary CPU is a host thread.
+//!
+//! The crate provides the C `main` function of the process, which calls the
+//! function decorated by [`axplat::main`].
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate axplat;
+
+mod boot;
+mod console;
+mod devices;
+mod init;
+#[cfg(feature = "irq")]
+mod irq;
+mod mem;
+mod power;
+mod time;
+
+pub use boot::this_cpu_id;
+#[cfg(feature = "irq")]
+pub use irq::{disable_irqs, enable_irqs, irqs_enabled};
+
+pub mod config {
+    //! Platform configuration module.
+    //!
+    //! If the `AX_CONFIG_PATH` environment variable is set, it will load the configuration from the specified path.
+    //! Otherwise, it will fall back to the `axconfig.toml` file in the current directory and generate the default configuration.
+    //!
+    //! If the `PACKAGE` field in the configuration does not match the package name, it will panic with an error message.
+    axconfig_macros::include_configs!(path_env = "AX_CONFIG_PATH", fallback = "axconfig.toml");
+    assert_str_eq!(
+        PACKAGE,
+        env!("CARGO_PKG_NAME"),
+        "`PACKAGE` field in the configuration does not match the Package name. Please check your configuration file."
+    );
+}
@@ -0,0 +1,112 @@
+use axplat::mem::{DmaRange, DmaZone, MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
+use lazyinit::LazyInit;
+
+use crate::config::plat::{PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+
+/// The emulated physical memory, allocated on startup.
+static RAM_RANGES: LazyInit<[RawRange; 1]> = LazyInit::new();
+
+/// Allocates the emulated physical memory.
+pub(crate) fn init_early() {
+    let base = unsafe {
+        libc::mmap(
+            core::ptr::null_mut(),
+            PHYS_MEMORY_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+            -1,
+            0,
+        )
+    };
+    if base == libc::MAP_FAILED {
+        panic!(
+            "failed to allocate {PHYS_MEMORY_SIZE:#x} bytes of physical memory: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    RAM_RANGES.init_once([(base as usize, PHYS_MEMORY_SIZE)]);
+}
+
+struct MemIfImpl;
+
+#[impl_interface]
+impl MemIf for MemIfImpl {
+    /// Returns all physical memory (RAM) ranges on the platform.
+    ///
+    /// All memory ranges except reserved ranges (including the kernel loaded
+    /// range) are free for allocation.
+    fn phys_ram_ranges() -> &'static [RawRange] {
+        RAM_RANGES.as_slice()
+    }
+
+    /// Returns all reserved physical memory ranges on the platform.
+    ///
+    /// Reserved memory can be contained in [`phys_ram_ranges`], they are not
+    /// allocatable but should be mapped to kernel's address space.
+    ///
+    /// Note that the ranges returned should not include the range where the
+    /// kernel is loaded.
+    fn reserved_phys_ram_ranges() -> &'static [RawRange] {
+        &[]
+    }
+
+    /// Returns all device memory (MMIO) ranges on the platform.
+    fn mmio_ranges() -> &'static [RawRange] {
+        &[]
+    }
+
+    /// Translates a physical address to a virtual address.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
+    }
+
+    /// Translates a virtual address to a physical address.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
+    }
+
+    /// Whether the DMA of devices is coherent with the CPU caches.
+    ///
+    /// The emulated physical memory is only accessed by the host CPUs.
+    fn dma_coherent() -> bool {
+        true
+    }
+
+    /// Cleans the data cache lines that cover the given virtual address range.
+    ///
+    /// The caches are coherent, so it only orders the memory accesses.
+    fn cache_clean_range(_vaddr: VirtAddr, _size: usize) {
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Invalidates the data cache lines that cover the given virtual address
+    /// range.
+    ///
+    /// The caches are coherent, so it only orders the memory accesses.
+    fn cache_invalidate_range(_vaddr: VirtAddr, _size: usize) {
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Cleans and invalidates the data cache lines that cover the given
+    /// virtual address range.
+    ///
+    /// The caches are coherent, so it only orders the memory accesses.
+    fn cache_flush_range(_vaddr: VirtAddr, _size: usize) {
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Returns the windows through which devices access physical memory by
+    /// DMA.
+    ///
+    /// There is no device that does DMA.
+    fn dma_ranges() -> &'static [DmaRange] {
+        &[]
+    }
+
+    /// Returns the end physical address (exclusive) of the given DMA zone.
+    ///
+    /// There is no device that does DMA.
+    fn dma_zone_end(_zone: DmaZone) -> Option<usize> {
+        None
+    }
+}
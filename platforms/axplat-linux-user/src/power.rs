@@ -0,0 +1,105 @@
+use std::os::unix::process::CommandExt;
+
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
+
+/// The environment variable that tells the re-executed process the kind of
+/// the reset.
+const RESET_KIND_ENV: &str = "AXPLAT_RESET_KIND";
+
+struct PowerImpl;
+
+#[impl_interface]
+impl PowerIf for PowerImpl {
+    /// Bootstraps the given CPU core with the given initial stack (in physical
+    /// address).
+    ///
+    /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
+    /// CPU cores on the platform).
+    ///
+    /// The CPU is emulated by a new host thread, which has its own stack, so
+    /// `stack_top_paddr` is ignored.
+    #[cfg(feature = "smp")]
+    fn cpu_boot(cpu_id: usize, _stack_top_paddr: usize) -> axplat::power::CpuBootResult {
+        crate::boot::start_secondary_cpu(cpu_id)
+    }
+
+    /// Takes the calling CPU offline.
+    ///
+    /// The host thread of the CPU is blocked forever, and a new one is started
+    /// when the CPU is brought back by [`cpu_boot`](Self::cpu_boot).
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        crate::boot::stop_current_cpu()
+    }
+
+    /// Returns the power state of the given CPU.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        crate::boot::CPU_STATES.get(cpu_id)
+    }
+
+    /// Returns the idle states that CPUs can enter.
+    fn idle_states() -> &'static [IdleState] {
+        &[IdleState {
+            name: "sigsuspend",
+            entry_latency_ns: 10_000,
+            exit_latency_ns: 10_000,
+            min_residency_ns: 20_000,
+        }]
+    }
+
+    /// Waits for an IRQ (signal) on the current CPU.
+    ///
+    /// The IRQ is handled before returning. Without the `irq` feature, it only
+    /// yields the host thread.
+    fn cpu_idle(_index: usize) {
+        #[cfg(feature = "irq")]
+        crate::irq::wait_for_irq();
+        #[cfg(not(feature = "irq"))]
+        std::thread::yield_now();
+    }
+
+    /// Shutdown the whole system.
+    fn system_off() -> ! {
+        info!("Shutting down...");
+        std::process::exit(0)
+    }
+
+    /// Resets the whole system by re-executing the process.
+    ///
+    /// The memory is not kept on both kinds of reset.
+    fn system_reset(kind: ResetKind) -> ! {
+        info!("Resetting ({kind:?})...");
+        let mut args = std::env::args_os();
+        let arg0 = args.next().unwrap_or_default();
+        let kind = match kind {
+            ResetKind::Cold => "cold",
+            ResetKind::Warm => "warm",
+        };
+        let err = std::process::Command::new("/proc/self/exe")
+            .arg0(arg0)
+            .args(args)
+            .env(RESET_KIND_ENV, kind)
+            .exec();
+        error!("failed to reset: {err}");
+        std::process::exit(1)
+    }
+
+    /// Returns the reason for the last reset.
+    ///
+    /// It is [`ResetReason::PowerOn`] when the process is started from the
+    /// host, or [`ResetReason::Software`] after [`system_reset`](Self::system_reset).
+    fn reset_reason() -> Option<ResetReason> {
+        let reason = match std::env::var(RESET_KIND_ENV).as_deref() {
+            Ok("cold") => ResetReason::Software(ResetKind::Cold),
+            Ok("warm") => ResetReason::Software(ResetKind::Warm),
+            _ => ResetReason::PowerOn,
+        };
+        Some(reason)
+    }
+
+    /// Get the number of CPU cores available on this platform.
+    fn cpu_num() -> usize {
+        crate::config::plat::MAX_CPU_NUM
+    }
+}
@@ -0,0 +1,135 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axplat::time::{NANOS_PER_SEC, TimeIf};
+
+/// Wall time offset in nanoseconds at monotonic time base.
+static EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
+fn clock_nanos(clock: libc::clockid_t) -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(clock, &mut ts) };
+    ts.tv_sec as u64 * NANOS_PER_SEC + ts.tv_nsec as u64
+}
+
+pub(super) fn init_early() {
+    let offset = clock_nanos(libc::CLOCK_REALTIME) - clock_nanos(libc::CLOCK_MONOTONIC);
+    EPOCHOFFSET_NANOS.store(offset, Ordering::Release);
+}
+
+#[cfg(feature = "irq")]
+mod oneshot {
+    use core::cell::Cell;
+
+    use crate::config::devices::TIMER_IRQ;
+
+    std::thread_local! {
+        /// The POSIX timer of the current CPU, which delivers `TIMER_IRQ` to
+        /// the current thread only.
+        static TIMER: Cell<Option<libc::timer_t>> = const { Cell::new(None) };
+    }
+
+    pub(crate) fn init_percpu() {
+        let mut sev: libc::sigevent = unsafe { core::mem::zeroed() };
+        sev.sigev_notify = libc::SIGEV_THREAD_ID;
+        sev.sigev_signo = TIMER_IRQ as _;
+        sev.sigev_notify_thread_id = unsafe { libc::gettid() };
+        let mut timer = core::ptr::null_mut();
+        if unsafe { libc::timer_create(libc::CLOCK_MONOTONIC, &mut sev, &mut timer) } != 0 {
+            panic!(
+                "failed to create timer: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        TIMER.set(Some(timer));
+        // Fire the first timer IRQ as soon as IRQs are enabled.
+        set_deadline(0);
+    }
+
+    pub(crate) fn set_deadline(deadline_ns: u64) {
+        let Some(timer) = TIMER.get() else {
+            warn!("timer is not initialized on this CPU");
+            return;
+        };
+        // A zero `it_value` disarms the timer, so fire an expired deadline
+        // with the smallest non-zero time.
+        let deadline_ns = deadline_ns.max(1);
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: (deadline_ns / super::NANOS_PER_SEC) as _,
+                tv_nsec: (deadline_ns % super::NANOS_PER_SEC) as _,
+            },
+        };
+        unsafe { libc::timer_settime(timer, libc::TIMER_ABSTIME, &spec, core::ptr::null_mut()) };
+    }
+}
+
+#[cfg(feature = "irq")]
+pub(super) use oneshot::init_percpu;
+
+struct TimeIfImpl;
+
+#[impl_interface]
+impl TimeIf for TimeIfImpl {
+    /// Returns the current clock time in hardware ticks.
+    fn current_ticks() -> u64 {
+        clock_nanos(libc::CLOCK_MONOTONIC)
+    }
+
+    /// Converts hardware ticks to nanoseconds.
+    fn ticks_to_nanos(ticks: u64) -> u64 {
+        ticks
+    }
+
+    /// Converts nanoseconds to hardware ticks.
+    fn nanos_to_ticks(nanos: u64) -> u64 {
+        nanos
+    }
+
+    /// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
+    fn epochoffset_nanos() -> u64 {
+        EPOCHOFFSET_NANOS.load(Ordering::Acquire)
+    }
+
+    /// Sets the wall time (also known as realtime) in nanoseconds since epoch.
+    ///
+    /// Only the wall time of the kernel is changed, the host clock is kept.
+    fn set_wall_time_nanos(nanos: u64) {
+        let offset = nanos.saturating_sub(clock_nanos(libc::CLOCK_MONOTONIC));
+        EPOCHOFFSET_NANOS.store(offset, Ordering::Release);
+    }
+
+    /// Set a one-shot timer.
+    ///
+    /// A timer interrupt will be triggered at the specified monotonic time deadline (in nanoseconds).
+    #[cfg(feature = "irq")]
+    fn set_oneshot_timer(deadline_ns: u64) {
+        oneshot::set_deadline(deadline_ns);
+    }
+
+    /// Returns the IRQ number of the RTC alarm, or `None` if the platform has
+    /// no RTC alarm.
+    #[cfg(feature = "irq")]
+    fn rtc_alarm_irq() -> Option<usize> {
+        None
+    }
+
+    /// Sets the RTC alarm at the specified wall time (in nanoseconds since
+    /// epoch).
+    ///
+    /// It is not supported, as there is no RTC device in the user space.
+    #[cfg(feature = "irq")]
+    fn set_rtc_alarm_nanos(_wall_time_nanos: u64) -> bool {
+        false
+    }
+
+    /// Cancels the pending RTC alarm, if any.
+    #[cfg(feature = "irq")]
+    fn cancel_rtc_alarm() {}
+}
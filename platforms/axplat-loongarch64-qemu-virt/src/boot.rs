@@ -50,14 +50,67 @@ fn init_mmu() {
     );
 }
 
+/// The signature of the EFI system table, `"IBI SYST"`.
+const EFI_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_5953_2049_4249;
+/// The offsets of the number and the address of configuration tables in the
+/// EFI system table.
+const EFI_SYSTAB_NR_TABLES_OFFSET: usize = 0x68;
+const EFI_SYSTAB_TABLES_OFFSET: usize = 0x70;
+/// The size of each configuration table entry, a GUID and an address.
+const EFI_CONFIG_TABLE_SIZE: usize = 24;
+
+/// The GUID of the EFI configuration table holding the device tree, in the
+/// byte order in memory.
+const EFI_DTB_TABLE_GUID: [u8; 16] = [
+    0xd5, 0x21, 0xb6, 0xb1, 0x9c, 0xf1, 0xa5, 0x41, 0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0,
+];
+
+/// Finds the physical address of the device tree in the EFI system table,
+/// or returns `0` if there is no valid system table or device tree.
+///
+/// Following the LoongArch boot protocol, QEMU passes the physical address of
+/// the EFI system table in `a2` when loading the kernel directly, and the
+/// device tree is one of its configuration tables.
+pub(crate) fn efi_fdt_paddr(systab_paddr: usize) -> usize {
+    let read = |paddr: usize| unsafe {
+        axplat::mem::phys_to_virt(pa!(paddr))
+            .as_ptr_of::<u64>()
+            .read_unaligned() as usize
+    };
+    if systab_paddr == 0 || read(systab_paddr) as u64 != EFI_SYSTEM_TABLE_SIGNATURE {
+        return 0;
+    }
+    let nr_tables = read(systab_paddr + EFI_SYSTAB_NR_TABLES_OFFSET);
+    let tables = read(systab_paddr + EFI_SYSTAB_TABLES_OFFSET);
+    if tables == 0 {
+        return 0;
+    }
+    (0..nr_tables)
+        .map(|i| tables + i * EFI_CONFIG_TABLE_SIZE)
+        .find(|&entry| {
+            let guid = unsafe {
+                axplat::mem::phys_to_virt(pa!(entry))
+                    .as_ptr_of::<[u8; 16]>()
+                    .read_unaligned()
+            };
+            guid == EFI_DTB_TABLE_GUID
+        })
+        .map_or(0, |entry| read(entry + 16))
+}
+
 /// The earliest entry point for the primary CPU.
 ///
 /// We can't use bl to jump to higher address, so we use jirl to jump to higher address.
+///
+/// The physical address of the EFI system table in `a2` is passed to the
+/// kernel as the argument.
 #[unsafe(naked)]
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.boot")]
 unsafe extern "C" fn _start() -> ! {
     core::arch::naked_asm!("
+        move        $s0, $a2            # EFI system table, callee-saved
+
         ori         $t0, $zero, 0x1     # CSR_DMW1_PLV0
         lu52i.d     $t0, $t0, -2048     # UC, PLV0, 0x8000 xxxx xxxx xxxx
         csrwr       $t0, 0x180          # LOONGARCH_CSR_DMWIN0
@@ -75,8 +128,8 @@ unsafe extern "C" fn _start() -> ! {
         bl          {init_boot_page_table}
         bl          {init_mmu}          # setup boot page table and enable MMU
 
-        csrrd       $a0, 0x20           # cpuid
-        li.d        $a1, 0              # TODO: parse dtb
+        csrrd       $a0, 0x20           # hardware cpuid
+        move        $a1, $s0            # EFI system table
         la.global   $t0, {entry}
         jirl        $zero, $t0, 0",
         boot_stack_size = const BOOT_STACK_SIZE,
@@ -84,7 +137,7 @@ unsafe extern "C" fn _start() -> ! {
         enable_fp_simd = sym enable_fp_simd,
         init_boot_page_table = sym init_boot_page_table,
         init_mmu = sym init_mmu,
-        entry = sym axplat::call_main,
+        entry = sym axplat::call_main_hw,
     )
 }
 
@@ -107,12 +160,12 @@ unsafe extern "C" fn _start_secondary() -> ! {
         bl           {enable_fp_simd}    # enable FP/SIMD instructions
         bl           {init_mmu}          # setup boot page table and enable MMU
 
-        csrrd        $a0, 0x20                  # cpuid
+        csrrd        $a0, 0x20                  # hardware cpuid
         la.global    $t0, {entry}
         jirl         $zero, $t0, 0",
         sm_boot_stack_top = sym super::mp::SMP_BOOT_STACK_TOP,
         enable_fp_simd = sym enable_fp_simd,
         init_mmu = sym init_mmu,
-        entry = sym axplat::call_secondary_main,
+        entry = sym axplat::call_secondary_main_hw,
     )
 }
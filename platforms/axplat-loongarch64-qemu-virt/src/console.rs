@@ -1,3 +1,6 @@
+#[cfg(feature = "irq")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::mem::phys_to_virt;
 use axplat::mem::{PhysAddr, pa};
 use kspin::SpinNoIrq;
@@ -7,6 +10,74 @@ const UART_BASE: PhysAddr = pa!(crate::config::devices::UART_PADDR);
 
 static UART: SpinNoIrq<Uart> = SpinNoIrq::new(Uart::new(phys_to_virt(UART_BASE).as_usize()));
 
+/// Whether the UART IRQ handler is registered.
+#[cfg(feature = "irq")]
+static IRQ_ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn do_putchar(uart: &Uart, c: u8) {
+    match c {
+        b'\n' => {
+            let _ = uart.put(b'\r');
+            let _ = uart.put(b'\n');
+        }
+        c => {
+            let _ = uart.put(c);
+        }
+    }
+}
+
+/// Writes bytes to the UART by a new `Uart` instance, bypassing [`UART`].
+fn emergency_write_bytes(bytes: &[u8]) {
+    let uart = Uart::new(phys_to_virt(UART_BASE).as_usize());
+    for &c in bytes {
+        do_putchar(&uart, c);
+    }
+}
+
+/// Early stage initialization of the console.
+pub fn init_early() {
+    axplat::console::set_emergency_write_fn(emergency_write_bytes);
+}
+
+/// UART IRQ handler, which drains the receive FIFO into
+/// [`axplat::console::rx`].
+#[cfg(feature = "console-irq")]
+fn irq_handler() {
+    {
+        let uart = UART.lock();
+        while let Some(c) = uart.get() {
+            axplat::console::rx::push(c);
+        }
+    }
+    axplat::console::rx::notify();
+}
+
+/// Switches the console input to the interrupt-driven mode.
+#[cfg(feature = "console-irq")]
+pub fn init_irq() {
+    const IER: usize = 1;
+    const IER_RDA: u8 = 1 << 0;
+    const MCR: usize = 4;
+    const MCR_OUT2: u8 = 1 << 3;
+
+    {
+        // Enable the "received data available" interrupt. OUT2 gates the
+        // interrupt output on some 16550 variants.
+        let _uart = UART.lock();
+        let base = phys_to_virt(UART_BASE).as_usize();
+        let ier = (base + IER) as *mut u8;
+        let mcr = (base + MCR) as *mut u8;
+        unsafe {
+            ier.write_volatile(ier.read_volatile() | IER_RDA);
+            mcr.write_volatile(mcr.read_volatile() | MCR_OUT2);
+        }
+    }
+    if axplat::irq::register(crate::config::devices::UART_IRQ, irq_handler).is_some() {
+        IRQ_ENABLED.store(true, Ordering::Release);
+        axplat::console::rx::enable();
+    }
+}
+
 use axplat::console::ConsoleIf;
 
 struct ConsoleIfImpl;
@@ -16,22 +87,17 @@ impl ConsoleIf for ConsoleIfImpl {
     /// Writes bytes to the console from input u8 slice.
     fn write_bytes(bytes: &[u8]) {
         for &c in bytes {
-            let uart = UART.lock();
-            match c {
-                b'\n' => {
-                    let _ = uart.put(b'\r');
-                    let _ = uart.put(b'\n');
-                }
-                c => {
-                    let _ = uart.put(c);
-                }
-            }
+            do_putchar(&UART.lock(), c);
         }
     }
 
     /// Reads bytes from the console into the given mutable slice.
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
+        #[cfg(feature = "irq")]
+        if axplat::console::rx::is_enabled() {
+            return axplat::console::rx::read(bytes);
+        }
         for (i, byte) in bytes.iter_mut().enumerate() {
             match UART.lock().get() {
                 Some(c) => *byte = c,
@@ -40,4 +106,13 @@ impl ConsoleIf for ConsoleIfImpl {
         }
         bytes.len()
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize> {
+        IRQ_ENABLED
+            .load(Ordering::Acquire)
+            .then_some(crate::config::devices::UART_IRQ)
+    }
 }
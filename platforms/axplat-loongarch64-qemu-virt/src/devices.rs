@@ -0,0 +1,41 @@
+use axplat::devices::{Device, DeviceIf, DeviceKind, DeviceList};
+
+use crate::config::devices::{
+    PCI_BUS_END, PCI_ECAM_BASE, RTC_PADDR, UART_IRQ, UART_PADDR, VIRTIO_MMIO_RANGES,
+};
+
+/// The devices in the static configuration.
+static DEVICES: DeviceList = DeviceList::new()
+    .with(Device::new(
+        DeviceKind::Uart,
+        "ns16550a",
+        (UART_PADDR, 0x100),
+        Some(UART_IRQ),
+    ))
+    .with(Device::new(
+        DeviceKind::Rtc,
+        "loongson,ls7a-rtc",
+        (RTC_PADDR, 0x100),
+        None,
+    ))
+    .with_each(DeviceKind::VirtioMmio, "virtio,mmio", VIRTIO_MMIO_RANGES)
+    // Each bus takes 1M bytes of the ECAM space.
+    .with(Device::new(
+        DeviceKind::PciHostBridge,
+        "pci-host-ecam-generic",
+        (PCI_ECAM_BASE, (PCI_BUS_END + 1) << 20),
+        None,
+    ));
+
+struct DeviceIfImpl;
+
+#[impl_interface]
+impl DeviceIf for DeviceIfImpl {
+    /// Returns the devices on the platform that drivers may use.
+    ///
+    /// They are taken from the device tree, or from the static configuration
+    /// if no DTB is present.
+    fn devices() -> &'static [Device] {
+        axplat::fdt::devices().unwrap_or(&DEVICES)
+    }
+}
@@ -1,4 +1,7 @@
 use axplat::init::InitIf;
+use axplat::power::topology::{self, CpuTopology};
+
+use crate::config::plat::MAX_CPU_NUM;
 
 struct InitIfImpl;
 
@@ -7,14 +10,27 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _mbi: usize) {
+    fn init_early(_cpu_id: usize, efi_systab: usize) {
+        #[cfg(feature = "smp")]
+        crate::mp::CPU_STATES.set(_cpu_id, axplat::power::CpuState::On);
         axcpu::init::init_trap();
+        crate::console::init_early();
+        // Fall back to the static configuration if no valid DTB is present.
+        unsafe { axplat::fdt::init(crate::boot::efi_fdt_paddr(efi_systab)) }.ok();
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init_from_fdt();
+        match axplat::fdt::cpus() {
+            Some(cpus) => topology::register_all(cpus.iter().copied()),
+            None => topology::register_all((0..MAX_CPU_NUM).map(CpuTopology::flat)),
+        };
         crate::time::init_early();
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
     #[cfg(feature = "smp")]
-    fn init_early_secondary(_cpu_id: usize) {
+    fn init_early_secondary(cpu_id: usize) {
+        crate::mp::CPU_STATES.set(cpu_id, axplat::power::CpuState::On);
         axcpu::init::init_trap();
     }
 
@@ -24,14 +40,25 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _arg: usize) {
+        #[cfg(feature = "irq")]
+        {
+            crate::irq::init();
+            crate::irq::init_percpu();
+        }
         crate::time::init_percpu();
+        #[cfg(feature = "console-irq")]
+        crate::console::init_irq();
         #[cfg(all(feature = "smp", feature = "irq"))]
         axplat::irq::set_enable(crate::config::devices::IPI_IRQ, true);
+        #[cfg(feature = "pci")]
+        crate::pci::init();
     }
 
     /// Initializes the platform at the later stage for secondary cores.
     #[cfg(feature = "smp")]
     fn init_later_secondary(_cpu_id: usize) {
+        #[cfg(feature = "irq")]
+        crate::irq::init_percpu();
         crate::time::init_percpu();
         #[cfg(feature = "irq")]
         axplat::irq::set_enable(crate::config::devices::IPI_IRQ, true);
@@ -1,4 +1,7 @@
-use axplat::irq::{HandlerTable, IpiTarget, IrqHandler, IrqIf};
+use axplat::irq::{
+    HandlerTable, HandlerToken, IpiTarget, IrqContextHandler, IrqError, IrqIf, IrqResult,
+    TriggerMode, stats,
+};
 use loongArch64::{
     iocsr::{iocsr_read_w, iocsr_write_w},
     register::{
@@ -9,8 +12,25 @@ use loongArch64::{
 
 use crate::config::devices::{IPI_IRQ, TIMER_IRQ};
 
-/// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 13;
+/// The highest bit of the IRQ numbers of the CPU-side interrupts, whose lower
+/// bits are the interrupt line in `ESTAT` (e.g., 11 for the timer).
+const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
+
+/// The `HWI0` line, where EXTIOI delivers the device interrupts.
+const EXTIOI_IRQ: usize = INTC_IRQ_BASE + 2;
+
+/// The maximum number of CPU-side interrupts, indexed by the interrupt line in
+/// `ESTAT`.
+const MAX_INTC_IRQ_COUNT: usize = 16;
+
+/// Handlers of the CPU-side interrupts (timer and IPI).
+static INTC_HANDLER_TABLE: HandlerTable<MAX_INTC_IRQ_COUNT> = HandlerTable::new();
+
+/// The maximum number of device IRQs, which are the EXTIOI vectors.
+pub const MAX_IRQ_COUNT: usize = extioi::NUM_VECTORS;
+
+static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
+
 const IOCSR_IPI_SEND_CPU_SHIFT: u32 = 16;
 const IOCSR_IPI_SEND_BLOCKING: u32 = 1 << 31;
 
@@ -21,6 +41,187 @@ const IOCSR_IPI_ENABLE: usize = 0x1004;
 const IOCSR_IPI_CLEAR: usize = 0x100c;
 const IOCSR_IPI_SEND: usize = 0x1040;
 
+/// Extended I/O interrupt controller (EXTIOI) of the CPU.
+///
+/// It collects 256 interrupt vectors (from PCH-PIC and PCH-MSI), and routes
+/// each vector to one core. All vectors are mapped to the `HWI0` line.
+///
+/// See Section 11.2 of the [Loongson 3A5000 Manual](https://loongson.github.io/LoongArch-Documentation/Loongson-3A5000-usermanual-EN.html).
+mod extioi {
+    use kspin::SpinNoIrq;
+    use loongArch64::iocsr::{iocsr_read_w, iocsr_write_w};
+
+    /// The number of interrupt vectors.
+    pub const NUM_VECTORS: usize = 256;
+
+    /// The number of cores that a vector can be routed to in a node.
+    pub const CORES_PER_NODE: usize = 4;
+
+    const IOCSR_EXTIOI_NODETYPE: usize = 0x14a0;
+    const IOCSR_EXTIOI_IPMAP: usize = 0x14c0;
+    const IOCSR_EXTIOI_ENABLE: usize = 0x1600;
+    const IOCSR_EXTIOI_BOUNCE: usize = 0x1680;
+    /// The ISR of the current core, write 1 to clear.
+    const IOCSR_EXTIOI_CORE_ISR: usize = 0x1800;
+    const IOCSR_EXTIOI_ROUTE: usize = 0x1c00;
+
+    /// Serializes the read-modify-write of the registers.
+    static LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+    fn update(reg: usize, f: impl FnOnce(u32) -> u32) {
+        let _guard = LOCK.lock();
+        iocsr_write_w(reg, f(iocsr_read_w(reg)));
+    }
+
+    /// Maps all vectors to `HWI0` of the core `hw_id` in node 0, and disables
+    /// them.
+    pub fn init(hw_id: usize) {
+        // Node type 0 only contains node 0.
+        iocsr_write_w(IOCSR_EXTIOI_NODETYPE, 1);
+        // One byte for each group of 32 vectors, bit 0 is `HWI0`.
+        for i in 0..2 {
+            iocsr_write_w(IOCSR_EXTIOI_IPMAP + i * 4, 0x0101_0101);
+        }
+        let route = u32::from_ne_bytes([route_byte(hw_id); 4]);
+        for i in 0..NUM_VECTORS / 4 {
+            iocsr_write_w(IOCSR_EXTIOI_ROUTE + i * 4, route);
+        }
+        for i in 0..NUM_VECTORS / 32 {
+            iocsr_write_w(IOCSR_EXTIOI_ENABLE + i * 4, 0);
+            iocsr_write_w(IOCSR_EXTIOI_BOUNCE + i * 4, 0);
+        }
+    }
+
+    /// The route of a vector: a core bitmap in the lower 4 bits, and node 0 in
+    /// the higher 4 bits.
+    const fn route_byte(hw_id: usize) -> u8 {
+        1 << (hw_id % CORES_PER_NODE)
+    }
+
+    pub fn set_enable(vector: usize, enabled: bool) {
+        let bit = 1 << (vector % 32);
+        update(
+            IOCSR_EXTIOI_ENABLE + vector / 32 * 4,
+            |value| match enabled {
+                true => value | bit,
+                false => value & !bit,
+            },
+        );
+    }
+
+    /// Routes the vector to the core `hw_id`, which must be less than
+    /// [`CORES_PER_NODE`].
+    pub fn set_route(vector: usize, hw_id: usize) {
+        let shift = vector % 4 * 8;
+        update(IOCSR_EXTIOI_ROUTE + vector / 4 * 4, |value| {
+            value & !(0xff << shift) | (route_byte(hw_id) as u32) << shift
+        });
+    }
+
+    /// Takes the pending vectors of the current core, and calls `f` for each.
+    pub fn for_each_pending(mut f: impl FnMut(usize)) {
+        for i in 0..NUM_VECTORS / 32 {
+            let reg = IOCSR_EXTIOI_CORE_ISR + i * 4;
+            let mut pending = iocsr_read_w(reg);
+            if pending == 0 {
+                continue;
+            }
+            iocsr_write_w(reg, pending);
+            while pending != 0 {
+                let bit = pending.trailing_zeros() as usize;
+                pending &= !(1 << bit);
+                f(i * 32 + bit);
+            }
+        }
+    }
+}
+
+/// Programmable interrupt controller of the LS7A bridge (PCH-PIC).
+///
+/// It converts its 64 inputs to the EXTIOI vectors of the same numbers, so the
+/// IRQ number of a device is its PCH-PIC input.
+mod pch_pic {
+    use axplat::mem::pa;
+    use kspin::SpinNoIrq;
+
+    use crate::config::devices::PCH_PIC_PADDR;
+    use crate::mem::phys_to_virt;
+
+    /// The number of inputs.
+    pub const NUM_INPUTS: usize = 64;
+
+    const INT_MASK: usize = 0x20;
+    const HTMSI_EN: usize = 0x40;
+    const INT_EDGE: usize = 0x60;
+    const INT_CLEAR: usize = 0x80;
+    const AUTO_CTRL0: usize = 0xc0;
+    const AUTO_CTRL1: usize = 0xe0;
+    const ROUTE_ENTRY: usize = 0x100;
+    const HTMSI_VECTOR: usize = 0x200;
+    const INT_POL: usize = 0x3e0;
+
+    /// Serializes the read-modify-write of the registers.
+    static LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+    fn reg<T>(offset: usize) -> *mut T {
+        (phys_to_virt(pa!(PCH_PIC_PADDR)).as_usize() + offset) as *mut T
+    }
+
+    fn read(offset: usize) -> u32 {
+        unsafe { reg::<u32>(offset).read_volatile() }
+    }
+
+    fn write(offset: usize, value: u32) {
+        unsafe { reg::<u32>(offset).write_volatile(value) }
+    }
+
+    /// Sets or clears the bit of `input` in a 64-bit register.
+    fn update_bit(offset: usize, input: usize, set: bool) {
+        let offset = offset + input / 32 * 4;
+        let bit = 1 << (input % 32);
+        let _guard = LOCK.lock();
+        let value = read(offset);
+        write(offset, if set { value | bit } else { value & !bit });
+    }
+
+    /// Maps input `i` to EXTIOI vector `i`, and masks all inputs, which are
+    /// level-triggered and active-high.
+    pub fn init() {
+        for i in 0..NUM_INPUTS {
+            unsafe {
+                reg::<u8>(HTMSI_VECTOR + i).write_volatile(i as u8);
+                reg::<u8>(ROUTE_ENTRY + i).write_volatile(1);
+            }
+        }
+        for i in 0..2 {
+            write(INT_MASK + i * 4, u32::MAX);
+            write(INT_EDGE + i * 4, 0);
+            write(INT_POL + i * 4, 0);
+            write(INT_CLEAR + i * 4, u32::MAX);
+            write(AUTO_CTRL0 + i * 4, 0);
+            write(AUTO_CTRL1 + i * 4, 0);
+            write(HTMSI_EN + i * 4, u32::MAX);
+        }
+    }
+
+    pub fn set_enable(input: usize, enabled: bool) {
+        update_bit(INT_MASK, input, !enabled);
+    }
+
+    pub fn set_edge(input: usize, edge: bool) {
+        update_bit(INT_EDGE, input, edge);
+    }
+
+    /// Clears the latched edge-triggered interrupt, so that the next edge
+    /// can be delivered.
+    pub fn ack(input: usize) {
+        let bit = 1 << (input % 32);
+        if read(INT_EDGE + input / 32 * 4) & bit != 0 {
+            write(INT_CLEAR + input / 32 * 4, bit);
+        }
+    }
+}
+
 fn make_ipi_send_value(cpu_id: usize, vector: u32, blocking: bool) -> u32 {
     let mut value = (cpu_id as u32) << IOCSR_IPI_SEND_CPU_SHIFT | vector;
     if blocking {
@@ -32,6 +233,7 @@ fn make_ipi_send_value(cpu_id: usize, vector: u32, blocking: bool) -> u32 {
 fn handle_ipi(irq: usize) {
     let mut status = iocsr_read_w(IOCSR_IPI_STATUS);
     if status == 0 {
+        stats::record_spurious();
         return;
     }
     iocsr_write_w(IOCSR_IPI_CLEAR, status);
@@ -39,13 +241,45 @@ fn handle_ipi(irq: usize) {
     while status != 0 {
         let vector = status.trailing_zeros() as usize;
         status &= !(1 << vector);
-        if !IRQ_HANDLER_TABLE.handle(irq) {
+        let handled = INTC_HANDLER_TABLE.handle_with_irq(irq & !INTC_IRQ_BASE, irq);
+        stats::record_irq(irq, handled);
+        if !handled {
+            warn!("Unhandled IRQ {:#x}", irq);
+        }
+    }
+}
+
+fn handle_extioi() {
+    let mut any = false;
+    extioi::for_each_pending(|irq| {
+        any = true;
+        trace!("IRQ {}", irq);
+        if irq < pch_pic::NUM_INPUTS {
+            pch_pic::ack(irq);
+        }
+        let handled = IRQ_HANDLER_TABLE.handle_with_irq(irq, irq);
+        stats::record_irq(irq, handled);
+        if !handled {
             warn!("Unhandled IRQ {}", irq);
         }
+    });
+    if !any {
+        stats::record_spurious();
     }
 }
 
-static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
+/// Initializes the interrupt controllers, routing all device IRQs to the
+/// current CPU.
+pub(super) fn init() {
+    let hw_id = axplat::power::topology::hw_id(0).unwrap_or(0);
+    extioi::init(hw_id);
+    pch_pic::init();
+}
+
+/// Enables the EXTIOI line on the current CPU.
+pub(super) fn init_percpu() {
+    ecfg::set_lie(ecfg::read().lie() | LineBasedInterrupt::HWI0);
+}
 
 struct IrqIfImpl;
 
@@ -53,6 +287,17 @@ struct IrqIfImpl;
 impl IrqIf for IrqIfImpl {
     /// Enables or disables the given IRQ.
     fn set_enable(irq_num: usize, enabled: bool) {
+        if irq_num & INTC_IRQ_BASE == 0 {
+            if irq_num >= MAX_IRQ_COUNT {
+                warn!("set_enable: invalid irq {}", irq_num);
+                return;
+            }
+            if irq_num < pch_pic::NUM_INPUTS {
+                pch_pic::set_enable(irq_num, enabled);
+            }
+            extioi::set_enable(irq_num, enabled);
+            return;
+        }
         let interrupt_bit = match irq_num {
             TIMER_IRQ => LineBasedInterrupt::TIMER,
             IPI_IRQ => {
@@ -61,7 +306,7 @@ impl IrqIf for IrqIfImpl {
                 LineBasedInterrupt::IPI
             }
             _ => {
-                warn!("set_enable: unsupported irq {}", irq_num);
+                warn!("set_enable: unsupported irq {:#x}", irq_num);
                 return;
             }
         };
@@ -73,60 +318,161 @@ impl IrqIf for IrqIfImpl {
         ecfg::set_lie(new_value);
     }
 
-    /// Registers an IRQ handler for the given IRQ.
-    fn register(irq_num: usize, handler: IrqHandler) -> bool {
-        if IRQ_HANDLER_TABLE.register_handler(irq_num, handler) {
-            Self::set_enable(irq_num, true);
-            return true;
+    /// Registers an IRQ handler with context for the given IRQ.
+    ///
+    /// It also enables the IRQ if the registration succeeds. It returns the
+    /// token to unregister the handler, or `None` if the registration failed.
+    ///
+    /// The `irq` parameter has the following semantics
+    /// 1. If its highest bit is 1, it is an interrupt on the CPU side, i.e.,
+    /// [`TIMER_IRQ`] or [`IPI_IRQ`], whose lower bits are the interrupt line
+    /// in `ESTAT`.
+    /// 2. If its highest bit is 0, it is a device interrupt, and its value is
+    /// the EXTIOI vector, which is the same as the PCH-PIC input.
+    fn register_with_context(
+        irq_num: usize,
+        handler: IrqContextHandler,
+        ctx: usize,
+    ) -> Option<HandlerToken> {
+        let token = match irq_num {
+            TIMER_IRQ | IPI_IRQ => INTC_HANDLER_TABLE.register_handler_with_context(
+                irq_num & !INTC_IRQ_BASE,
+                handler,
+                ctx,
+            ),
+            _ if irq_num & INTC_IRQ_BASE != 0 => None,
+            _ => IRQ_HANDLER_TABLE.register_handler_with_context(irq_num, handler, ctx),
+        };
+        match token {
+            Some(_) => Self::set_enable(irq_num, true),
+            None => warn!("register handler for IRQ {:#x} failed", irq_num),
         }
-        warn!("register handler for IRQ {} failed", irq_num);
-        false
+        token
     }
 
-    /// Unregisters the IRQ handler for the given IRQ.
+    /// Unregisters the IRQ handler registered with the given token for the
+    /// given IRQ.
     ///
-    /// It also disables the IRQ if the unregistration succeeds. It returns the
-    /// existing handler if it is registered, `None` otherwise.
-    fn unregister(irq: usize) -> Option<IrqHandler> {
-        Self::set_enable(irq, false);
-        IRQ_HANDLER_TABLE.unregister_handler(irq)
+    /// The IRQ is disabled only if the last handler is unregistered. It
+    /// returns `true` if the handler was registered, `false` otherwise.
+    fn unregister(irq: usize, token: HandlerToken) -> bool {
+        let (unregistered, last) = match irq {
+            TIMER_IRQ | IPI_IRQ => {
+                let idx = irq & !INTC_IRQ_BASE;
+                (
+                    INTC_HANDLER_TABLE.unregister_handler(idx, token),
+                    !INTC_HANDLER_TABLE.is_registered(idx),
+                )
+            }
+            _ if irq & INTC_IRQ_BASE != 0 => return false,
+            _ => (
+                IRQ_HANDLER_TABLE.unregister_handler(irq, token),
+                !IRQ_HANDLER_TABLE.is_registered(irq),
+            ),
+        };
+        if !unregistered {
+            return false;
+        }
+        if last {
+            Self::set_enable(irq, false);
+        }
+        true
     }
 
     /// Handles the IRQ.
     ///
-    /// It is called by the common interrupt handler. It should look up in the
-    /// IRQ handler table and calls the corresponding handler. If necessary, it
-    /// also acknowledges the interrupt controller after handling.
+    /// `irq` is the interrupt line in `ESTAT` passed by the trap handler (the
+    /// highest bit is optional). For the `HWI0` line, the pending device IRQs
+    /// are taken from EXTIOI, and the edge-triggered ones are acknowledged in
+    /// PCH-PIC before handling.
     fn handle(irq: usize) {
-        if irq == IPI_IRQ {
-            handle_ipi(irq);
-        } else {
-            if irq == TIMER_IRQ {
-                ticlr::clear_timer_interrupt();
-            }
-            trace!("IRQ {}", irq);
-            if !IRQ_HANDLER_TABLE.handle(irq) {
-                warn!("Unhandled IRQ {}", irq);
+        let irq = irq | INTC_IRQ_BASE;
+        match irq {
+            IPI_IRQ => handle_ipi(irq),
+            EXTIOI_IRQ => handle_extioi(),
+            _ => {
+                if irq == TIMER_IRQ {
+                    ticlr::clear_timer_interrupt();
+                }
+                trace!("IRQ {:#x}", irq);
+                let handled = INTC_HANDLER_TABLE.handle_with_irq(irq & !INTC_IRQ_BASE, irq);
+                stats::record_irq(irq, handled);
+                if !handled {
+                    warn!("Unhandled IRQ {:#x}", irq);
+                }
             }
         }
     }
 
     /// Sends an inter-processor interrupt (IPI) to the specified target CPU or all CPUs.
     fn send_ipi(_irq_num: usize, target: IpiTarget) {
+        stats::record_ipi_sent(IPI_IRQ);
+        let send_to = |cpu_id: usize| match axplat::power::topology::hw_id(cpu_id) {
+            Some(hw_id) => iocsr_write_w(IOCSR_IPI_SEND, make_ipi_send_value(hw_id, 0, true)),
+            None => warn!("send_ipi: CPU {cpu_id} is not present"),
+        };
         match target {
-            IpiTarget::Current { cpu_id } => {
-                iocsr_write_w(IOCSR_IPI_SEND, make_ipi_send_value(cpu_id, 0, true));
-            }
-            IpiTarget::Other { cpu_id } => {
-                iocsr_write_w(IOCSR_IPI_SEND, make_ipi_send_value(cpu_id, 0, true));
-            }
+            IpiTarget::Current { cpu_id } | IpiTarget::Other { cpu_id } => send_to(cpu_id),
             IpiTarget::AllExceptCurrent { cpu_id, cpu_num } => {
-                for i in 0..cpu_num {
-                    if i != cpu_id {
-                        iocsr_write_w(IOCSR_IPI_SEND, make_ipi_send_value(i, 0, true));
-                    }
+                for i in (0..cpu_num).filter(|&i| i != cpu_id) {
+                    send_to(i);
                 }
             }
         }
     }
+
+    /// Routes the given IRQ to the CPUs in `cpu_mask`.
+    ///
+    /// EXTIOI delivers a vector to a single core of the first 4 cores, so
+    /// `cpu_mask` must contain exactly one such CPU. The timer and IPI are
+    /// local to each CPU, and can not be routed.
+    fn set_affinity(irq_num: usize, cpu_mask: usize) -> IrqResult {
+        if irq_num & INTC_IRQ_BASE != 0 {
+            return Err(IrqError::Unsupported);
+        }
+        if irq_num >= MAX_IRQ_COUNT || cpu_mask == 0 {
+            return Err(IrqError::InvalidInput);
+        }
+        if !cpu_mask.is_power_of_two() {
+            return Err(IrqError::Unsupported);
+        }
+        let hw_id = axplat::power::topology::hw_id(cpu_mask.trailing_zeros() as usize)
+            .ok_or(IrqError::InvalidInput)?;
+        if hw_id >= extioi::CORES_PER_NODE {
+            return Err(IrqError::Unsupported);
+        }
+        extioi::set_route(irq_num, hw_id);
+        Ok(())
+    }
+
+    /// Sets the priority of the given IRQ.
+    ///
+    /// It is not supported. EXTIOI has no priority per vector: the vectors
+    /// are only mapped to the `HWI` lines of the CPU in groups of 32, and all
+    /// of them use `HWI0`. The priorities of the CPU interrupt lines are
+    /// fixed.
+    fn set_priority(irq_num: usize, _priority: u8) -> IrqResult {
+        if irq_num & INTC_IRQ_BASE == 0 && irq_num >= MAX_IRQ_COUNT {
+            return Err(IrqError::InvalidInput);
+        }
+        Err(IrqError::Unsupported)
+    }
+
+    /// Sets the trigger mode (edge or level) of the given IRQ.
+    ///
+    /// It is set in PCH-PIC for its inputs. The MSI vectors are always
+    /// edge-triggered, and the trigger modes of the timer and IPI are fixed.
+    fn set_trigger(irq_num: usize, mode: TriggerMode) -> IrqResult {
+        if irq_num & INTC_IRQ_BASE != 0 {
+            return Err(IrqError::Unsupported);
+        }
+        if irq_num >= MAX_IRQ_COUNT {
+            return Err(IrqError::InvalidInput);
+        }
+        if irq_num >= pch_pic::NUM_INPUTS {
+            return Err(IrqError::Unsupported);
+        }
+        pch_pic::set_edge(irq_num, mode == TriggerMode::Edge);
+        Ok(())
+    }
 }
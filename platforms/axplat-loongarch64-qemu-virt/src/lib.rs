@@ -22,11 +22,14 @@ pub mod config {
 
 mod boot;
 mod console;
+mod devices;
 mod init;
 #[cfg(feature = "irq")]
 mod irq;
 mod mem;
 #[cfg(feature = "smp")]
 mod mp;
+#[cfg(feature = "pci")]
+mod pci;
 mod power;
 mod time;
@@ -1,21 +1,96 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
 use axplat::mem::PhysAddr;
+use axplat::power::{CpuBootError, CpuBootResult, CpuState, CpuStateTable, topology};
+use loongArch64::iocsr::{iocsr_read_w, iocsr_write_w};
 use loongArch64::ipi::{csr_mail_send, send_ipi_single};
+use loongArch64::register::ecfg::{self, LineBasedInterrupt};
 
+use crate::config::plat::MAX_CPU_NUM;
 use crate::mem::phys_to_virt;
 
 const ACTION_BOOT_CPU: u32 = 1;
 
+const IOCSR_IPI_STATUS: usize = 0x1000;
+const IOCSR_IPI_ENABLE: usize = 0x1004;
+const IOCSR_IPI_CLEAR: usize = 0x100c;
+
 pub static mut SMP_BOOT_STACK_TOP: usize = 0;
 
+/// The power states of CPUs.
+pub static CPU_STATES: CpuStateTable<MAX_CPU_NUM> = CpuStateTable::new();
+
+/// Whether the CPU waits in [`park_current_cpu`] rather than in the firmware.
+static PARKED: [AtomicBool; MAX_CPU_NUM] = [const { AtomicBool::new(false) }; MAX_CPU_NUM];
+
+/// The boot stack tops (in virtual address) to restart the parked CPUs with,
+/// or `0` if not released.
+static RELEASE_STACK_TOP: [AtomicUsize; MAX_CPU_NUM] = [const { AtomicUsize::new(0) }; MAX_CPU_NUM];
+
+fn current_hw_id() -> usize {
+    let cpuid: usize;
+    unsafe { core::arch::asm!("csrrd {}, 0x20", out(reg) cpuid) };
+    cpuid & 0x1ff
+}
+
 /// Starts the given secondary CPU with its boot stack.
-pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
+///
+/// The CPU must be off, i.e., it has not been started, or has been taken
+/// offline by [`park_current_cpu`].
+pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) -> CpuBootResult {
     unsafe extern "C" {
         fn _start_secondary();
     }
+    let hw_id = topology::hw_id(cpu_id).ok_or(CpuBootError::NotPresent)?;
+    CPU_STATES.start_boot(cpu_id)?;
     let stack_top_virt_addr = phys_to_virt(stack_top).as_usize();
+    if PARKED[cpu_id].load(Ordering::Acquire) {
+        RELEASE_STACK_TOP[cpu_id].store(stack_top_virt_addr, Ordering::Release);
+    } else {
+        unsafe {
+            SMP_BOOT_STACK_TOP = stack_top_virt_addr;
+        }
+        csr_mail_send(_start_secondary as *const () as _, hw_id, 0);
+    }
+    send_ipi_single(hw_id, ACTION_BOOT_CPU);
+    Ok(())
+}
+
+/// Takes the current CPU offline.
+///
+/// It idles with only the IPI enabled, until it is released by
+/// [`start_secondary_cpu`]. Then it restarts from the secondary entry on the
+/// new boot stack.
+pub fn park_current_cpu() -> ! {
+    // The CPU has entered the kernel, so it must have been registered.
+    let cpu_id = topology::cpu_id(current_hw_id()).unwrap();
+    axcpu::asm::disable_irqs();
+    iocsr_write_w(IOCSR_IPI_ENABLE, u32::MAX);
+    ecfg::set_lie(LineBasedInterrupt::IPI);
+
+    PARKED[cpu_id].store(true, Ordering::Release);
+    CPU_STATES.set(cpu_id, CpuState::Off);
+    let stack_top = loop {
+        // `idle` wakes up on pending interrupts, even if they are disabled.
+        unsafe { core::arch::asm!("idle 0") };
+        iocsr_write_w(IOCSR_IPI_CLEAR, iocsr_read_w(IOCSR_IPI_STATUS));
+        let stack_top = RELEASE_STACK_TOP[cpu_id].swap(0, Ordering::Acquire);
+        if stack_top != 0 {
+            break stack_top;
+        }
+    };
+    PARKED[cpu_id].store(false, Ordering::Relaxed);
+
+    // The MMU is already set up, enter the kernel directly.
     unsafe {
-        SMP_BOOT_STACK_TOP = stack_top_virt_addr;
+        core::arch::asm!("
+            move        $sp, {stack_top}
+            la.global   $t0, {entry}
+            jirl        $zero, $t0, 0",
+            stack_top = in(reg) stack_top,
+            entry = sym axplat::call_secondary_main,
+            in("$a0") cpu_id,
+            options(noreturn),
+        )
     }
-    csr_mail_send(_start_secondary as *const () as _, cpu_id, 0);
-    send_ipi_single(cpu_id, ACTION_BOOT_CPU);
 }
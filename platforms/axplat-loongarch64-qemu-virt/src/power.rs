@@ -1,5 +1,5 @@
 use axplat::mem::pa;
-use axplat::power::PowerIf;
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
 
 struct PowerImpl;
 
@@ -11,8 +11,41 @@ impl PowerIf for PowerImpl {
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
-        crate::mp::start_secondary_cpu(cpu_id, pa!(stack_top_paddr));
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> axplat::power::CpuBootResult {
+        crate::mp::start_secondary_cpu(cpu_id, pa!(stack_top_paddr))
+    }
+
+    /// Takes the calling CPU offline.
+    ///
+    /// The CPU idles until it is woken up by the IPI sent in
+    /// [`cpu_boot`](Self::cpu_boot).
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        crate::mp::park_current_cpu()
+    }
+
+    /// Returns the power state of the given CPU.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        crate::mp::CPU_STATES.get(cpu_id)
+    }
+
+    /// Returns the idle states that CPUs can enter.
+    fn idle_states() -> &'static [IdleState] {
+        &[IdleState {
+            name: "IDLE",
+            entry_latency_ns: 1_000,
+            exit_latency_ns: 1_000,
+            min_residency_ns: 2_000,
+        }]
+    }
+
+    /// Enters the given idle state by the `idle` instruction.
+    ///
+    /// It wakes up on interrupts enabled in `ECFG`, even if interrupts are
+    /// disabled globally.
+    fn cpu_idle(_index: usize) {
+        axcpu::asm::halt();
     }
 
     /// Shutdown the whole system.
@@ -29,8 +62,31 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Resets the whole system by the reset register of the GED (generic
+    /// event device).
+    ///
+    /// Only cold reset is supported.
+    fn system_reset(_kind: ResetKind) -> ! {
+        const RESET_ADDR: *mut u8 =
+            crate::mem::phys_to_virt(pa!(crate::config::devices::GED_PADDR + 2)).as_mut_ptr();
+
+        info!("Resetting...");
+        unsafe { RESET_ADDR.write_volatile(0x42) };
+        axcpu::asm::halt();
+        warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns the reason for the last reset, which is not known.
+    fn reset_reason() -> Option<ResetReason> {
+        None
+    }
+
     /// Get the number of CPU cores available on this platform.
     fn cpu_num() -> usize {
-        crate::config::plat::MAX_CPU_NUM
+        use crate::config::plat::MAX_CPU_NUM;
+        axplat::fdt::cpu_num().map_or(MAX_CPU_NUM, |n| n.min(MAX_CPU_NUM))
     }
 }
@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use axplat::time::TimeIf;
 use lazyinit::LazyInit;
 use loongArch64::time::Time;
@@ -5,7 +7,7 @@ use loongArch64::time::Time;
 static NANOS_PER_TICK: LazyInit<u64> = LazyInit::new();
 
 /// RTC wall time offset in nanoseconds at monotonic time base.
-static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+static RTC_EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
 
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
@@ -18,6 +20,28 @@ pub(super) fn init_percpu() {
     }
 }
 
+/// Registers of the LS7A RTC.
+#[cfg(feature = "rtc")]
+mod ls7a_rtc {
+    use crate::mem::phys_to_virt;
+    use axplat::mem::{PhysAddr, pa};
+
+    pub const SYS_TOY_WRITE0: usize = 0x24;
+    pub const SYS_TOY_WRITE1: usize = 0x28;
+    pub const SYS_TOY_READ0: usize = 0x2C;
+    pub const SYS_TOY_READ1: usize = 0x30;
+    pub const SYS_RTCCTRL: usize = 0x40;
+
+    pub const TOY_ENABLE: u32 = 1 << 11;
+    pub const OSC_ENABLE: u32 = 1 << 8;
+
+    const LS7A_RTC_VADDR: PhysAddr = pa!(crate::config::devices::RTC_PADDR);
+
+    pub fn base_ptr() -> *mut u8 {
+        phys_to_virt(LS7A_RTC_VADDR).as_mut_ptr()
+    }
+}
+
 /// Initializes the RTC (Real-Time Clock) device.
 ///
 /// The QEMU-loongson3-virt platform supports loongson7a RTC device, whose documentation can be found at [Loongson7a RTC][1].
@@ -28,20 +52,10 @@ pub(super) fn init_percpu() {
 /// [2]: https://gitlab.com/qemu-project/qemu/-/blob/1cf9bc6eba7506ab6d9de635f224259225f63466/hw/rtc/ls7a_rtc.c
 #[cfg(feature = "rtc")]
 fn init_rtc() {
-    use crate::mem::phys_to_virt;
-    use axplat::mem::{PhysAddr, pa};
     use chrono::{TimeZone, Timelike, Utc};
+    use ls7a_rtc::*;
 
-    const SYS_TOY_READ0: usize = 0x2C;
-    const SYS_TOY_READ1: usize = 0x30;
-    const SYS_RTCCTRL: usize = 0x40;
-
-    const TOY_ENABLE: u32 = 1 << 11;
-    const OSC_ENABLE: u32 = 1 << 8;
-
-    const LS7A_RTC_VADDR: PhysAddr = pa!(crate::config::devices::RTC_PADDR);
-
-    let rtc_base_ptr = phys_to_virt(LS7A_RTC_VADDR).as_mut_ptr();
+    let rtc_base_ptr = base_ptr();
 
     fn extract_bits(value: u32, range: core::ops::Range<u32>) -> u32 {
         (value >> range.start) & ((1 << (range.end - range.start)) - 1)
@@ -72,10 +86,32 @@ fn init_rtc() {
         .unwrap();
 
     if let Some(epoch_time_nanos) = date_time.timestamp_nanos_opt() {
-        unsafe {
-            RTC_EPOCHOFFSET_NANOS =
-                epoch_time_nanos as u64 - TimeIfImpl::ticks_to_nanos(TimeIfImpl::current_ticks());
-        }
+        RTC_EPOCHOFFSET_NANOS.store(
+            epoch_time_nanos as u64 - TimeIfImpl::ticks_to_nanos(TimeIfImpl::current_ticks()),
+            Ordering::Release,
+        );
+    }
+}
+
+/// Writes the wall time (in nanoseconds since epoch) to the TOY counter.
+#[cfg(feature = "rtc")]
+fn write_rtc(nanos: u64) {
+    use chrono::{Datelike, TimeZone, Timelike, Utc};
+    use ls7a_rtc::*;
+
+    let date_time = Utc.timestamp_nanos(nanos as i64);
+    // The same layout as `SYS_TOY_READ0`, the sub-second field is left zero.
+    let toy_low = (date_time.month() << 26)
+        | (date_time.day() << 21)
+        | (date_time.hour() << 16)
+        | (date_time.minute() << 10)
+        | (date_time.second() << 4);
+    let toy_high = (date_time.year() - 1900) as u32;
+
+    let rtc_base_ptr = base_ptr();
+    unsafe {
+        (rtc_base_ptr.add(SYS_TOY_WRITE0) as *mut u32).write_volatile(toy_low);
+        (rtc_base_ptr.add(SYS_TOY_WRITE1) as *mut u32).write_volatile(toy_high);
     }
 }
 
@@ -98,7 +134,19 @@ impl TimeIf for TimeIfImpl {
 
     /// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
     fn epochoffset_nanos() -> u64 {
-        unsafe { RTC_EPOCHOFFSET_NANOS }
+        RTC_EPOCHOFFSET_NANOS.load(Ordering::Acquire)
+    }
+
+    /// Sets the wall time (also known as realtime) in nanoseconds since epoch.
+    ///
+    /// It also writes the new time back to the LS7A RTC if the `rtc` feature
+    /// is enabled.
+    fn set_wall_time_nanos(nanos: u64) {
+        let monotonic_nanos = Self::ticks_to_nanos(Self::current_ticks());
+        RTC_EPOCHOFFSET_NANOS.store(nanos.saturating_sub(monotonic_nanos), Ordering::Release);
+
+        #[cfg(feature = "rtc")]
+        write_rtc(nanos);
     }
 
     /// Converts hardware ticks to nanoseconds.
@@ -126,4 +174,24 @@ impl TimeIf for TimeIfImpl {
         tcfg::set_init_val(init_value as _);
         tcfg::set_en(true);
     }
+
+    /// Returns the IRQ number of the RTC alarm, or `None` if the platform has
+    /// no RTC alarm.
+    #[cfg(feature = "irq")]
+    fn rtc_alarm_irq() -> Option<usize> {
+        None
+    }
+
+    /// Sets the RTC alarm at the specified wall time (in nanoseconds since
+    /// epoch).
+    ///
+    /// It is not supported, as the alarm of the LS7A RTC is not implemented.
+    #[cfg(feature = "irq")]
+    fn set_rtc_alarm_nanos(_wall_time_nanos: u64) -> bool {
+        false
+    }
+
+    /// Cancels the pending RTC alarm, if any.
+    #[cfg(feature = "irq")]
+    fn cancel_rtc_alarm() {}
 }
@@ -53,14 +53,14 @@ unsafe extern "C" fn _start() -> ! {
         mv      a1, s1
         la      a2, {entry}
         add     a2, a2, s2
-        jalr    a2                      // call_main(cpu_id, dtb)
+        jalr    a2                      // call_main_hw(hartid, dtb)
         j       .",
         phys_virt_offset = const PHYS_VIRT_OFFSET,
         boot_stack_size = const BOOT_STACK_SIZE,
         boot_stack = sym BOOT_STACK,
         init_boot_page_table = sym init_boot_page_table,
         init_mmu = sym init_mmu,
-        entry = sym axplat::call_main,
+        entry = sym axplat::call_main_hw,
     )
 }
 
@@ -84,10 +84,10 @@ unsafe extern "C" fn _start_secondary() -> ! {
         mv      a0, s0
         la      a1, {entry}
         add     a1, a1, s1
-        jalr    a1                      // call_secondary_main(cpu_id)
+        jalr    a1                      // call_secondary_main_hw(hartid)
         j       .",
         phys_virt_offset = const PHYS_VIRT_OFFSET,
         init_mmu = sym init_mmu,
-        entry = sym axplat::call_secondary_main,
+        entry = sym axplat::call_secondary_main_hw,
     )
 }
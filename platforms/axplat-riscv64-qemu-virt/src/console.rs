@@ -48,4 +48,13 @@ impl ConsoleIf for ConsoleIfImpl {
         ))
         .value
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    ///
+    /// The SBI console has no IRQ, so it is always polled.
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize> {
+        None
+    }
 }
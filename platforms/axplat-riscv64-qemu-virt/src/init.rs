@@ -1,4 +1,7 @@
 use axplat::init::InitIf;
+use axplat::power::topology::{self, CpuTopology};
+
+use crate::config::plat::MAX_CPU_NUM;
 
 struct InitIfImpl;
 
@@ -7,9 +10,19 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _mbi: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
         axcpu::init::init_trap();
+        // Fall back to the static configuration if no valid DTB is present.
+        unsafe { axplat::fdt::init(dtb) }.ok();
+        crate::mem::init_early();
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init_from_fdt();
+        match axplat::fdt::cpus() {
+            Some(cpus) => topology::register_all(cpus.iter().copied()),
+            None => topology::register_all((0..MAX_CPU_NUM).map(CpuTopology::flat)),
+        };
         crate::time::init_early();
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -25,15 +38,22 @@ impl InitIf for InitIfImpl {
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _arg: usize) {
         #[cfg(feature = "irq")]
-        crate::irq::init_percpu();
+        {
+            crate::irq::init();
+            crate::irq::init_percpu(_cpu_id);
+        }
         crate::time::init_percpu();
+        #[cfg(all(feature = "irq", feature = "rtc"))]
+        crate::time::init_rtc_alarm();
+        #[cfg(feature = "pci")]
+        crate::pci::init();
     }
 
     /// Initializes the platform at the later stage for secondary cores.
     #[cfg(feature = "smp")]
     fn init_later_secondary(_cpu_id: usize) {
         #[cfg(feature = "irq")]
-        crate::irq::init_percpu();
+        crate::irq::init_percpu(_cpu_id);
         crate::time::init_percpu();
     }
 }
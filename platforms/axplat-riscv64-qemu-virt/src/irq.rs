@@ -1,7 +1,9 @@
-//! TODO: PLIC
+//! IRQs of the CPU (timer and IPI), and device IRQs through the PLIC.
 
-use axplat::irq::{HandlerTable, IpiTarget, IrqHandler, IrqIf};
-use core::sync::atomic::{AtomicPtr, Ordering};
+use axplat::irq::{
+    HandlerTable, HandlerToken, IpiTarget, IrqContextHandler, IrqError, IrqIf, IrqResult,
+    TriggerMode, stats,
+};
 use riscv::register::sie;
 use sbi_rt::HartMask;
 
@@ -18,15 +20,179 @@ pub(super) const S_TIMER: usize = INTC_IRQ_BASE + 5;
 /// Supervisor external interrupt in `scause`
 pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 
-static TIMER_HANDLER: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+/// The maximum number of CPU-side interrupts, indexed by the exception code
+/// in `scause`.
+const MAX_INTC_IRQ_COUNT: usize = 16;
 
-static IPI_HANDLER: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+/// Handlers of the CPU-side interrupts (timer and IPI).
+static INTC_HANDLER_TABLE: HandlerTable<MAX_INTC_IRQ_COUNT> = HandlerTable::new();
 
-/// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 1024;
+/// The maximum number of IRQs, which are the PLIC sources.
+pub const MAX_IRQ_COUNT: usize = plic::NUM_SOURCES;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// Platform-level interrupt controller (PLIC).
+///
+/// Each source is enabled separately for each context, which is a hart in a
+/// privilege mode. The supervisor mode of hart `i` is context `2 * i + 1` on
+/// QEMU virt.
+///
+/// See the [RISC-V PLIC Specification](https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc).
+mod plic {
+    use axplat::mem::{pa, phys_to_virt};
+    use kspin::SpinNoIrq;
+
+    use crate::config::devices::PLIC_PADDR;
+
+    /// The number of interrupt sources (`riscv,ndev` in the device tree, plus
+    /// the reserved source 0).
+    pub const NUM_SOURCES: usize = 96;
+
+    /// The highest priority supported.
+    pub const MAX_PRIORITY: u8 = 7;
+
+    const PRIORITY: usize = 0;
+    const ENABLE: usize = 0x2000;
+    const ENABLE_STRIDE: usize = 0x80;
+    const CONTEXT: usize = 0x20_0000;
+    const CONTEXT_STRIDE: usize = 0x1000;
+    const THRESHOLD: usize = 0;
+    const CLAIM: usize = 4;
+
+    /// Whether a source is enabled, and the CPUs it is routed to.
+    #[derive(Clone, Copy)]
+    struct Route {
+        enabled: bool,
+        cpu_mask: usize,
+    }
+
+    /// The routes of the sources, which also serializes the read-modify-write
+    /// of the enable registers. They are routed to CPU 0 by default.
+    static ROUTES: SpinNoIrq<[Route; NUM_SOURCES]> = SpinNoIrq::new(
+        [Route {
+            enabled: false,
+            cpu_mask: 1,
+        }; NUM_SOURCES],
+    );
+
+    fn reg(offset: usize) -> *mut u32 {
+        (phys_to_virt(pa!(PLIC_PADDR)).as_usize() + offset) as *mut u32
+    }
+
+    fn read(offset: usize) -> u32 {
+        unsafe { reg(offset).read_volatile() }
+    }
+
+    fn write(offset: usize, value: u32) {
+        unsafe { reg(offset).write_volatile(value) }
+    }
+
+    /// The supervisor mode context of the hart.
+    const fn context(hart_id: usize) -> usize {
+        2 * hart_id + 1
+    }
+
+    /// Sets the enable bits of `source` in the contexts of all CPUs, as given
+    /// by its route.
+    fn apply(source: usize, route: Route) {
+        let bit = 1 << (source % 32);
+        for cpu_id in 0..axplat::power::topology::cpu_num() {
+            let Some(hart_id) = axplat::power::topology::hw_id(cpu_id) else {
+                continue;
+            };
+            let offset = ENABLE + context(hart_id) * ENABLE_STRIDE + source / 32 * 4;
+            let value = read(offset);
+            write(
+                offset,
+                match route.enabled && route.cpu_mask & (1 << cpu_id) != 0 {
+                    true => value | bit,
+                    false => value & !bit,
+                },
+            );
+        }
+    }
+
+    /// Sets all sources to the lowest priority that can interrupt.
+    pub fn init() {
+        for source in 1..NUM_SOURCES {
+            write(PRIORITY + source * 4, 1);
+        }
+    }
+
+    /// Allows all sources with non-zero priorities to interrupt the hart.
+    pub fn init_percpu(hart_id: usize) {
+        write(CONTEXT + context(hart_id) * CONTEXT_STRIDE + THRESHOLD, 0);
+    }
+
+    pub fn set_enable(source: usize, enabled: bool) {
+        let mut routes = ROUTES.lock();
+        routes[source].enabled = enabled;
+        apply(source, routes[source]);
+    }
+
+    /// Routes `source` to the CPUs in `cpu_mask`, which must be present.
+    pub fn set_affinity(source: usize, cpu_mask: usize) {
+        let mut routes = ROUTES.lock();
+        routes[source].cpu_mask = cpu_mask;
+        apply(source, routes[source]);
+    }
+
+    /// Sets the priority of `source`, which is not higher than
+    /// [`MAX_PRIORITY`]. Priority `0` never interrupts.
+    pub fn set_priority(source: usize, priority: u8) {
+        write(PRIORITY + source * 4, priority as u32);
+    }
+
+    /// Claims the highest priority pending source of the hart, or returns
+    /// `None` if there is none.
+    pub fn claim(hart_id: usize) -> Option<usize> {
+        match read(CONTEXT + context(hart_id) * CONTEXT_STRIDE + CLAIM) {
+            0 => None,
+            source => Some(source as usize),
+        }
+    }
+
+    /// Signals the completion of a claimed source, so that it can interrupt
+    /// again.
+    pub fn complete(hart_id: usize, source: usize) {
+        write(
+            CONTEXT + context(hart_id) * CONTEXT_STRIDE + CLAIM,
+            source as u32,
+        );
+    }
+}
+
+/// Whether the IRQ is a PLIC source, rather than an interrupt of the CPU.
+fn is_plic_source(irq: usize) -> bool {
+    (1..plic::NUM_SOURCES).contains(&irq)
+}
+
+/// Handles the device IRQs pending in the PLIC for the current CPU.
+///
+/// The current CPU is found by [`axplat::power::this_cpu_id`], so IRQs
+/// routed to CPUs other than CPU 0 require the kernel to set it up by
+/// [`axplat::power::set_this_cpu_id_fn`].
+fn handle_plic() {
+    let cpu_id = axplat::power::this_cpu_id();
+    let Some(hart_id) = axplat::power::topology::hw_id(cpu_id) else {
+        stats::record_spurious();
+        return;
+    };
+    let Some(irq) = plic::claim(hart_id) else {
+        // Claimed by another hart, or withdrawn by the device.
+        stats::record_spurious();
+        return;
+    };
+    trace!("IRQ {}", irq);
+    let handled = IRQ_HANDLER_TABLE.handle(irq);
+    stats::record_irq(irq, handled);
+    if !handled {
+        warn!("Unhandled IRQ {}", irq);
+    }
+    plic::complete(hart_id, irq);
+}
+
 macro_rules! with_cause {
     ($cause: expr, @S_TIMER => $timer_op: expr, @S_SOFT => $ipi_op: expr, @S_EXT => $ext_op: expr, @EX_IRQ => $plic_op: expr $(,)?) => {
         match $cause {
@@ -46,7 +212,15 @@ macro_rules! with_cause {
     };
 }
 
-pub(super) fn init_percpu() {
+/// Initializes the PLIC, with all device IRQs disabled and routed to CPU 0.
+pub(super) fn init() {
+    plic::init();
+}
+
+pub(super) fn init_percpu(cpu_id: usize) {
+    if let Some(hart_id) = axplat::power::topology::hw_id(cpu_id) {
+        plic::init_percpu(hart_id);
+    }
     // enable soft interrupts, timer interrupts, and external interrupts
     unsafe {
         sie::set_ssoft();
@@ -60,15 +234,21 @@ struct IrqIfImpl;
 #[impl_interface]
 impl IrqIf for IrqIfImpl {
     /// Enables or disables the given IRQ.
-    fn set_enable(irq: usize, _enabled: bool) {
-        // TODO: set enable in PLIC
-        warn!("set_enable is not implemented for IRQ {}", irq);
+    ///
+    /// Device IRQs are enabled in the PLIC for the CPUs they are routed to.
+    /// The interrupts of the CPU are always enabled.
+    fn set_enable(irq: usize, enabled: bool) {
+        if is_plic_source(irq) {
+            plic::set_enable(irq, enabled);
+        } else if irq & INTC_IRQ_BASE == 0 {
+            warn!("set_enable: invalid irq {}", irq);
+        }
     }
 
-    /// Registers an IRQ handler for the given IRQ.
+    /// Registers an IRQ handler with context for the given IRQ.
     ///
-    /// It also enables the IRQ if the registration succeeds. It returns `false` if
-    /// the registration failed.
+    /// It also enables the IRQ if the registration succeeds. It returns the
+    /// token to unregister the handler, or `None` if the registration failed.
     ///
     /// The `irq` parameter has the following semantics
     /// 1. If its highest bit is 1, it means it is an interrupt on the CPU side. Its
@@ -78,55 +258,53 @@ impl IrqIf for IrqIfImpl {
     /// be obtained from PLIC.
     /// 2. If its highest bit is 0, it means it is an interrupt on the device side,
     /// and its value is equal to the IRQ number provided by PLIC.
-    fn register(irq: usize, handler: IrqHandler) -> bool {
+    fn register_with_context(
+        irq: usize,
+        handler: IrqContextHandler,
+        ctx: usize,
+    ) -> Option<HandlerToken> {
         with_cause!(
             irq,
-            @S_TIMER => TIMER_HANDLER.compare_exchange(core::ptr::null_mut(), handler as *mut _, Ordering::AcqRel, Ordering::Acquire).is_ok(),
-            @S_SOFT => IPI_HANDLER.compare_exchange(core::ptr::null_mut(), handler as *mut _, Ordering::AcqRel, Ordering::Acquire).is_ok(),
+            @S_TIMER => INTC_HANDLER_TABLE.register_handler_with_context(irq & !INTC_IRQ_BASE, handler, ctx),
+            @S_SOFT => INTC_HANDLER_TABLE.register_handler_with_context(irq & !INTC_IRQ_BASE, handler, ctx),
             @S_EXT => {
                 warn!("External IRQ should be got from PLIC, not scause");
-                false
+                None
             },
             @EX_IRQ => {
-                if IRQ_HANDLER_TABLE.register_handler(irq, handler) {
-                    Self::set_enable(irq, true);
-                    true
-                } else {
-                    warn!("register handler for External IRQ {} failed", irq);
-                    false
+                let token = IRQ_HANDLER_TABLE.register_handler_with_context(irq, handler, ctx);
+                match token {
+                    Some(_) => Self::set_enable(irq, true),
+                    None => warn!("register handler for External IRQ {} failed", irq),
                 }
+                token
             }
         )
     }
 
-    /// Unregisters the IRQ handler for the given IRQ.
+    /// Unregisters the IRQ handler registered with the given token for the
+    /// given IRQ.
     ///
-    /// It also disables the IRQ if the unregistration succeeds. It returns the
-    /// existing handler if it is registered, `None` otherwise.
-    fn unregister(irq: usize) -> Option<IrqHandler> {
+    /// The IRQ is disabled only if the last handler is unregistered. It
+    /// returns `true` if the handler was registered, `false` otherwise.
+    fn unregister(irq: usize, token: HandlerToken) -> bool {
         with_cause!(
             irq,
-            @S_TIMER => {
-                let handler = TIMER_HANDLER.swap(core::ptr::null_mut(), Ordering::AcqRel);
-                if !handler.is_null() {
-                    Some(unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler) })
-                } else {
-                    None
-                }
-            },
-            @S_SOFT => {
-                let handler = IPI_HANDLER.swap(core::ptr::null_mut(), Ordering::AcqRel);
-                if !handler.is_null() {
-                    Some(unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler) })
-                } else {
-                    None
-                }
-            },
+            @S_TIMER => INTC_HANDLER_TABLE.unregister_handler(irq & !INTC_IRQ_BASE, token),
+            @S_SOFT => INTC_HANDLER_TABLE.unregister_handler(irq & !INTC_IRQ_BASE, token),
             @S_EXT => {
                 warn!("External IRQ should be got from PLIC, not scause");
-                None
+                false
             },
-            @EX_IRQ => IRQ_HANDLER_TABLE.unregister_handler(irq)
+            @EX_IRQ => {
+                if !IRQ_HANDLER_TABLE.unregister_handler(irq, token) {
+                    return false;
+                }
+                if !IRQ_HANDLER_TABLE.is_registered(irq) {
+                    Self::set_enable(irq, false);
+                }
+                true
+            }
         )
     }
 
@@ -140,29 +318,18 @@ impl IrqIf for IrqIfImpl {
             irq,
             @S_TIMER => {
                 trace!("IRQ: timer");
-                let handler = TIMER_HANDLER.load(Ordering::Acquire);
-                if !handler.is_null() {
-                    // SAFETY: The handler is guaranteed to be a valid function pointer.
-                    unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler)() };
-                }
+                let handled = INTC_HANDLER_TABLE.handle_with_irq(irq & !INTC_IRQ_BASE, irq);
+                stats::record_irq(irq, handled);
             },
             @S_SOFT => {
                 trace!("IRQ: IPI");
-                let handler = IPI_HANDLER.load(Ordering::Acquire);
-                if !handler.is_null() {
-                    // SAFETY: The handler is guaranteed to be a valid function pointer.
-                    unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler)() };
-                }
+                let handled = INTC_HANDLER_TABLE.handle_with_irq(irq & !INTC_IRQ_BASE, irq);
+                stats::record_irq(irq, handled);
                 unsafe {
                     riscv::register::sip::clear_ssoft();
                 }
             },
-            @S_EXT => {
-                // TODO: get IRQ number from PLIC
-                if !IRQ_HANDLER_TABLE.handle(0) {
-                    warn!("Unhandled IRQ {}", 0);
-                }
-            },
+            @S_EXT => handle_plic(),
             @EX_IRQ => {
                 unreachable!("Device-side IRQs should be handled by triggering the External Interrupt.");
             }
@@ -171,29 +338,65 @@ impl IrqIf for IrqIfImpl {
 
     /// Sends an inter-processor interrupt (IPI) to the specified target CPU or all CPUs.
     fn send_ipi(_irq_num: usize, target: IpiTarget) {
-        match target {
-            IpiTarget::Current { cpu_id } => {
-                let res = sbi_rt::send_ipi(HartMask::from_mask_base(1 << cpu_id, 0));
-                if res.is_err() {
-                    warn!("send_ipi failed: {:?}", res);
-                }
-            }
-            IpiTarget::Other { cpu_id } => {
-                let res = sbi_rt::send_ipi(HartMask::from_mask_base(1 << cpu_id, 0));
-                if res.is_err() {
-                    warn!("send_ipi failed: {:?}", res);
-                }
+        stats::record_ipi_sent(S_SOFT);
+        let send_to = |cpu_id: usize| {
+            let Some(hart_id) = axplat::power::topology::hw_id(cpu_id) else {
+                warn!("send_ipi: CPU {cpu_id} is not present");
+                return;
+            };
+            let res = sbi_rt::send_ipi(HartMask::from_mask_base(1, hart_id));
+            if res.is_err() {
+                warn!("send_ipi to hart {hart_id} failed: {:?}", res);
             }
+        };
+        match target {
+            IpiTarget::Current { cpu_id } | IpiTarget::Other { cpu_id } => send_to(cpu_id),
             IpiTarget::AllExceptCurrent { cpu_id, cpu_num } => {
-                for i in 0..cpu_num {
-                    if i != cpu_id {
-                        let res = sbi_rt::send_ipi(HartMask::from_mask_base(1 << i, 0));
-                        if res.is_err() {
-                            warn!("send_ipi_all_others failed: {:?}", res);
-                        }
-                    }
+                for i in (0..cpu_num).filter(|&i| i != cpu_id) {
+                    send_to(i);
                 }
             }
         }
     }
+
+    /// Routes the given IRQ to the CPUs in `cpu_mask`.
+    ///
+    /// A device IRQ can be routed to any present CPUs, and is handled by the
+    /// one that claims it first. Routing to CPUs other than CPU 0 requires the
+    /// kernel to set up [`axplat::power::set_this_cpu_id_fn`]. The timer and
+    /// IPI are local to each CPU, and can not be routed.
+    fn set_affinity(irq: usize, cpu_mask: usize) -> IrqResult {
+        if irq & INTC_IRQ_BASE != 0 {
+            return Err(IrqError::Unsupported);
+        }
+        // The number of CPUs up to the highest one in the mask.
+        let cpu_end = (usize::BITS - cpu_mask.leading_zeros()) as usize;
+        if !is_plic_source(irq) || cpu_mask == 0 || cpu_end > axplat::power::topology::cpu_num() {
+            return Err(IrqError::InvalidInput);
+        }
+        plic::set_affinity(irq, cpu_mask);
+        Ok(())
+    }
+
+    /// Sets the priority of the given IRQ.
+    ///
+    /// For the PLIC, a higher value means a higher priority, from `1` to
+    /// `7`. Priority `0` keeps the IRQ from interrupting at all. The
+    /// priorities of the timer and IPI are fixed.
+    fn set_priority(irq: usize, priority: u8) -> IrqResult {
+        if irq & INTC_IRQ_BASE != 0 {
+            return Err(IrqError::Unsupported);
+        }
+        if !is_plic_source(irq) || priority > plic::MAX_PRIORITY {
+            return Err(IrqError::InvalidInput);
+        }
+        plic::set_priority(irq, priority);
+        Ok(())
+    }
+
+    /// Sets the trigger mode (edge or level) of the given IRQ.
+    fn set_trigger(_irq: usize, _mode: TriggerMode) -> IrqResult {
+        // PLIC does not support configuring the trigger mode.
+        Err(IrqError::Unsupported)
+    }
 }
@@ -7,10 +7,13 @@ extern crate axplat;
 
 mod boot;
 mod console;
+mod devices;
 mod init;
 #[cfg(feature = "irq")]
 mod irq;
 mod mem;
+#[cfg(feature = "pci")]
+mod pci;
 mod power;
 mod time;
 
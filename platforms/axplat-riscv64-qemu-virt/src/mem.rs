@@ -1,10 +1,78 @@
-use axplat::mem::{MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axplat::mem::{DmaRange, DmaZone, MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
 
 use crate::config::devices::MMIO_RANGES;
 use crate::config::plat::{
-    KERNEL_BASE_PADDR, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET,
+    KERNEL_BASE_PADDR, PHYS_BUS_OFFSET, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET,
 };
 
+/// The cache block size of the Zicbom extension, or `0` if it is not
+/// supported.
+static CBOM_BLOCK_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Detects the Zicbom extension of the first CPU in the device tree.
+pub fn init_early() {
+    let Some(fdt) = axplat::fdt::get() else {
+        return;
+    };
+    let Some(cpu) = fdt.all_nodes().find(|n| n.name.starts_with("cpu@")) else {
+        return;
+    };
+    let in_extensions = cpu
+        .property("riscv,isa-extensions")
+        .is_some_and(|p| p.value.split(|&c| c == 0).any(|ext| ext == b"zicbom"));
+    let in_isa = cpu
+        .property("riscv,isa")
+        .and_then(|p| p.as_str())
+        .is_some_and(|isa| isa.split('_').any(|ext| ext == "zicbom"));
+    if in_extensions || in_isa {
+        let block_size = cpu
+            .property("riscv,cbom-block-size")
+            .and_then(|p| p.as_usize());
+        CBOM_BLOCK_SIZE.store(block_size.unwrap_or(0), Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum CacheOp {
+    Clean,
+    Invalidate,
+    Flush,
+}
+
+/// Applies the Zicbom operation to each cache block that covers the range.
+///
+/// The blocks partially covered by the range are flushed instead of
+/// invalidated. It does nothing if Zicbom is not supported.
+fn cbo_range(vaddr: VirtAddr, size: usize, op: CacheOp) {
+    let block_size = CBOM_BLOCK_SIZE.load(Ordering::Relaxed);
+    if block_size == 0 || size == 0 {
+        return;
+    }
+    let (start, end) = (vaddr.as_usize(), vaddr.as_usize() + size);
+    let mut block = start & !(block_size - 1);
+    while block < end {
+        let op = match op {
+            CacheOp::Invalidate if block < start || block + block_size > end => CacheOp::Flush,
+            op => op,
+        };
+        // The instructions are encoded by `.insn`, because the assembler may
+        // not enable Zicbom.
+        unsafe {
+            match op {
+                CacheOp::Clean => core::arch::asm!(".insn i 0x0f, 2, x0, {}, 1", in(reg) block),
+                CacheOp::Invalidate => {
+                    core::arch::asm!(".insn i 0x0f, 2, x0, {}, 0", in(reg) block)
+                }
+                CacheOp::Flush => core::arch::asm!(".insn i 0x0f, 2, x0, {}, 2", in(reg) block),
+            }
+        }
+        block += block_size;
+    }
+    unsafe { core::arch::asm!("fence rw, rw") };
+}
+
 struct MemIfImpl;
 
 #[impl_interface]
@@ -14,12 +82,14 @@ impl MemIf for MemIfImpl {
     /// All memory ranges except reserved ranges (including the kernel loaded
     /// range) are free for allocation.
     fn phys_ram_ranges() -> &'static [RawRange] {
-        // TODO: paser dtb to get the available memory ranges
-        // We can't directly use `PHYS_MEMORY_BASE` here, because it may has been used by sbi.
-        &[(
-            KERNEL_BASE_PADDR,
-            PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE - KERNEL_BASE_PADDR,
-        )]
+        // SBI reports the memory used by itself in `/reserved-memory`.
+        axplat::fdt::phys_ram_ranges().unwrap_or(
+            // We can't directly use `PHYS_MEMORY_BASE` here, because it may has been used by sbi.
+            &[(
+                KERNEL_BASE_PADDR,
+                PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE - KERNEL_BASE_PADDR,
+            )],
+        )
     }
 
     /// Returns all reserved physical memory ranges on the platform.
@@ -30,12 +100,12 @@ impl MemIf for MemIfImpl {
     /// Note that the ranges returned should not include the range where the
     /// kernel is loaded.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[]
+        axplat::fdt::reserved_phys_ram_ranges().unwrap_or(&[])
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
-        &MMIO_RANGES
+        axplat::fdt::mmio_ranges().unwrap_or(&MMIO_RANGES)
     }
 
     /// Translates a physical address to a virtual address.
@@ -47,4 +117,48 @@ impl MemIf for MemIfImpl {
     fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
         pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
     }
+
+    /// Whether the DMA of devices is coherent with the CPU caches.
+    ///
+    /// QEMU devices access memory coherently.
+    fn dma_coherent() -> bool {
+        true
+    }
+
+    /// Cleans the data cache blocks that cover the given virtual address
+    /// range by `cbo.clean`, if Zicbom is supported.
+    fn cache_clean_range(vaddr: VirtAddr, size: usize) {
+        cbo_range(vaddr, size, CacheOp::Clean)
+    }
+
+    /// Invalidates the data cache blocks that cover the given virtual address
+    /// range by `cbo.inval`, if Zicbom is supported.
+    fn cache_invalidate_range(vaddr: VirtAddr, size: usize) {
+        cbo_range(vaddr, size, CacheOp::Invalidate)
+    }
+
+    /// Cleans and invalidates the data cache blocks that cover the given
+    /// virtual address range by `cbo.flush`, if Zicbom is supported.
+    fn cache_flush_range(vaddr: VirtAddr, size: usize) {
+        cbo_range(vaddr, size, CacheOp::Flush)
+    }
+
+    /// Returns the windows through which devices access physical memory by
+    /// DMA.
+    ///
+    /// All physical memory is accessed at the bus addresses offset by
+    /// `PHYS_BUS_OFFSET`.
+    fn dma_ranges() -> &'static [DmaRange] {
+        &[DmaRange::new(0, PHYS_BUS_OFFSET, usize::MAX)]
+    }
+
+    /// Returns the end physical address (exclusive) of the given DMA zone.
+    ///
+    /// There is no device more restricted than those with 32-bit DMA
+    /// addresses.
+    fn dma_zone_end(zone: DmaZone) -> Option<usize> {
+        match zone {
+            DmaZone::Dma | DmaZone::Dma32 => Some(0x1_0000_0000),
+        }
+    }
 }
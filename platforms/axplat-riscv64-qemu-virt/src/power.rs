@@ -1,4 +1,9 @@
-use axplat::power::PowerIf;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
+
+/// Whether the SBI implementation fails to suspend the hart.
+static SUSPEND_FAILED: AtomicBool = AtomicBool::new(false);
 
 struct PowerImpl;
 
@@ -10,17 +15,91 @@ impl PowerIf for PowerImpl {
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> axplat::power::CpuBootResult {
         use axplat::mem::{va, virt_to_phys};
+        use axplat::power::{CpuBootError, topology};
         unsafe extern "C" {
             fn _start_secondary();
         }
+        let hart_id = topology::hw_id(cpu_id).ok_or(CpuBootError::NotPresent)?;
         if sbi_rt::probe_extension(sbi_rt::Hsm).is_unavailable() {
             warn!("HSM SBI extension is not supported for current SEE.");
-            return;
+            return Err(CpuBootError::Denied);
         }
         let entry = virt_to_phys(va!(_start_secondary as *const () as usize));
-        sbi_rt::hart_start(cpu_id, entry.as_usize(), stack_top_paddr);
+        let ret = sbi_rt::hart_start(hart_id, entry.as_usize(), stack_top_paddr);
+        ret.into_result().map(|_| ()).map_err(|e| {
+            error!("failed to boot hart {} ({:?})", hart_id, e);
+            match e {
+                sbi_rt::Error::InvalidParam => CpuBootError::NotPresent,
+                sbi_rt::Error::AlreadyAvailable => CpuBootError::AlreadyOn,
+                _ => CpuBootError::Denied,
+            }
+        })
+    }
+
+    /// Powers off the calling CPU by the SBI HSM extension.
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        // It does not return on success.
+        let ret = sbi_rt::hart_stop();
+        error!("failed to stop the hart ({:?})", ret);
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns the power state of the given CPU by the SBI HSM extension.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        use axplat::power::CpuState;
+        const HART_STATE_STOPPED: usize = 1;
+        const HART_STATE_START_PENDING: usize = 2;
+
+        let hart_id = axplat::power::topology::hw_id(cpu_id)?;
+        match sbi_rt::hart_get_status(hart_id).into_result().ok()? {
+            HART_STATE_STOPPED => Some(CpuState::Off),
+            HART_STATE_START_PENDING => Some(CpuState::OnPending),
+            // The hart is started, or still running when stopping, suspended
+            // or resuming.
+            _ => Some(CpuState::On),
+        }
+    }
+
+    /// Returns the idle states that CPUs can enter.
+    fn idle_states() -> &'static [IdleState] {
+        &[
+            IdleState {
+                name: "WFI",
+                entry_latency_ns: 1_000,
+                exit_latency_ns: 1_000,
+                min_residency_ns: 2_000,
+            },
+            IdleState {
+                name: "SBI retentive suspend",
+                entry_latency_ns: 10_000,
+                exit_latency_ns: 10_000,
+                min_residency_ns: 100_000,
+            },
+        ]
+    }
+
+    /// Enters the given idle state by WFI or the SBI HSM extension.
+    ///
+    /// Both of them wake up on interrupts enabled in `sie`, even if interrupts
+    /// are disabled in `sstatus`.
+    fn cpu_idle(index: usize) {
+        if index == 1 && !SUSPEND_FAILED.load(Ordering::Relaxed) {
+            // The retentive suspend returns like WFI, without the resume address.
+            if sbi_rt::hart_suspend(sbi_rt::Retentive, 0, 0)
+                .into_result()
+                .is_ok()
+            {
+                return;
+            }
+            SUSPEND_FAILED.store(true, Ordering::Relaxed);
+        }
+        axcpu::asm::halt();
     }
 
     /// Shutdown the whole system.
@@ -33,8 +112,29 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Resets the whole system by the SBI SRST extension.
+    fn system_reset(kind: ResetKind) -> ! {
+        info!("Resetting ({kind:?})...");
+        if kind == ResetKind::Warm {
+            sbi_rt::system_reset(sbi_rt::WarmReboot, sbi_rt::NoReason);
+            // Warm reboot is optional in SBI, try a cold one.
+            warn!("Warm reset is not supported, falling back to cold reset");
+        }
+        sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason);
+        warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns the reason for the last reset, which is not known.
+    fn reset_reason() -> Option<ResetReason> {
+        None
+    }
+
     /// Get the number of CPU cores available on this platform.
     fn cpu_num() -> usize {
-        crate::config::plat::MAX_CPU_NUM
+        use crate::config::plat::MAX_CPU_NUM;
+        axplat::fdt::cpu_num().map_or(MAX_CPU_NUM, |n| n.min(MAX_CPU_NUM))
     }
 }
@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use riscv::register::time;
 
 use axplat::time::TimeIf;
@@ -6,7 +8,7 @@ const NANOS_PER_SEC: u64 = 1_000_000_000;
 
 const NANOS_PER_TICK: u64 = NANOS_PER_SEC / crate::config::devices::TIMER_FREQUENCY as u64;
 /// RTC wall time offset in nanoseconds at monotonic time base.
-static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+static RTC_EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
 
 pub(super) fn init_early() {
     #[cfg(feature = "rtc")]
@@ -24,13 +26,90 @@ pub(super) fn init_early() {
         let epoch_time_nanos =
             Rtc::new(phys_to_virt(GOLDFISH_BASE).as_usize()).get_unix_timestamp() * 1_000_000_000;
 
-        unsafe {
-            RTC_EPOCHOFFSET_NANOS =
-                epoch_time_nanos - TimeIfImpl::ticks_to_nanos(TimeIfImpl::current_ticks());
+        RTC_EPOCHOFFSET_NANOS.store(
+            epoch_time_nanos - TimeIfImpl::ticks_to_nanos(TimeIfImpl::current_ticks()),
+            Ordering::Release,
+        );
+    }
+}
+
+/// Alarm of the goldfish RTC.
+#[cfg(all(feature = "irq", feature = "rtc"))]
+mod rtc_alarm {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use axplat::mem::{pa, phys_to_virt};
+
+    use crate::config::devices::{RTC_IRQ, RTC_PADDR};
+
+    const ALARM_LOW: usize = 0x08;
+    const ALARM_HIGH: usize = 0x0c;
+    const IRQ_ENABLED: usize = 0x10;
+    const CLEAR_ALARM: usize = 0x14;
+    const CLEAR_INTERRUPT: usize = 0x1c;
+
+    static ENABLED: AtomicBool = AtomicBool::new(false);
+
+    fn write_reg(reg: usize, value: u32) {
+        let base = phys_to_virt(pa!(RTC_PADDR)).as_usize();
+        unsafe { ((base + reg) as *mut u32).write_volatile(value) };
+    }
+
+    fn ack(_irq: usize, _ctx: usize) -> bool {
+        // The goldfish RTC raises the IRQ only for the alarm.
+        write_reg(CLEAR_INTERRUPT, 1);
+        true
+    }
+
+    pub fn init() {
+        if RTC_PADDR != 0 && axplat::irq::register_with_context(RTC_IRQ, ack, 0).is_some() {
+            ENABLED.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn irq() -> Option<usize> {
+        ENABLED.load(Ordering::Acquire).then_some(RTC_IRQ)
+    }
+
+    pub fn set(wall_time_nanos: u64) -> bool {
+        if irq().is_none() {
+            return false;
+        }
+        write_reg(ALARM_HIGH, (wall_time_nanos >> 32) as u32);
+        // Writing the low half arms the alarm.
+        write_reg(ALARM_LOW, wall_time_nanos as u32);
+        write_reg(IRQ_ENABLED, 1);
+        true
+    }
+
+    pub fn cancel() {
+        if irq().is_some() {
+            write_reg(IRQ_ENABLED, 0);
+            write_reg(CLEAR_ALARM, 1);
+            write_reg(CLEAR_INTERRUPT, 1);
         }
     }
 }
 
+#[cfg(all(feature = "irq", not(feature = "rtc")))]
+mod rtc_alarm {
+    pub fn irq() -> Option<usize> {
+        None
+    }
+
+    pub fn set(_wall_time_nanos: u64) -> bool {
+        false
+    }
+
+    pub fn cancel() {}
+}
+
+/// Enables the RTC alarm IRQ.
+#[cfg(all(feature = "irq", feature = "rtc"))]
+pub(super) fn init_rtc_alarm() {
+    rtc_alarm::init();
+}
+
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
     sbi_rt::set_timer(0);
@@ -57,7 +136,24 @@ impl TimeIf for TimeIfImpl {
 
     /// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
     fn epochoffset_nanos() -> u64 {
-        unsafe { RTC_EPOCHOFFSET_NANOS }
+        RTC_EPOCHOFFSET_NANOS.load(Ordering::Acquire)
+    }
+
+    /// Sets the wall time (also known as realtime) in nanoseconds since epoch.
+    ///
+    /// It also writes the new time back to the goldfish RTC if present.
+    fn set_wall_time_nanos(nanos: u64) {
+        let monotonic_nanos = Self::ticks_to_nanos(Self::current_ticks());
+        RTC_EPOCHOFFSET_NANOS.store(nanos.saturating_sub(monotonic_nanos), Ordering::Release);
+
+        #[cfg(feature = "rtc")]
+        if crate::config::devices::RTC_PADDR != 0 {
+            use axplat::mem::{pa, phys_to_virt};
+            use riscv_goldfish::Rtc;
+
+            let rtc_base = phys_to_virt(pa!(crate::config::devices::RTC_PADDR));
+            Rtc::new(rtc_base.as_usize()).set_unix_timestamp(nanos / NANOS_PER_SEC);
+        }
     }
 
     /// Set a one-shot timer.
@@ -67,4 +163,26 @@ impl TimeIf for TimeIfImpl {
     fn set_oneshot_timer(deadline_ns: u64) {
         sbi_rt::set_timer(Self::nanos_to_ticks(deadline_ns));
     }
+
+    /// Returns the IRQ number of the RTC alarm, or `None` if the platform has
+    /// no RTC alarm.
+    #[cfg(feature = "irq")]
+    fn rtc_alarm_irq() -> Option<usize> {
+        rtc_alarm::irq()
+    }
+
+    /// Sets the RTC alarm at the specified wall time (in nanoseconds since
+    /// epoch).
+    ///
+    /// It uses the goldfish RTC, whose resolution is one nanosecond.
+    #[cfg(feature = "irq")]
+    fn set_rtc_alarm_nanos(wall_time_nanos: u64) -> bool {
+        rtc_alarm::set(wall_time_nanos)
+    }
+
+    /// Cancels the pending RTC alarm, if any.
+    #[cfg(feature = "irq")]
+    fn cancel_rtc_alarm() {
+        rtc_alarm::cancel()
+    }
 }
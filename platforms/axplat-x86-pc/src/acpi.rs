@@ -0,0 +1,164 @@
+//! Minimal ACPI table parsing, to find CPUs and interrupt source overrides in
+//! the MADT.
+
+use axplat::mem::{pa, phys_to_virt};
+use axplat::power::topology::{self, CpuTopology};
+use raw_cpuid::{CpuId, TopologyType};
+
+use crate::config::plat::MAX_CPU_NUM;
+
+const SDT_HEADER_SIZE: usize = 36;
+const MADT_ENTRIES_OFFSET: usize = 44;
+
+const MADT_LOCAL_APIC: u8 = 0;
+#[cfg(all(feature = "irq", feature = "pci"))]
+const MADT_INT_SRC_OVERRIDE: u8 = 2;
+const MADT_LOCAL_X2APIC: u8 = 9;
+const MADT_CPU_ENABLED: u32 = 1;
+
+/// Reads a value at the given physical address, which is mapped by the boot
+/// page table.
+fn read<T: Copy>(paddr: usize) -> T {
+    let ptr = phys_to_virt(pa!(paddr)).as_ptr() as *const T;
+    unsafe { ptr.read_unaligned() }
+}
+
+fn checksum_ok(paddr: usize, len: usize) -> bool {
+    (0..len).fold(0u8, |sum, i| sum.wrapping_add(read::<u8>(paddr + i))) == 0
+}
+
+/// Finds the RSDP in the first 1KB of the EBDA, or the BIOS read-only memory
+/// area.
+fn find_rsdp() -> Option<usize> {
+    let ebda = (read::<u16>(0x40e) as usize) << 4;
+    [(ebda, 0x400), (0xe_0000, 0x2_0000)]
+        .into_iter()
+        .flat_map(|(start, len)| (start..start + len).step_by(16))
+        .find(|&p| read::<[u8; 8]>(p) == *b"RSD PTR " && checksum_ok(p, 20))
+}
+
+/// Finds the system description table with the given signature by the RSDT
+/// or XSDT.
+fn find_table(signature: &[u8; 4]) -> Option<usize> {
+    let rsdp = find_rsdp()?;
+    let xsdt = if read::<u8>(rsdp + 15) >= 2 {
+        read::<u64>(rsdp + 24) as usize
+    } else {
+        0
+    };
+    let (root, entry_size) = if xsdt != 0 {
+        (xsdt, 8)
+    } else {
+        (read::<u32>(rsdp + 16) as usize, 4)
+    };
+    let len = read::<u32>(root + 4) as usize;
+    (root + SDT_HEADER_SIZE..root + len)
+        .step_by(entry_size)
+        .map(|p| match entry_size {
+            8 => read::<u64>(p) as usize,
+            _ => read::<u32>(p) as usize,
+        })
+        .find(|&table| read::<[u8; 4]>(table) == *signature)
+}
+
+/// Calls `f` with the type and the physical address of each entry in the
+/// MADT.
+///
+/// Returns `false` if the MADT is not found.
+fn for_each_madt_entry(mut f: impl FnMut(u8, usize)) -> bool {
+    let Some(madt) = find_table(b"APIC") else {
+        return false;
+    };
+    let end = madt + read::<u32>(madt + 4) as usize;
+    let mut entry = madt + MADT_ENTRIES_OFFSET;
+    while entry + 2 <= end {
+        let len = read::<u8>(entry + 1) as usize;
+        if len < 2 {
+            break;
+        }
+        f(read::<u8>(entry), entry);
+        entry += len;
+    }
+    true
+}
+
+/// Calls `f` with the APIC IDs of the enabled CPUs in the MADT, in the order
+/// they appear.
+///
+/// Returns `false` if the MADT is not found.
+fn for_each_madt_cpu(mut f: impl FnMut(usize)) -> bool {
+    for_each_madt_entry(|ty, entry| match ty {
+        MADT_LOCAL_APIC if read::<u32>(entry + 4) & MADT_CPU_ENABLED != 0 => {
+            f(read::<u8>(entry + 3) as usize);
+        }
+        MADT_LOCAL_X2APIC if read::<u32>(entry + 8) & MADT_CPU_ENABLED != 0 => {
+            f(read::<u32>(entry + 4) as usize);
+        }
+        _ => {}
+    })
+}
+
+/// Returns whether the given ISA IRQ is active-low and level-triggered, by
+/// its interrupt source override in the MADT.
+///
+/// `default` is the polarity and trigger mode of the bus that the interrupt
+/// comes from, which is used if there is no override, or the override
+/// conforms to the bus.
+#[cfg(all(feature = "irq", feature = "pci"))]
+pub fn isa_irq_mode(irq: u8, default: (bool, bool)) -> (bool, bool) {
+    // The MPS INTI flags: polarity in bits 0-1, trigger mode in bits 2-3,
+    // where `1` is active-high or edge, `3` is active-low or level.
+    fn decode(bits: u16, default: bool) -> bool {
+        match bits & 3 {
+            1 => false,
+            3 => true,
+            _ => default,
+        }
+    }
+
+    let (mut low_active, mut level) = default;
+    for_each_madt_entry(|ty, entry| {
+        if ty == MADT_INT_SRC_OVERRIDE && read::<u8>(entry + 3) == irq {
+            let flags = read::<u16>(entry + 8);
+            low_active = decode(flags, default.0);
+            level = decode(flags >> 2, default.1);
+        }
+    });
+    (low_active, level)
+}
+
+/// Decodes the topology from the APIC ID, by the widths of the SMT and core
+/// fields reported by CPUID.
+fn apic_topology(apic_id: usize) -> CpuTopology {
+    let (mut smt_bits, mut core_bits) = (0, 0);
+    if let Some(levels) = CpuId::new().get_extended_topology_info() {
+        for level in levels {
+            match level.level_type() {
+                TopologyType::SMT => smt_bits = level.shift_right_for_next_apic_id(),
+                TopologyType::Core => core_bits = level.shift_right_for_next_apic_id(),
+                _ => {}
+            }
+        }
+    }
+    let core_bits = core_bits.max(smt_bits);
+    CpuTopology {
+        hw_id: apic_id,
+        package: (apic_id >> core_bits) as u16,
+        cluster: 0,
+        core: ((apic_id & ((1 << core_bits) - 1)) >> smt_bits) as u16,
+        thread: (apic_id & ((1 << smt_bits) - 1)) as u16,
+    }
+}
+
+/// Registers the CPUs in the MADT to [`topology`].
+///
+/// If there is no MADT, CPUs are assumed to have contiguous APIC IDs.
+pub fn init_topology() {
+    let found = for_each_madt_cpu(|apic_id| {
+        topology::register(apic_topology(apic_id));
+    });
+    if !found {
+        warn!("MADT is not found, assume contiguous APIC IDs");
+        topology::register_all((0..MAX_CPU_NUM).map(apic_topology));
+    }
+}
@@ -2,16 +2,23 @@
 
 use core::mem::MaybeUninit;
 
+#[cfg(feature = "irq")]
+use axplat::irq::{IrqError, IrqResult, TriggerMode};
 use axplat::mem::{PhysAddr, pa, phys_to_virt};
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use x2apic::ioapic::IoApic;
+#[cfg(feature = "irq")]
+use x2apic::ioapic::IrqFlags;
 use x2apic::lapic::{LocalApic, LocalApicBuilder, xapic_base};
 use x86_64::instructions::port::Port;
 
 use self::vectors::*;
 
 pub(super) mod vectors {
+    /// The vector of I/O APIC IRQ 0. I/O APIC IRQ `n` is delivered with vector
+    /// `IO_APIC_VECTOR_BASE + n`.
+    pub const IO_APIC_VECTOR_BASE: u8 = 0x20;
     pub const APIC_TIMER_VECTOR: u8 = 0xf0;
     pub const APIC_SPURIOUS_VECTOR: u8 = 0xf1;
     pub const APIC_ERROR_VECTOR: u8 = 0xf2;
@@ -23,21 +30,91 @@ static mut LOCAL_APIC: MaybeUninit<LocalApic> = MaybeUninit::uninit();
 static mut IS_X2APIC: bool = false;
 static IO_APIC: LazyInit<SpinNoIrq<IoApic>> = LazyInit::new();
 
+/// Returns the I/O APIC IRQ delivered with the given vector, or `None` if
+/// the vector is not for the I/O APIC (e.g., LAPIC interrupts).
+#[cfg(feature = "irq")]
+fn io_apic_irq(vector: usize) -> Option<u8> {
+    (IO_APIC_VECTOR_BASE as usize..APIC_TIMER_VECTOR as usize)
+        .contains(&vector)
+        .then(|| (vector - IO_APIC_VECTOR_BASE as usize) as u8)
+}
+
 /// Enables or disables the given IRQ.
 #[cfg(feature = "irq")]
 pub fn set_enable(vector: usize, enabled: bool) {
     // should not affect LAPIC interrupts
-    if vector < APIC_TIMER_VECTOR as _ {
+    if let Some(irq) = io_apic_irq(vector) {
         unsafe {
             if enabled {
-                IO_APIC.lock().enable_irq(vector as u8);
+                IO_APIC.lock().enable_irq(irq);
             } else {
-                IO_APIC.lock().disable_irq(vector as u8);
+                IO_APIC.lock().disable_irq(irq);
             }
         }
     }
 }
 
+/// Updates the I/O APIC redirection table entry of the given IRQ.
+#[cfg(feature = "irq")]
+fn update_io_apic_entry(
+    vector: usize,
+    f: impl FnOnce(&mut x2apic::ioapic::RedirectionTableEntry),
+) -> IrqResult {
+    // LAPIC interrupts are not routed through the I/O APIC
+    if vector >= APIC_TIMER_VECTOR as _ {
+        return Err(IrqError::Unsupported);
+    }
+    let irq = io_apic_irq(vector).ok_or(IrqError::InvalidInput)?;
+    let mut io_apic = IO_APIC.lock();
+    unsafe {
+        if irq > io_apic.max_table_entry() {
+            return Err(IrqError::InvalidInput);
+        }
+        let mut entry = io_apic.table_entry(irq);
+        f(&mut entry);
+        io_apic.set_table_entry(irq, entry);
+    }
+    Ok(())
+}
+
+/// Routes the given IRQ to the CPU in `cpu_mask`.
+///
+/// The I/O APIC delivers interrupts in physical destination mode, so exactly
+/// one target CPU is supported.
+#[cfg(feature = "irq")]
+pub fn set_affinity(vector: usize, cpu_mask: usize) -> IrqResult {
+    if cpu_mask == 0 {
+        return Err(IrqError::InvalidInput);
+    }
+    if !cpu_mask.is_power_of_two() {
+        return Err(IrqError::Unsupported);
+    }
+    let apic_id = axplat::power::topology::hw_id(cpu_mask.trailing_zeros() as usize)
+        .filter(|&id| id <= u8::MAX as usize)
+        .ok_or(IrqError::InvalidInput)?;
+    update_io_apic_entry(vector, |entry| entry.set_dest(apic_id as u8))
+}
+
+/// Sets the trigger mode of the given IRQ.
+#[cfg(feature = "irq")]
+pub fn set_trigger(vector: usize, mode: TriggerMode) -> IrqResult {
+    update_io_apic_entry(vector, |entry| {
+        let mut flags = entry.flags();
+        flags.set(IrqFlags::LEVEL_TRIGGERED, mode == TriggerMode::Level);
+        entry.set_flags(flags);
+    })
+}
+
+/// Sets the polarity of the given IRQ, which is active-high by default.
+#[cfg(all(feature = "irq", feature = "pci"))]
+pub fn set_low_active(vector: usize, low_active: bool) -> IrqResult {
+    update_io_apic_entry(vector, |entry| {
+        let mut flags = entry.flags();
+        flags.set(IrqFlags::LOW_ACTIVE, low_active);
+        entry.set_flags(flags);
+    })
+}
+
 #[cfg(any(feature = "smp", feature = "irq"))]
 #[allow(static_mut_refs)]
 pub fn local_apic<'a>() -> &'a mut LocalApic {
@@ -46,11 +123,11 @@ pub fn local_apic<'a>() -> &'a mut LocalApic {
 }
 
 #[cfg(any(feature = "smp", feature = "irq"))]
-pub fn raw_apic_id(id_u8: u8) -> u32 {
+pub fn raw_apic_id(apic_id: usize) -> u32 {
     if unsafe { IS_X2APIC } {
-        id_u8 as u32
+        apic_id as u32
     } else {
-        (id_u8 as u32) << 24
+        (apic_id as u32) << 24
     }
 }
 
@@ -93,7 +170,9 @@ pub fn init_primary() {
     }
 
     info!("Initialize IO APIC...");
-    let io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
+    let mut io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
+    // Assign vectors to all IRQs, and keep them masked until enabled.
+    unsafe { io_apic.init(IO_APIC_VECTOR_BASE) };
     IO_APIC.init_once(SpinNoIrq::new(io_apic));
 }
 
@@ -104,7 +183,10 @@ pub fn init_secondary() {
 
 #[cfg(feature = "irq")]
 mod irq_impl {
-    use axplat::irq::{HandlerTable, IpiTarget, IrqHandler, IrqIf};
+    use axplat::irq::{
+        HandlerTable, HandlerToken, IpiTarget, IrqContextHandler, IrqError, IrqIf, IrqResult,
+        TriggerMode, stats,
+    };
 
     /// The maximum number of IRQs.
     const MAX_IRQ_COUNT: usize = 256;
@@ -120,26 +202,36 @@ mod irq_impl {
             super::set_enable(vector, enabled);
         }
 
-        /// Registers an IRQ handler for the given IRQ.
+        /// Registers an IRQ handler with context for the given IRQ.
         ///
-        /// It also enables the IRQ if the registration succeeds. It returns `false` if
-        /// the registration failed.
-        fn register(vector: usize, handler: IrqHandler) -> bool {
-            if IRQ_HANDLER_TABLE.register_handler(vector, handler) {
-                Self::set_enable(vector, true);
-                return true;
+        /// It also enables the IRQ if the registration succeeds. It returns the
+        /// token to unregister the handler, or `None` if the registration failed.
+        fn register_with_context(
+            vector: usize,
+            handler: IrqContextHandler,
+            ctx: usize,
+        ) -> Option<HandlerToken> {
+            let token = IRQ_HANDLER_TABLE.register_handler_with_context(vector, handler, ctx);
+            match token {
+                Some(_) => Self::set_enable(vector, true),
+                None => warn!("register handler for IRQ {} failed", vector),
             }
-            warn!("register handler for IRQ {} failed", vector);
-            false
+            token
         }
 
-        /// Unregisters the IRQ handler for the given IRQ.
+        /// Unregisters the IRQ handler registered with the given token for the
+        /// given IRQ.
         ///
-        /// It also disables the IRQ if the unregistration succeeds. It returns the
-        /// existing handler if it is registered, `None` otherwise.
-        fn unregister(vector: usize) -> Option<IrqHandler> {
-            Self::set_enable(vector, false);
-            IRQ_HANDLER_TABLE.unregister_handler(vector)
+        /// The IRQ is disabled only if the last handler is unregistered. It
+        /// returns `true` if the handler was registered, `false` otherwise.
+        fn unregister(vector: usize, token: HandlerToken) -> bool {
+            if !IRQ_HANDLER_TABLE.unregister_handler(vector, token) {
+                return false;
+            }
+            if !IRQ_HANDLER_TABLE.is_registered(vector) {
+                Self::set_enable(vector, false);
+            }
+            true
         }
 
         /// Handles the IRQ.
@@ -149,7 +241,14 @@ mod irq_impl {
         /// also acknowledges the interrupt controller after handling.
         fn handle(vector: usize) {
             trace!("IRQ {}", vector);
-            if !IRQ_HANDLER_TABLE.handle(vector) {
+            if vector == super::APIC_SPURIOUS_VECTOR as usize {
+                // Spurious interrupts must not be acknowledged.
+                stats::record_spurious();
+                return;
+            }
+            let handled = IRQ_HANDLER_TABLE.handle(vector);
+            stats::record_irq(vector, handled);
+            if !handled {
                 warn!("Unhandled IRQ {vector}");
             }
             unsafe { super::local_apic().end_of_interrupt() };
@@ -157,6 +256,7 @@ mod irq_impl {
 
         /// Sends an inter-processor interrupt (IPI) to the specified target CPU or all CPUs.
         fn send_ipi(irq_num: usize, target: IpiTarget) {
+            stats::record_ipi_sent(irq_num);
             match target {
                 IpiTarget::Current { cpu_id: _ } => {
                     unsafe {
@@ -164,7 +264,11 @@ mod irq_impl {
                     };
                 }
                 IpiTarget::Other { cpu_id } => {
-                    let apic_id = super::raw_apic_id(cpu_id as u8);
+                    let Some(apic_id) = axplat::power::topology::hw_id(cpu_id) else {
+                        warn!("send_ipi: CPU {cpu_id} is not present");
+                        return;
+                    };
+                    let apic_id = super::raw_apic_id(apic_id);
                     unsafe {
                         super::local_apic().send_ipi(irq_num as _, apic_id as _);
                     };
@@ -181,5 +285,26 @@ mod irq_impl {
                 }
             }
         }
+
+        /// Routes the given IRQ to the CPUs in `cpu_mask`.
+        ///
+        /// Only one target CPU is supported, and LAPIC interrupts (e.g., the
+        /// timer) can not be routed.
+        fn set_affinity(vector: usize, cpu_mask: usize) -> IrqResult {
+            super::set_affinity(vector, cpu_mask)
+        }
+
+        /// Sets the priority of the given IRQ.
+        ///
+        /// It is not supported, as the priority of an interrupt on x86 is
+        /// determined by its vector number.
+        fn set_priority(_vector: usize, _priority: u8) -> IrqResult {
+            Err(IrqError::Unsupported)
+        }
+
+        /// Sets the trigger mode (edge or level) of the given IRQ.
+        fn set_trigger(vector: usize, mode: TriggerMode) -> IrqResult {
+            super::set_trigger(vector, mode)
+        }
     }
 }
@@ -1,11 +1,22 @@
 //! Uart 16550 serial port.
 
+#[cfg(feature = "irq")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use axplat::console::ConsoleIf;
 use kspin::SpinNoIrq;
 use uart_16550::SerialPort;
 
 static COM1: SpinNoIrq<SerialPort> = unsafe { SpinNoIrq::new(SerialPort::new(0x3f8)) };
 
+/// The vector of the COM1 IRQ (ISA IRQ 4).
+#[cfg(feature = "irq")]
+const COM1_IRQ: usize = crate::apic::vectors::IO_APIC_VECTOR_BASE as usize + 4;
+
+/// Whether the COM1 IRQ handler is registered.
+#[cfg(feature = "irq")]
+static IRQ_ENABLED: AtomicBool = AtomicBool::new(false);
+
 /// Writes a byte to the console.
 pub fn putchar(c: u8) {
     COM1.lock().send(c)
@@ -16,8 +27,40 @@ pub fn getchar() -> Option<u8> {
     COM1.lock().try_receive().ok()
 }
 
+/// Writes bytes to COM1 by a new `SerialPort` instance, bypassing [`COM1`].
+fn emergency_write_bytes(bytes: &[u8]) {
+    let mut com1 = unsafe { SerialPort::new(0x3f8) };
+    for c in bytes {
+        com1.send(*c);
+    }
+}
+
 pub fn init() {
+    // It also enables the "received data available" interrupt.
     COM1.lock().init();
+    axplat::console::set_emergency_write_fn(emergency_write_bytes);
+}
+
+/// COM1 IRQ handler, which drains the receive FIFO into
+/// [`axplat::console::rx`].
+#[cfg(feature = "console-irq")]
+fn irq_handler() {
+    {
+        let mut com1 = COM1.lock();
+        while let Ok(c) = com1.try_receive() {
+            axplat::console::rx::push(c);
+        }
+    }
+    axplat::console::rx::notify();
+}
+
+/// Switches the console input to the interrupt-driven mode.
+#[cfg(feature = "console-irq")]
+pub fn init_irq() {
+    if axplat::irq::register(COM1_IRQ, irq_handler).is_some() {
+        IRQ_ENABLED.store(true, Ordering::Release);
+        axplat::console::rx::enable();
+    }
 }
 
 struct ConsoleIfImpl;
@@ -35,6 +78,10 @@ impl ConsoleIf for ConsoleIfImpl {
     ///
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
+        #[cfg(feature = "irq")]
+        if axplat::console::rx::is_enabled() {
+            return axplat::console::rx::read(bytes);
+        }
         let mut read_len = 0;
         while read_len < bytes.len() {
             if let Some(c) = getchar() {
@@ -46,4 +93,11 @@ impl ConsoleIf for ConsoleIfImpl {
         }
         read_len
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the input is
+    /// polled only.
+    #[cfg(feature = "irq")]
+    fn irq_num() -> Option<usize> {
+        IRQ_ENABLED.load(Ordering::Acquire).then_some(COM1_IRQ)
+    }
 }
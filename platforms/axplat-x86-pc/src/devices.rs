@@ -0,0 +1,24 @@
+use axplat::devices::{Device, DeviceIf, DeviceKind, DeviceList};
+
+use crate::config::devices::{PCI_BUS_END, PCI_ECAM_BASE, VIRTIO_MMIO_RANGES};
+
+/// The devices in the static configuration.
+static DEVICES: DeviceList = DeviceList::new()
+    .with_each(DeviceKind::VirtioMmio, "virtio,mmio", VIRTIO_MMIO_RANGES)
+    // Each bus takes 1M bytes of the ECAM space.
+    .with(Device::new(
+        DeviceKind::PciHostBridge,
+        "pci-host-ecam-generic",
+        (PCI_ECAM_BASE, (PCI_BUS_END + 1) << 20),
+        None,
+    ));
+
+struct DeviceIfImpl;
+
+#[impl_interface]
+impl DeviceIf for DeviceIfImpl {
+    /// Returns the devices on the platform that drivers may use.
+    fn devices() -> &'static [Device] {
+        &DEVICES
+    }
+}
@@ -10,15 +10,26 @@ impl InitIf for InitIfImpl {
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
     fn init_early(_cpu_id: usize, mbi: usize) {
+        #[cfg(feature = "smp")]
+        crate::mp::CPU_STATES.set(_cpu_id, axplat::power::CpuState::On);
         axcpu::init::init_trap();
         crate::console::init();
         crate::time::init_early();
+        crate::power::init();
         crate::mem::init(mbi);
+        #[cfg(feature = "persistent-log")]
+        axplat::console::persistent_log::init(
+            axplat::mem::pa!(crate::config::plat::PERSISTENT_LOG_PADDR),
+            crate::config::plat::PERSISTENT_LOG_SIZE,
+        );
+        crate::acpi::init_topology();
+        axplat::mem::map::validate();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
     #[cfg(feature = "smp")]
-    fn init_early_secondary(_cpu_id: usize) {
+    fn init_early_secondary(cpu_id: usize) {
+        crate::mp::CPU_STATES.set(cpu_id, axplat::power::CpuState::On);
         axcpu::init::init_trap();
     }
 
@@ -30,6 +41,10 @@ impl InitIf for InitIfImpl {
     fn init_later(_cpu_id: usize, _arg: usize) {
         crate::apic::init_primary();
         crate::time::init_primary();
+        #[cfg(feature = "console-irq")]
+        crate::console::init_irq();
+        #[cfg(feature = "pci")]
+        crate::pci::init();
     }
 
     /// Initializes the platform at the later stage for secondary cores.
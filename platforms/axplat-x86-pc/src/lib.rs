@@ -5,11 +5,15 @@ extern crate log;
 #[macro_use]
 extern crate axplat;
 
+mod acpi;
 mod apic;
 mod boot;
 mod console;
+mod devices;
 mod init;
 mod mem;
+#[cfg(feature = "pci")]
+mod pci;
 mod power;
 mod time;
 
@@ -31,7 +35,7 @@ pub mod config {
     );
 }
 
-fn current_cpu_id() -> usize {
+fn current_apic_id() -> usize {
     match raw_cpuid::CpuId::new().get_feature_info() {
         Some(finfo) => finfo.initial_local_apic_id() as usize,
         None => 0,
@@ -40,13 +44,13 @@ fn current_cpu_id() -> usize {
 
 unsafe extern "C" fn rust_entry(magic: usize, mbi: usize) {
     if magic == self::boot::MULTIBOOT_BOOTLOADER_MAGIC {
-        axplat::call_main(current_cpu_id(), mbi);
+        axplat::call_main_hw(current_apic_id(), mbi);
     }
 }
 
 unsafe extern "C" fn rust_entry_secondary(_magic: usize) {
     #[cfg(feature = "smp")]
     if _magic == self::boot::MULTIBOOT_BOOTLOADER_MAGIC {
-        axplat::call_secondary_main(current_cpu_id());
+        axplat::call_secondary_main_hw(current_apic_id());
     }
 }
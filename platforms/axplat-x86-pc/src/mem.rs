@@ -1,17 +1,33 @@
 //! Physical memory information.
 
-use axplat::mem::{MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axplat::mem::{DmaRange, DmaZone, MemIf, PhysAddr, RawRange, VirtAddr, pa, va};
 use heapless::Vec;
 use lazyinit::LazyInit;
 use multiboot::information::{MemoryManagement, MemoryType, Multiboot, PAddr};
 
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::PHYS_VIRT_OFFSET;
+use crate::config::plat::{PHYS_BUS_OFFSET, PHYS_VIRT_OFFSET};
 
 const MAX_REGIONS: usize = 16;
 
+#[cfg(not(feature = "persistent-log"))]
+const RESERVED_RANGES: &[RawRange] = &[(0, 0x100000)];
+#[cfg(feature = "persistent-log")]
+const RESERVED_RANGES: &[RawRange] = &[
+    (0, 0x100000),
+    (
+        crate::config::plat::PERSISTENT_LOG_PADDR,
+        crate::config::plat::PERSISTENT_LOG_SIZE,
+    ),
+];
+
 static RAM_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
 
+/// The size of cache lines flushed by `clflush`, or `0` if not read yet.
+static CLFLUSH_LINE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
 pub fn init(multiboot_info_ptr: usize) {
     let mut mm = MemIfImpl;
     let info = unsafe { Multiboot::from_ptr(multiboot_info_ptr as _, &mut mm).unwrap() };
@@ -27,6 +43,26 @@ pub fn init(multiboot_info_ptr: usize) {
     RAM_REGIONS.init_once(regions);
 }
 
+/// Writes back and invalidates the cache lines that cover the range.
+fn clflush_range(vaddr: VirtAddr, size: usize) {
+    let mut line_size = CLFLUSH_LINE_SIZE.load(Ordering::Relaxed);
+    if line_size == 0 {
+        line_size = raw_cpuid::CpuId::new()
+            .get_feature_info()
+            .map(|info| info.cflush_cache_line_size() as usize * 8)
+            .filter(|&size| size != 0)
+            .unwrap_or(64);
+        CLFLUSH_LINE_SIZE.store(line_size, Ordering::Relaxed);
+    }
+    let end = vaddr.as_usize() + size;
+    let mut line = vaddr.as_usize() & !(line_size - 1);
+    while line < end {
+        unsafe { core::arch::asm!("clflush [{}]", in(reg) line) };
+        line += line_size;
+    }
+    unsafe { core::arch::asm!("mfence") };
+}
+
 struct MemIfImpl;
 
 impl MemoryManagement for MemIfImpl {
@@ -51,9 +87,10 @@ impl MemIf for MemIfImpl {
 
     /// Returns all reserved physical memory ranges on the platform.
     ///
-    /// Lower 1MiB memory is reserved and not allocatable.
+    /// Lower 1MiB memory is reserved and not allocatable, so is the console
+    /// log buffer with the `persistent-log` feature.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[(0, 0x100000)]
+        RESERVED_RANGES
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
@@ -70,4 +107,48 @@ impl MemIf for MemIfImpl {
     fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
         pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
     }
+
+    /// Whether the DMA of devices is coherent with the CPU caches.
+    ///
+    /// Devices snoop the CPU caches on x86.
+    fn dma_coherent() -> bool {
+        true
+    }
+
+    /// Cleans the data cache lines that cover the given virtual address range
+    /// by `clflush`.
+    fn cache_clean_range(vaddr: VirtAddr, size: usize) {
+        clflush_range(vaddr, size)
+    }
+
+    /// Invalidates the data cache lines that cover the given virtual address
+    /// range by `clflush`, which also writes back the dirty lines.
+    fn cache_invalidate_range(vaddr: VirtAddr, size: usize) {
+        clflush_range(vaddr, size)
+    }
+
+    /// Cleans and invalidates the data cache lines that cover the given
+    /// virtual address range by `clflush`.
+    fn cache_flush_range(vaddr: VirtAddr, size: usize) {
+        clflush_range(vaddr, size)
+    }
+
+    /// Returns the windows through which devices access physical memory by
+    /// DMA.
+    ///
+    /// All physical memory is accessed at the bus addresses offset by
+    /// `PHYS_BUS_OFFSET`.
+    fn dma_ranges() -> &'static [DmaRange] {
+        &[DmaRange::new(0, PHYS_BUS_OFFSET, usize::MAX)]
+    }
+
+    /// Returns the end physical address (exclusive) of the given DMA zone.
+    ///
+    /// Legacy ISA devices only reach the first 16 MiB of memory.
+    fn dma_zone_end(zone: DmaZone) -> Option<usize> {
+        match zone {
+            DmaZone::Dma => Some(0x100_0000),
+            DmaZone::Dma32 => Some(0x1_0000_0000),
+        }
+    }
 }
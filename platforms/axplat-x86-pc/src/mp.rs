@@ -1,11 +1,15 @@
 //! Multi-processor booting.
 
 use axplat::mem::{PAGE_SIZE_4K, PhysAddr, pa};
+use axplat::power::{CpuBootError, CpuBootResult, CpuState, CpuStateTable, topology};
 use axplat::time::{Duration, busy_wait};
 
 const START_PAGE_IDX: u8 = 6;
 const START_PAGE_PADDR: PhysAddr = pa!(START_PAGE_IDX as usize * PAGE_SIZE_4K);
 
+/// The power states of CPUs.
+pub static CPU_STATES: CpuStateTable<{ crate::config::plat::MAX_CPU_NUM }> = CpuStateTable::new();
+
 core::arch::global_asm!(
     include_str!("ap_start.S"),
     start_page_paddr = const START_PAGE_PADDR.as_usize(),
@@ -33,10 +37,16 @@ unsafe fn setup_startup_page(stack_top: PhysAddr) {
 }
 
 /// Starts the given secondary CPU with its boot stack.
-pub fn start_secondary_cpu(apic_id: usize, stack_top: PhysAddr) {
+///
+/// The CPU must be off, i.e., it has not been started, or has been taken
+/// offline by [`park_current_cpu`].
+pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) -> CpuBootResult {
+    let apic_id = topology::hw_id(cpu_id).ok_or(CpuBootError::NotPresent)?;
+    // The INIT IPI resets a running CPU, do not send it to an online one.
+    CPU_STATES.start_boot(cpu_id)?;
     unsafe { setup_startup_page(stack_top) };
 
-    let apic_id = super::apic::raw_apic_id(apic_id as u8);
+    let apic_id = super::apic::raw_apic_id(apic_id);
     let lapic = super::apic::local_apic();
 
     // INIT-SIPI-SIPI Sequence
@@ -46,4 +56,19 @@ pub fn start_secondary_cpu(apic_id: usize, stack_top: PhysAddr) {
     unsafe { lapic.send_sipi(START_PAGE_IDX, apic_id) };
     busy_wait(Duration::from_micros(200)); // 200us
     unsafe { lapic.send_sipi(START_PAGE_IDX, apic_id) };
+    Ok(())
+}
+
+/// Takes the current CPU offline.
+///
+/// It halts with interrupts disabled, and only wakes up by the INIT IPI sent
+/// by [`start_secondary_cpu`].
+pub fn park_current_cpu() -> ! {
+    axcpu::asm::disable_irqs();
+    // The CPU has entered the kernel, so it must have been registered.
+    let cpu_id = topology::cpu_id(crate::current_apic_id()).unwrap();
+    CPU_STATES.set(cpu_id, CpuState::Off);
+    loop {
+        axcpu::asm::halt();
+    }
 }
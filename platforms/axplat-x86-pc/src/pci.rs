@@ -0,0 +1,109 @@
+use axplat::pci::{ConfigAccess, HostBridge, IntxRouting, PciAddress, PciWindows};
+use kspin::SpinNoIrq;
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+use crate::apic::vectors::IO_APIC_VECTOR_BASE;
+use crate::config::devices::{PCI_BUS_END, PCI_RANGES};
+
+const CONFIG_ADDRESS_PORT: u16 = 0xcf8;
+const CONFIG_DATA_PORT: u16 = 0xcfc;
+
+/// The legacy configuration access through I/O ports `0xcf8` and `0xcfc`,
+/// which only reaches the first 256 bytes of the configuration space.
+struct PortIoAccess {
+    ports: SpinNoIrq<(PortWriteOnly<u32>, Port<u32>)>,
+}
+
+impl PortIoAccess {
+    const fn new() -> Self {
+        Self {
+            ports: SpinNoIrq::new((
+                PortWriteOnly::new(CONFIG_ADDRESS_PORT),
+                Port::new(CONFIG_DATA_PORT),
+            )),
+        }
+    }
+
+    fn config_address(addr: PciAddress, offset: u16) -> u32 {
+        (1 << 31)
+            | (addr.bus as u32) << 16
+            | (addr.device as u32) << 11
+            | (addr.function as u32) << 8
+            | (offset as u32 & 0xfc)
+    }
+}
+
+impl ConfigAccess for PortIoAccess {
+    fn read(&self, addr: PciAddress, offset: u16) -> u32 {
+        if offset >= 0x100 {
+            return u32::MAX;
+        }
+        let mut ports = self.ports.lock();
+        unsafe {
+            ports.0.write(Self::config_address(addr, offset));
+            ports.1.read()
+        }
+    }
+
+    fn write(&self, addr: PciAddress, offset: u16, value: u32) {
+        if offset >= 0x100 {
+            return;
+        }
+        let mut ports = self.ports.lock();
+        unsafe {
+            ports.0.write(Self::config_address(addr, offset));
+            ports.1.write(value);
+        }
+    }
+}
+
+static PORT_IO: PortIoAccess = PortIoAccess::new();
+
+/// Sets up the I/O APIC entry of a PCI INTx IRQ.
+///
+/// INTx pins are level-triggered and active-low, unless the MADT overrides
+/// the ISA IRQ that they are routed to (e.g., QEMU reports them as
+/// active-high).
+#[cfg(feature = "irq")]
+fn set_intx_mode(vector: usize) {
+    use axplat::irq::TriggerMode;
+
+    let irq = (vector - IO_APIC_VECTOR_BASE as usize) as u8;
+    let (low_active, level) = crate::acpi::isa_irq_mode(irq, (true, true));
+    let mode = if level {
+        TriggerMode::Level
+    } else {
+        TriggerMode::Edge
+    };
+    if let Err(e) = crate::apic::set_trigger(vector, mode)
+        .and_then(|_| crate::apic::set_low_active(vector, low_active))
+    {
+        warn!("failed to set up the PCI IRQ {irq}: {e:?}");
+    }
+}
+
+/// Enumerates the PCI functions through the legacy port I/O access.
+///
+/// The bus numbers and BARs assigned by the firmware are kept (`pci-ranges` is
+/// empty), and the INTx IRQs are the I/O APIC vectors of the Interrupt Line
+/// registers set by the firmware.
+pub fn init() {
+    let bridge = HostBridge::new(
+        &PORT_IO,
+        PCI_BUS_END as u8,
+        PciWindows::from_ranges(PCI_RANGES),
+        IntxRouting::InterruptLine {
+            offset: IO_APIC_VECTOR_BASE as usize,
+        },
+    );
+    for dev in axplat::pci::init(bridge) {
+        debug!(
+            "PCI {}: {:04x}:{:04x}, class {:02x}{:02x}",
+            dev.addr, dev.vendor_id, dev.device_id, dev.class, dev.subclass
+        );
+        #[cfg(feature = "irq")]
+        if let Some(vector) = dev.irq {
+            set_intx_mode(vector);
+        }
+    }
+}
@@ -1,8 +1,76 @@
 //! Power management.
 
-use axplat::power::PowerIf;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use axplat::power::{IdleState, PowerIf, ResetKind, ResetReason};
+use raw_cpuid::CpuId;
 use x86_64::instructions::port::PortWriteOnly;
 
+/// The idle states. C1 is entered by `hlt`, and the deeper ones are entered by
+/// `mwait` with the hints in [`MWAIT_HINTS`].
+const IDLE_STATES: [IdleState; 3] = [
+    IdleState {
+        name: "C1",
+        entry_latency_ns: 1_000,
+        exit_latency_ns: 1_000,
+        min_residency_ns: 2_000,
+    },
+    IdleState {
+        name: "C2",
+        entry_latency_ns: 20_000,
+        exit_latency_ns: 20_000,
+        min_residency_ns: 100_000,
+    },
+    IdleState {
+        name: "C3",
+        entry_latency_ns: 100_000,
+        exit_latency_ns: 100_000,
+        min_residency_ns: 500_000,
+    },
+];
+
+const MWAIT_HINTS: [u32; 3] = [0x00, 0x10, 0x20];
+
+/// The number of idle states supported by the CPU.
+static NUM_IDLE_STATES: AtomicUsize = AtomicUsize::new(1);
+
+/// Detects the C-states that can be entered by `mwait`.
+pub fn init() {
+    let cpuid = CpuId::new();
+    if !cpuid
+        .get_feature_info()
+        .is_some_and(|info| info.has_monitor_mwait())
+    {
+        return;
+    }
+    // `mwait` must be able to wake up on interrupts even if they are disabled.
+    let Some(info) = cpuid
+        .get_monitor_mwait_info()
+        .filter(|info| info.extensions_supported() && info.interrupts_as_break_event())
+    else {
+        return;
+    };
+    let num = if info.supported_c2_states() == 0 {
+        1
+    } else if info.supported_c3_states() == 0 {
+        2
+    } else {
+        3
+    };
+    NUM_IDLE_STATES.store(num, Ordering::Relaxed);
+}
+
+/// Waits for interrupts by `mwait` with the given hint.
+fn mwait(hint: u32) {
+    /// The address range to monitor, which is never written.
+    static MONITOR_LINE: AtomicU64 = AtomicU64::new(0);
+    unsafe {
+        core::arch::asm!("monitor", in("rax") MONITOR_LINE.as_ptr(), in("ecx") 0, in("edx") 0);
+        // ECX bit 0: treat interrupts as break events even if disabled.
+        core::arch::asm!("mwait", in("eax") hint, in("ecx") 1);
+    }
+}
+
 struct PowerImpl;
 
 #[impl_interface]
@@ -13,28 +81,63 @@ impl PowerIf for PowerImpl {
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
     #[cfg(feature = "smp")]
-    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
+    fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) -> axplat::power::CpuBootResult {
         use axplat::mem::pa;
         crate::mp::start_secondary_cpu(cpu_id, pa!(stack_top_paddr))
     }
 
+    /// Takes the calling CPU offline.
+    ///
+    /// The CPU is halted until it is started again by the INIT-SIPI-SIPI
+    /// sequence in [`cpu_boot`](Self::cpu_boot).
+    #[cfg(feature = "smp")]
+    fn cpu_off() -> ! {
+        crate::mp::park_current_cpu()
+    }
+
+    /// Returns the power state of the given CPU.
+    #[cfg(feature = "smp")]
+    fn cpu_state(cpu_id: usize) -> Option<axplat::power::CpuState> {
+        crate::mp::CPU_STATES.get(cpu_id)
+    }
+
+    /// Returns the idle states that CPUs can enter.
+    fn idle_states() -> &'static [IdleState] {
+        &IDLE_STATES[..NUM_IDLE_STATES.load(Ordering::Relaxed)]
+    }
+
+    /// Enters the given idle state by `hlt` or `mwait`.
+    ///
+    /// Interrupts are enabled during `hlt`, so the interrupt that wakes up the
+    /// CPU is handled before returning.
+    fn cpu_idle(index: usize) {
+        if (1..NUM_IDLE_STATES.load(Ordering::Relaxed)).contains(&index) {
+            mwait(MWAIT_HINTS[index]);
+        } else {
+            // `hlt` never wakes up if interrupts are disabled, and `sti` takes
+            // effect after `hlt`, so no interrupt is missed.
+            unsafe { core::arch::asm!("sti; hlt; cli") };
+        }
+    }
+
     /// Shutdown the whole system (in QEMU).
     ///
     /// See <https://wiki.osdev.org/Shutdown> for more information.
     fn system_off() -> ! {
-        info!("Shutting down...");
-
         // For real hardware platforms, using port `0x604` to shutdown does not
-        // work. Therefore we use port `0x64` to reboot the system instead.
+        // work. Therefore we reboot the system instead.
         if cfg!(feature = "reboot-on-system-off") {
             axplat::console_println!("System will reboot, press any key to continue ...");
-            while super::console::getchar().is_none() {}
-            axplat::console_println!("Rebooting ...");
-            unsafe { PortWriteOnly::new(0x64).write(0xfeu8) };
-        } else {
-            unsafe { PortWriteOnly::new(0x604).write(0x2000u16) };
+            // The key may have been taken by the console IRQ handler.
+            let key_pressed =
+                || axplat::console::read_bytes(&mut [0]) > 0 || super::console::getchar().is_some();
+            while !key_pressed() {}
+            Self::system_reset(ResetKind::Cold);
         }
 
+        info!("Shutting down...");
+        unsafe { PortWriteOnly::new(0x604).write(0x2000u16) };
+
         axcpu::asm::halt();
         warn!("It should shutdown!");
         loop {
@@ -42,8 +145,48 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Resets the whole system by the reset control register (port `0xcf9`),
+    /// or the keyboard controller if it does not work.
+    ///
+    /// See <https://wiki.osdev.org/Reboot> for more information.
+    fn system_reset(kind: ResetKind) -> ! {
+        /// Reset control register: system reset (bit 1) and reset CPU (bit 2).
+        const RST_CNT_SYS_RST: u8 = 0x02;
+        const RST_CNT_RST_CPU: u8 = 0x04;
+        /// Reset control register: full reset (bit 3), power cycles the system.
+        const RST_CNT_FULL_RST: u8 = 0x08;
+
+        info!("Resetting ({kind:?})...");
+        let rst_cnt = match kind {
+            ResetKind::Cold => RST_CNT_FULL_RST | RST_CNT_SYS_RST,
+            ResetKind::Warm => RST_CNT_SYS_RST,
+        };
+        unsafe {
+            // The reset is triggered by the rising edge of `RST_CPU`.
+            let mut port = PortWriteOnly::<u8>::new(0xcf9);
+            port.write(rst_cnt);
+            port.write(rst_cnt | RST_CNT_RST_CPU);
+        }
+        axplat::time::busy_wait(core::time::Duration::from_millis(50));
+
+        // Pulse the CPU reset line by the keyboard controller.
+        unsafe { PortWriteOnly::new(0x64).write(0xfeu8) };
+        axplat::time::busy_wait(core::time::Duration::from_millis(50));
+
+        warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns the reason for the last reset, which is not known.
+    fn reset_reason() -> Option<ResetReason> {
+        None
+    }
+
     /// Get the number of CPU cores available on this platform.
     fn cpu_num() -> usize {
-        crate::config::plat::MAX_CPU_NUM
+        use crate::config::plat::MAX_CPU_NUM;
+        axplat::power::topology::cpu_num().clamp(1, MAX_CPU_NUM)
     }
 }
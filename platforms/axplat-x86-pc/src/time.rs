@@ -2,6 +2,8 @@
 //!
 //! Currently, the TSC is used as the clock source.
 
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use axplat::time::TimeIf;
 use raw_cpuid::CpuId;
 
@@ -18,7 +20,7 @@ static mut INIT_TICK: u64 = 0;
 static mut CPU_FREQ_MHZ: u64 = crate::config::devices::TIMER_FREQUENCY as u64 / 1_000_000;
 
 /// RTC wall time offset in nanoseconds at monotonic time base.
-static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+static RTC_EPOCHOFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
 
 pub fn init_early() {
     if let Some(freq) = CpuId::new()
@@ -42,12 +44,146 @@ pub fn init_early() {
         // Get the current time in microseconds since the epoch (1970-01-01) from the x86 RTC.
         // Subtract the timer ticks to get the actual time when ArceOS was booted.
         let eopch_time_nanos = Rtc::new().get_unix_timestamp() * 1_000_000_000;
+        RTC_EPOCHOFFSET_NANOS.store(
+            eopch_time_nanos - axplat::time::ticks_to_nanos(unsafe { INIT_TICK }),
+            Ordering::Release,
+        );
+    }
+}
+
+/// Alarm of the CMOS RTC.
+#[cfg(all(feature = "irq", feature = "rtc"))]
+mod rtc_alarm {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use kspin::SpinNoIrq;
+    use x86_64::instructions::port::Port;
+
+    use crate::apic::vectors::IO_APIC_VECTOR_BASE;
+
+    /// The vector of the RTC IRQ (ISA IRQ 8).
+    const RTC_VECTOR: usize = IO_APIC_VECTOR_BASE as usize + 8;
+
+    const CMOS_SECONDS_ALARM: u8 = 0x01;
+    const CMOS_MINUTES_ALARM: u8 = 0x03;
+    const CMOS_HOURS_ALARM: u8 = 0x05;
+    const CMOS_STATUS_B: u8 = 0x0b;
+    const CMOS_STATUS_C: u8 = 0x0c;
+
+    /// Hours are in 24-hour format.
+    const STATUS_B_24_HOUR: u8 = 1 << 1;
+    /// Values are in binary instead of BCD.
+    const STATUS_B_BINARY: u8 = 1 << 2;
+    /// Alarm interrupt enable.
+    const STATUS_B_AIE: u8 = 1 << 5;
+    /// Alarm interrupt flag.
+    const STATUS_C_AF: u8 = 1 << 5;
+
+    const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+    /// The lock of the CMOS index and data ports.
+    static CMOS_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+    static ENABLED: AtomicBool = AtomicBool::new(false);
+
+    fn read_cmos(reg: u8) -> u8 {
+        unsafe {
+            Port::<u8>::new(0x70).write(reg);
+            Port::<u8>::new(0x71).read()
+        }
+    }
+
+    fn write_cmos(reg: u8, value: u8) {
         unsafe {
-            RTC_EPOCHOFFSET_NANOS = eopch_time_nanos - axplat::time::ticks_to_nanos(INIT_TICK);
+            Port::<u8>::new(0x70).write(reg);
+            Port::<u8>::new(0x71).write(value);
+        }
+    }
+
+    fn ack(_irq: usize, _ctx: usize) -> bool {
+        let _guard = CMOS_LOCK.lock();
+        // Reading status register C clears the interrupt flags.
+        read_cmos(CMOS_STATUS_C) & STATUS_C_AF != 0
+    }
+
+    pub fn init() {
+        if axplat::irq::register_with_context(RTC_VECTOR, ack, 0).is_some() {
+            ENABLED.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn irq() -> Option<usize> {
+        ENABLED.load(Ordering::Acquire).then_some(RTC_VECTOR)
+    }
+
+    /// Sets the alarm, which matches the time of day, so it must be within
+    /// one day.
+    pub fn set(wall_time_nanos: u64) -> bool {
+        if irq().is_none() {
+            return false;
+        }
+        let secs = wall_time_nanos.div_ceil(axplat::time::NANOS_PER_SEC);
+        let now_secs = axplat::time::wall_time_nanos() / axplat::time::NANOS_PER_SEC;
+        if secs.saturating_sub(now_secs) >= SECS_PER_DAY {
+            return false;
+        }
+        let time_of_day = secs % SECS_PER_DAY;
+        let (hour, minute, second) = (
+            (time_of_day / 3600) as u8,
+            (time_of_day / 60 % 60) as u8,
+            (time_of_day % 60) as u8,
+        );
+
+        let _guard = CMOS_LOCK.lock();
+        let status_b = read_cmos(CMOS_STATUS_B);
+        let encode = |value: u8| {
+            if status_b & STATUS_B_BINARY != 0 {
+                value
+            } else {
+                ((value / 10) << 4) | (value % 10)
+            }
+        };
+        let hour = if status_b & STATUS_B_24_HOUR != 0 {
+            encode(hour)
+        } else {
+            // 12-hour format, with bit 7 set for PM.
+            let pm = if hour >= 12 { 0x80 } else { 0 };
+            encode(match hour % 12 {
+                0 => 12,
+                h => h,
+            }) | pm
+        };
+        write_cmos(CMOS_STATUS_B, status_b & !STATUS_B_AIE);
+        write_cmos(CMOS_SECONDS_ALARM, encode(second));
+        write_cmos(CMOS_MINUTES_ALARM, encode(minute));
+        write_cmos(CMOS_HOURS_ALARM, hour);
+        read_cmos(CMOS_STATUS_C);
+        write_cmos(CMOS_STATUS_B, status_b | STATUS_B_AIE);
+        true
+    }
+
+    pub fn cancel() {
+        if irq().is_some() {
+            let _guard = CMOS_LOCK.lock();
+            let status_b = read_cmos(CMOS_STATUS_B);
+            write_cmos(CMOS_STATUS_B, status_b & !STATUS_B_AIE);
+            read_cmos(CMOS_STATUS_C);
         }
     }
 }
 
+#[cfg(all(feature = "irq", not(feature = "rtc")))]
+mod rtc_alarm {
+    pub fn irq() -> Option<usize> {
+        None
+    }
+
+    pub fn set(_wall_time_nanos: u64) -> bool {
+        false
+    }
+
+    pub fn cancel() {}
+}
+
 pub fn init_primary() {
     #[cfg(feature = "irq")]
     unsafe {
@@ -63,6 +199,9 @@ pub fn init_primary() {
             axplat::time::NANOS_PER_SEC as u32,
         );
     }
+
+    #[cfg(all(feature = "irq", feature = "rtc"))]
+    rtc_alarm::init();
 }
 
 #[cfg(feature = "smp")]
@@ -95,7 +234,20 @@ impl TimeIf for TimeIfImpl {
     /// Return epoch offset in nanoseconds (wall time offset to monotonic
     /// clock start).
     fn epochoffset_nanos() -> u64 {
-        unsafe { RTC_EPOCHOFFSET_NANOS }
+        RTC_EPOCHOFFSET_NANOS.load(Ordering::Acquire)
+    }
+
+    /// Sets the wall time (also known as realtime) in nanoseconds since
+    /// epoch.
+    ///
+    /// It also writes the new time back to the CMOS RTC if the `rtc` feature
+    /// is enabled.
+    fn set_wall_time_nanos(nanos: u64) {
+        let monotonic_nanos = Self::ticks_to_nanos(Self::current_ticks());
+        RTC_EPOCHOFFSET_NANOS.store(nanos.saturating_sub(monotonic_nanos), Ordering::Release);
+
+        #[cfg(feature = "rtc")]
+        x86_rtc::Rtc::new().set_unix_timestamp(nanos / axplat::time::NANOS_PER_SEC);
     }
 
     /// Set a one-shot timer.
@@ -116,4 +268,27 @@ impl TimeIf for TimeIfImpl {
             }
         }
     }
+
+    /// Returns the IRQ number of the RTC alarm, or `None` if the platform has
+    /// no RTC alarm.
+    #[cfg(feature = "irq")]
+    fn rtc_alarm_irq() -> Option<usize> {
+        rtc_alarm::irq()
+    }
+
+    /// Sets the RTC alarm at the specified wall time (in nanoseconds since
+    /// epoch).
+    ///
+    /// It uses the CMOS RTC, whose resolution is one second. As the alarm
+    /// matches the time of day, the wall time must be within one day.
+    #[cfg(feature = "irq")]
+    fn set_rtc_alarm_nanos(wall_time_nanos: u64) -> bool {
+        rtc_alarm::set(wall_time_nanos)
+    }
+
+    /// Cancels the pending RTC alarm, if any.
+    #[cfg(feature = "irq")]
+    fn cancel_rtc_alarm() {
+        rtc_alarm::cancel()
+    }
 }